@@ -0,0 +1 @@
+ALTER TABLE scripting_schema DROP COLUMN document;
@@ -0,0 +1 @@
+ALTER TABLE scripting_schema ADD COLUMN document JSONB;
@@ -0,0 +1,28 @@
+-- world_output is a cache of the last ticks, see up.sql. Rows of different users share their
+-- (queen_tag, world_time), so they can not satisfy the restored unique constraint and are dropped.
+DELETE FROM world_output;
+
+ALTER TABLE world_output DROP CONSTRAINT IF EXISTS world_output_queen_tag_world_time_user_id_key;
+ALTER TABLE world_output DROP COLUMN IF EXISTS user_id;
+ALTER TABLE world_output ADD CONSTRAINT world_output_queen_tag_world_time_key
+    UNIQUE (queen_tag, world_time);
+
+CREATE OR REPLACE FUNCTION on_world_ouput_insert () RETURNS TRIGGER
+AS $$
+BEGIN
+    DELETE FROM world_output
+    WHERE
+        id NOT IN (
+            SELECT foo.id
+            FROM (
+                SELECT id
+                FROM world_output
+                ORDER BY created DESC
+                -- TODO this should consider the queen_tag as well...
+                LIMIT 200
+            ) foo
+        );
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
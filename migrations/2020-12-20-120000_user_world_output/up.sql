@@ -0,0 +1,27 @@
+-- world_output is a cache of the last ticks, rewritten by the worker every tick, clients only
+-- read its latest rows. Old rows hold the whole world, unfit to be served to a single user, and
+-- can not be assigned to a user, so they are dropped. The worker repopulates the table on its next
+-- tick.
+DELETE FROM world_output;
+
+ALTER TABLE world_output ADD COLUMN user_id UUID NOT NULL;
+ALTER TABLE world_output DROP CONSTRAINT world_output_queen_tag_world_time_key;
+ALTER TABLE world_output ADD CONSTRAINT world_output_queen_tag_world_time_user_id_key
+    UNIQUE (queen_tag, world_time, user_id);
+
+-- keep the last 200 ticks, regardless of the number of users.
+-- `world_cleanup` runs once per statement, the worker inserts every user of a tick in one.
+CREATE OR REPLACE FUNCTION on_world_ouput_insert () RETURNS TRIGGER
+AS $$
+BEGIN
+    DELETE FROM world_output o
+    WHERE
+        o.world_time < (
+            SELECT MAX(latest.world_time)
+            FROM world_output latest
+            WHERE latest.queen_tag = o.queen_tag
+        ) - 200;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
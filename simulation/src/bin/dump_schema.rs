@@ -0,0 +1,24 @@
+//! Dump the versioned scripting schema as JSON.
+//!
+//! Usage: `cao-dump-schema [output.json]`
+//!
+//! Writes to stdout if no output file is given.
+use caolo_sim::scripting_api::schema::export_schema;
+use std::{env, fs, process};
+
+fn run() -> anyhow::Result<()> {
+    let schema = export_schema();
+    let payload = serde_json::to_string_pretty(&schema)?;
+    match env::args().nth(1) {
+        Some(path) => fs::write(path, payload)?,
+        None => println!("{}", payload),
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
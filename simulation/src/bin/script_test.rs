@@ -0,0 +1,35 @@
+//! Run a Cao-lang program against a fixture world and print the resulting intents and logs.
+//!
+//! Usage: `cao-script-test <program.json> <fixture.json> <entity id>`
+//!
+//! See `caolo_sim::harness` for the fixture format.
+use caolo_sim::harness::{run_script, Fixture};
+use caolo_sim::prelude::EntityId;
+use std::{env, fs, process};
+
+const USAGE: &str = "Usage: cao-script-test <program.json> <fixture.json> <entity id>";
+
+fn run() -> anyhow::Result<()> {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let (program, fixture, entity_id) = match args.as_slice() {
+        [program, fixture, entity_id] => (program, fixture, entity_id),
+        _ => anyhow::bail!(USAGE),
+    };
+
+    let program = fs::read_to_string(program)?;
+    let program = serde_json::from_str(&program)?;
+    let fixture = fs::read_to_string(fixture)?;
+    let fixture: Fixture = serde_json::from_str(&fixture)?;
+    let entity_id = EntityId(entity_id.parse()?);
+
+    let output = run_script(None, program, &fixture, entity_id)?;
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
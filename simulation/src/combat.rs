@@ -0,0 +1,60 @@
+//! Damage rules of attacks.
+//!
+//! Attacks of a tick are resolved simultaneously: every attack's damage is computed first, then
+//! applied to the defenders at once, so the order of the intents does not matter.
+//!
+use crate::components::{ArmourComponent, DamageType, RangedAttackComponent};
+
+pub const MELEE_RANGE: u32 = 1;
+
+/// Damage of a ranged attack on a target `distance` tiles away, before the target's armour
+pub fn ranged_damage(attack: &RangedAttackComponent, distance: u32) -> u16 {
+    if distance > attack.range {
+        return 0;
+    }
+    let tiles = distance.saturating_sub(1).min(u16::MAX as u32) as u16;
+    attack
+        .strength
+        .saturating_sub(attack.falloff.saturating_mul(tiles))
+}
+
+/// Damage taken by a defender with the given armour
+pub fn mitigate_damage(damage: u16, ty: DamageType, armour: Option<&ArmourComponent>) -> u16 {
+    let armour = match armour {
+        Some(a) => a,
+        None => return damage,
+    };
+    let resistance = armour.resistances.get(&ty).copied().unwrap_or(0).min(100) as u32;
+    let damage = damage as u32 * (100 - resistance) / 100;
+    (damage as u16).saturating_sub(armour.armour)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranged_damage_falls_off_with_distance() {
+        let attack = RangedAttackComponent {
+            strength: 20,
+            range: 3,
+            falloff: 5,
+            damage_type: DamageType::Kinetic,
+        };
+        assert_eq!(ranged_damage(&attack, 1), 20);
+        assert_eq!(ranged_damage(&attack, 3), 10);
+        assert_eq!(ranged_damage(&attack, 4), 0);
+    }
+
+    #[test]
+    fn armour_applies_after_resistances() {
+        let armour = ArmourComponent {
+            armour: 2,
+            resistances: [(DamageType::Thermal, 50)].iter().cloned().collect(),
+        };
+        assert_eq!(mitigate_damage(20, DamageType::Thermal, Some(&armour)), 8);
+        assert_eq!(mitigate_damage(20, DamageType::Kinetic, Some(&armour)), 18);
+        assert_eq!(mitigate_damage(1, DamageType::Kinetic, Some(&armour)), 0);
+        assert_eq!(mitigate_damage(20, DamageType::Kinetic, None), 20);
+    }
+}
@@ -1,11 +1,15 @@
 pub mod game_config;
 
 mod bot_components;
+mod remains;
 mod resources;
 mod rooms;
+mod structures;
 pub use bot_components::*;
+pub use remains::*;
 pub use resources::*;
 pub use rooms::*;
+pub use structures::*;
 
 use crate::{
     indices::{EntityId, Room, UserId, WorldPosition},
@@ -16,7 +20,7 @@ use crate::{
 };
 use cao_lang::{prelude::CompiledProgram, vm::HistoryEntry};
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{BTreeSet, VecDeque};
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ScriptHistoryEntry {
@@ -25,12 +29,49 @@ pub struct ScriptHistoryEntry {
     pub time: u64,
 }
 
+/// Ring buffer of the last N ticks of script execution history of an entity.
+/// N is configured by `GameConfig::script_history_len`.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct ScriptHistory(pub Vec<HistoryEntry>);
+pub struct ScriptHistory(pub VecDeque<ScriptHistoryEntry>);
 impl Component<EntityId> for ScriptHistory {
     type Table = DenseVecTable<EntityId, Self>;
 }
 
+impl ScriptHistory {
+    /// Push a new entry and drop the oldest ones, so at most `capacity` entries remain.
+    pub fn push(&mut self, entry: ScriptHistoryEntry, capacity: usize) {
+        self.0.push_back(entry);
+        while self.0.len() > capacity {
+            self.0.pop_front();
+        }
+    }
+
+    /// Iterate over the entries recorded in the `[from, to]` tick range (inclusive)
+    pub fn range(&self, from: u64, to: u64) -> impl Iterator<Item = &ScriptHistoryEntry> {
+        self.0
+            .iter()
+            .filter(move |entry| from <= entry.time && entry.time <= to)
+    }
+}
+
+/// Script execution history of a deleted entity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadScriptHistory {
+    pub entity_id: EntityId,
+    /// Tick of the deletion
+    pub died: u64,
+    pub history: ScriptHistory,
+}
+
+/// Script execution histories of the deleted entities of a user.
+/// Kept for `GameConfig::dead_script_history_ttl` ticks after the deletion of the entity.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DeadScriptHistories(pub Vec<DeadScriptHistory>);
+impl Component<UserId> for DeadScriptHistories {
+    type Table = BTreeTable<UserId, Self>;
+}
+
 /// For tables that store entity ids as values
 #[derive(Debug, Clone, Serialize, Deserialize, Copy, Default, Ord, PartialOrd, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -128,6 +169,10 @@ impl<Id: TableId> Component<Id> for EnergyRegenComponent {
 #[serde(rename_all = "camelCase")]
 pub struct SpawnBotComponent {
     pub bot: Bot,
+    /// Name of the bot template, see `GameConfig::bot_templates`
+    pub template: String,
+    /// The group the bot joins when spawned, see `BotGroups`
+    pub group: Option<String>,
 }
 
 impl<Id: TableId> Component<Id> for SpawnBotComponent {
@@ -151,6 +196,106 @@ impl<Id: TableId> Component<Id> for ScriptComponent {
     type Table = BTreeTable<Id, Self>;
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptVersion {
+    pub version: u32,
+    pub author: UserId,
+    /// The tick the version was uploaded in
+    pub time: u64,
+    pub program: CompiledProgram,
+    /// Set if the version was rolled back because of its runtime errors
+    pub failed: bool,
+}
+
+/// The last N versions of a script, N is configured by `GameConfig::script_versions_len`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptVersions {
+    /// The version currently in `ScriptComponent`
+    pub current: u32,
+    pub versions: VecDeque<ScriptVersion>,
+}
+impl<Id: TableId> Component<Id> for ScriptVersions {
+    type Table = BTreeTable<Id, Self>;
+}
+
+impl ScriptVersions {
+    /// Push a new version and make it the current one.
+    /// Drops the oldest versions, so at most `capacity` versions remain.
+    /// Returns the new version number.
+    pub fn push(
+        &mut self,
+        author: UserId,
+        time: u64,
+        program: CompiledProgram,
+        capacity: usize,
+    ) -> u32 {
+        let version = self.versions.back().map(|v| v.version + 1).unwrap_or(1);
+        self.versions.push_back(ScriptVersion {
+            version,
+            author,
+            time,
+            program,
+            failed: false,
+        });
+        while self.versions.len() > capacity.max(1) {
+            self.versions.pop_front();
+        }
+        self.current = version;
+        version
+    }
+
+    pub fn get(&self, version: u32) -> Option<&ScriptVersion> {
+        self.versions.iter().find(|v| v.version == version)
+    }
+
+    pub fn get_mut(&mut self, version: u32) -> Option<&mut ScriptVersion> {
+        self.versions.iter_mut().find(|v| v.version == version)
+    }
+
+    /// The latest version before the current one, that has not failed
+    pub fn last_good(&self) -> Option<&ScriptVersion> {
+        let current = self.current;
+        self.versions
+            .iter()
+            .rev()
+            .find(|v| v.version < current && !v.failed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptRunStats {
+    pub time: u64,
+    pub runs: u32,
+    pub errors: u32,
+}
+
+/// Runtime error counts of a script in the last N ticks it was executed in.
+/// N is configured by `GameConfig::script_error_window`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ScriptErrorStats(pub VecDeque<ScriptRunStats>);
+impl<Id: TableId> Component<Id> for ScriptErrorStats {
+    type Table = BTreeTable<Id, Self>;
+}
+
+impl ScriptErrorStats {
+    pub fn push(&mut self, stats: ScriptRunStats, capacity: usize) {
+        self.0.push_back(stats);
+        while self.0.len() > capacity {
+            self.0.pop_front();
+        }
+    }
+
+    /// Returns (runs, errors) summed over the window
+    pub fn totals(&self) -> (u32, u32) {
+        self.0.iter().fold((0, 0), |(runs, errors), stats| {
+            (runs + stats.runs, errors + stats.errors)
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct UserComponent;
@@ -174,8 +319,33 @@ impl Component<UserId> for UserProperties {
     type Table = BTreeTable<UserId, Self>;
 }
 
+/// Script execution metering of a user in the last tick
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CpuUsage {
+    /// Cards executed by all scripts of the user
+    pub used: u32,
+    pub budget: u32,
+    /// Number of scripts that were not executed because the budget ran out
+    pub skipped: u32,
+    /// Skipped scripts are deferred to the next tick, starting with this entity
+    pub resume_from: Option<EntityId>,
+}
+
+impl Component<UserId> for CpuUsage {
+    type Table = BTreeTable<UserId, Self>;
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Rooms(pub Vec<Room>);
 impl<Id: TableId> Component<Id> for Rooms {
     type Table = BTreeTable<Id, Self>;
 }
+
+/// Rooms the user has seen at least once. Their terrain is included in the user's output.
+/// See `crate::visibility`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ExploredRooms(pub BTreeSet<Room>);
+impl Component<UserId> for ExploredRooms {
+    type Table = BTreeTable<UserId, Self>;
+}
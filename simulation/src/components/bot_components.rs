@@ -1,21 +1,80 @@
 use crate::tables::{btree::BTreeTable, dense::DenseVecTable, Component, TableId};
 use crate::{
+    components::Resource,
     indices::{EntityId, RoomPosition, ScriptId, UserId, WorldPosition},
+    scripting_api::OperationResult,
     tables::flag::SparseFlagTable,
 };
 use arrayvec::ArrayVec;
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Kind of damage an attack deals. Armour may resist each kind differently.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DamageType {
+    Kinetic,
+    Thermal,
+    Explosive,
+}
+
+impl Default for DamageType {
+    fn default() -> Self {
+        DamageType::Kinetic
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, Copy, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct MeleeAttackComponent {
     pub strength: u16,
+    #[serde(default)]
+    pub damage_type: DamageType,
 }
 impl<Id: TableId> Component<Id> for MeleeAttackComponent {
     type Table = BTreeTable<Id, Self>;
 }
 
+/// Attacks targets up to `range` tiles away.
+/// Damage decreases by `falloff` for every tile beyond the first.
+#[derive(Debug, Clone, Serialize, Deserialize, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RangedAttackComponent {
+    pub strength: u16,
+    pub range: u32,
+    pub falloff: u16,
+    #[serde(default)]
+    pub damage_type: DamageType,
+}
+impl<Id: TableId> Component<Id> for RangedAttackComponent {
+    type Table = BTreeTable<Id, Self>;
+}
+
+/// Restores `strength` hp of a bot per heal
+#[derive(Debug, Clone, Serialize, Deserialize, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HealComponent {
+    pub strength: u16,
+}
+impl<Id: TableId> Component<Id> for HealComponent {
+    type Table = BTreeTable<Id, Self>;
+}
+
+/// Reduces the damage taken.
+/// `resistances` reduce damage of the given type by a percentage, then `armour` is subtracted
+/// from every hit.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ArmourComponent {
+    pub armour: u16,
+    #[serde(default)]
+    pub resistances: BTreeMap<DamageType, u8>,
+}
+impl<Id: TableId> Component<Id> for ArmourComponent {
+    type Table = BTreeTable<Id, Self>;
+}
+
 /// Has a body so it's not `null` when serializing
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
@@ -25,8 +84,17 @@ impl Component<EntityId> for Bot {
     type Table = SparseFlagTable<EntityId, Self>;
 }
 
+/// Marks the bots of the NPC faction, see `crate::npc`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NpcComponent {}
+
+impl Component<EntityId> for NpcComponent {
+    type Table = SparseFlagTable<EntityId, Self>;
+}
+
 /// Represent time to decay of bots
-/// On decay the bot will loose hp
+/// On decay the bot will loose hp, which may be restored by healing
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct DecayComponent {
@@ -38,12 +106,77 @@ impl<Id: TableId> Component<Id> for DecayComponent {
     type Table = BTreeTable<Id, Self>;
 }
 
+/// Bots gain fatigue by moving, see `terrain::move_cost`, and may only move while they have none.
+/// `recovery` fatigue is removed every tick.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FatigueComponent {
+    pub fatigue: u16,
+    pub recovery: u16,
+}
+impl<Id: TableId> Component<Id> for FatigueComponent {
+    type Table = BTreeTable<Id, Self>;
+}
+
+/// Resources carried by an entity. `carry_max` limits the total of all resources.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CarryComponent {
-    pub carry: u16,
+    #[serde(default)]
+    pub contents: BTreeMap<Resource, u16>,
     pub carry_max: u16,
 }
+
+impl CarryComponent {
+    pub fn new(carry_max: u16) -> Self {
+        Self {
+            contents: BTreeMap::new(),
+            carry_max,
+        }
+    }
+
+    /// Amount of the given resource carried
+    pub fn get(&self, ty: Resource) -> u16 {
+        self.contents.get(&ty).copied().unwrap_or(0)
+    }
+
+    /// Total amount of resources carried
+    pub fn total(&self) -> u16 {
+        self.contents.values().sum()
+    }
+
+    pub fn free_capacity(&self) -> u16 {
+        self.carry_max.saturating_sub(self.total())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.free_capacity() == 0
+    }
+
+    /// Add at most `amount` of the resource, limited by the free capacity.
+    /// Returns the amount added.
+    pub fn add(&mut self, ty: Resource, amount: u16) -> u16 {
+        let amount = amount.min(self.free_capacity());
+        if amount > 0 {
+            *self.contents.entry(ty).or_insert(0) += amount;
+        }
+        amount
+    }
+
+    /// Remove at most `amount` of the resource, limited by the amount carried.
+    /// Returns the amount removed.
+    pub fn take(&mut self, ty: Resource, amount: u16) -> u16 {
+        let carried = self.get(ty);
+        let amount = amount.min(carried);
+        if amount == carried {
+            self.contents.remove(&ty);
+        } else {
+            self.contents.insert(ty, carried - amount);
+        }
+        amount
+    }
+}
+
 impl<Id: TableId> Component<Id> for CarryComponent {
     type Table = BTreeTable<Id, Self>;
 }
@@ -71,3 +204,38 @@ pub struct PathCacheComponent {
 impl<Id: TableId> Component<Id> for PathCacheComponent {
     type Table = BTreeTable<Id, Self>;
 }
+
+/// Final outcomes of an entity's intents in the last tick.
+/// `None` if the entity had no such intent.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct IntentOutcomes {
+    pub move_intent: Option<OperationResult>,
+    pub mine_intent: Option<OperationResult>,
+    pub dropoff_intent: Option<OperationResult>,
+    pub melee_intent: Option<OperationResult>,
+    pub ranged_intent: Option<OperationResult>,
+    pub heal_intent: Option<OperationResult>,
+    pub repair_intent: Option<OperationResult>,
+    pub transfer_intent: Option<OperationResult>,
+    pub drop_intent: Option<OperationResult>,
+    pub pickup_intent: Option<OperationResult>,
+    pub build_intent: Option<OperationResult>,
+}
+impl Component<EntityId> for IntentOutcomes {
+    type Table = DenseVecTable<EntityId, Self>;
+}
+
+/// Named groups of bots of a user, with the script of each group
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct BotGroups(pub BTreeMap<String, ScriptId>);
+impl Component<UserId> for BotGroups {
+    type Table = BTreeTable<UserId, Self>;
+}
+
+/// Name of the group the bot belongs to, see `BotGroups`
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct BotGroup(pub String);
+impl Component<EntityId> for BotGroup {
+    type Table = BTreeTable<EntityId, Self>;
+}
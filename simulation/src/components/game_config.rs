@@ -1,18 +1,227 @@
-use crate::indices::ConfigKey;
+use crate::components::{ArmourComponent, DamageType, RangedAttackComponent};
+use crate::indices::{ConfigKey, ScriptId};
 use crate::tables::{unique::UniqueTable, Component};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Name of the template used by the automatic spawns
+pub const DEFAULT_BOT_TEMPLATE: &str = "worker";
+
+/// Body of the bots spawned by a template, and the cost of spawning them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BotTemplate {
+    pub hp: u16,
+    /// Number of ticks between losing `decay_amount` hp
+    pub decay_interval: u8,
+    pub decay_amount: u16,
+    pub carry_max: u16,
+    /// Bots without melee strength can not attack
+    pub melee_strength: Option<u16>,
+    #[serde(default)]
+    pub ranged_attack: Option<RangedAttackComponent>,
+    #[serde(default)]
+    pub armour: Option<ArmourComponent>,
+    /// Bots without heal strength can not heal
+    #[serde(default)]
+    pub heal_strength: Option<u16>,
+    /// Fatigue the bot recovers every tick, see `terrain::move_cost`
+    #[serde(default = "default_fatigue_recovery")]
+    pub fatigue_recovery: u16,
+    /// Energy consumed from the spawn
+    pub cost: u16,
+    /// Number of ticks it takes to spawn the bot
+    pub spawn_time: i16,
+}
+
+/// Rules of room ownership, see `crate::room_rules`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomRules {
+    /// Users may place structures in rooms nobody owns
+    pub build_in_unowned_rooms: bool,
+    /// Users may place structures in rooms owned by other users
+    pub build_in_enemy_rooms: bool,
+    /// Bots may mine resources in rooms owned by other users
+    pub mine_in_enemy_rooms: bool,
+    /// Rooms claimed after the first one must be connected to a room the user owns
+    pub claim_adjacent_rooms_only: bool,
+}
+
+impl Default for RoomRules {
+    fn default() -> Self {
+        Self {
+            build_in_unowned_rooms: true,
+            build_in_enemy_rooms: false,
+            mine_in_enemy_rooms: false,
+            claim_adjacent_rooms_only: true,
+        }
+    }
+}
+
+/// Waves of NPC invaders attacking wealthy rooms, see `crate::npc`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvaderRules {
+    /// Number of ticks between waves, 0 disables invaders
+    pub interval: u64,
+    /// Owned rooms are invaded once the spawns, towers and storages of the room owner hold at
+    /// least this much resources
+    pub wealth_threshold: u32,
+    /// Number of invaders spawned in a room per wave
+    pub wave_size: u32,
+    /// No more invaders are spawned in rooms that have this many already
+    pub max_per_room: u32,
+    /// Name of the bot template of the invaders
+    pub template: String,
+    /// Invaders run this script, the built-in AI drives them if it's not set
+    #[serde(default)]
+    pub script: Option<ScriptId>,
+}
+
+impl Default for InvaderRules {
+    fn default() -> Self {
+        Self {
+            interval: 500,
+            wealth_threshold: 2000,
+            wave_size: 2,
+            max_per_room: 4,
+            template: "warrior".to_owned(),
+            script: None,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameConfig {
+    /// Maximum number of cards a single script may execute in a tick, enforced as the VM's
+    /// iteration limit. See `script_execution::execute_scripts`
     pub execution_limit: u32,
+    /// Number of cards all scripts of a single user may execute in a tick, see
+    /// `script_execution::execute_scripts`
+    pub user_cpu_budget: u32,
     pub target_tick_ms: u64,
+    /// Number of ticks to keep script execution history for, per entity
+    pub script_history_len: u32,
+    /// Number of ticks to keep the script execution history of dead entities for, see
+    /// `DeadScriptHistories`
+    pub dead_script_history_ttl: u32,
+    /// Seeds the random number generators of the scripts
+    pub world_seed: u64,
+    /// Number of versions to keep, per script
+    pub script_versions_len: u32,
+    /// Number of ticks to observe a script's runtime errors in, before rolling it back
+    pub script_error_window: u32,
+    /// Scripts failing in at least this percent of their runs in the window are rolled back to
+    /// their last good version
+    pub script_rollback_error_percent: u32,
+    /// Bot templates by name, see `DEFAULT_BOT_TEMPLATE`
+    pub bot_templates: BTreeMap<String, BotTemplate>,
+    /// Maximum number of construction sites a user may have at once
+    pub max_construction_sites: u32,
+    pub room_rules: RoomRules,
+    pub invaders: InvaderRules,
+}
+
+/// Enough to move onto a plain tile every tick
+fn default_fatigue_recovery() -> u16 {
+    2
+}
+
+fn default_bot_templates() -> BTreeMap<String, BotTemplate> {
+    let mut templates = BTreeMap::new();
+    templates.insert(
+        DEFAULT_BOT_TEMPLATE.to_owned(),
+        BotTemplate {
+            hp: 100,
+            decay_interval: 20,
+            decay_amount: 100,
+            carry_max: 50,
+            melee_strength: None,
+            ranged_attack: None,
+            armour: None,
+            heal_strength: None,
+            fatigue_recovery: default_fatigue_recovery(),
+            cost: 500,
+            spawn_time: 10,
+        },
+    );
+    templates.insert(
+        "warrior".to_owned(),
+        BotTemplate {
+            hp: 200,
+            decay_interval: 20,
+            decay_amount: 100,
+            carry_max: 0,
+            melee_strength: Some(20),
+            ranged_attack: None,
+            armour: Some(ArmourComponent {
+                armour: 2,
+                resistances: Default::default(),
+            }),
+            heal_strength: None,
+            // heavily armoured, slow on anything but roads
+            fatigue_recovery: 1,
+            cost: 500,
+            spawn_time: 15,
+        },
+    );
+    templates.insert(
+        "ranger".to_owned(),
+        BotTemplate {
+            hp: 150,
+            decay_interval: 20,
+            decay_amount: 100,
+            carry_max: 0,
+            melee_strength: None,
+            ranged_attack: Some(RangedAttackComponent {
+                strength: 15,
+                range: 3,
+                falloff: 4,
+                damage_type: DamageType::Thermal,
+            }),
+            armour: None,
+            heal_strength: None,
+            fatigue_recovery: default_fatigue_recovery(),
+            cost: 600,
+            spawn_time: 15,
+        },
+    );
+    templates.insert(
+        "healer".to_owned(),
+        BotTemplate {
+            hp: 150,
+            decay_interval: 20,
+            decay_amount: 100,
+            carry_max: 0,
+            melee_strength: None,
+            ranged_attack: None,
+            armour: None,
+            heal_strength: Some(15),
+            fatigue_recovery: default_fatigue_recovery(),
+            cost: 600,
+            spawn_time: 15,
+        },
+    );
+    templates
 }
 
 impl Default for GameConfig {
     fn default() -> Self {
         Self {
             execution_limit: 128,
+            user_cpu_budget: 4096,
             target_tick_ms: 100,
+            script_history_len: 16,
+            dead_script_history_ttl: 100,
+            world_seed: 0,
+            script_versions_len: 8,
+            script_error_window: 10,
+            script_rollback_error_percent: 50,
+            bot_templates: default_bot_templates(),
+            max_construction_sites: 20,
+            room_rules: RoomRules::default(),
+            invaders: InvaderRules::default(),
         }
     }
 }
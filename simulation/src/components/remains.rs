@@ -0,0 +1,81 @@
+use super::StructureKind;
+use crate::indices::{EntityId, UserId};
+use crate::tables::{btree::BTreeTable, Component, TableId};
+use cao_lang::{prelude::Scalar, traits::AutoByteEncodeProperties};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+#[derive(Debug, Serialize, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[repr(i32)]
+pub enum DeathCause {
+    /// The entity ran out of hp without a recorded source of damage
+    Unknown = 0,
+    Decay = 1,
+    Melee = 2,
+    Ranged = 3,
+    Tower = 4,
+    /// Deleted by a script
+    Deleted = 5,
+}
+
+impl Default for DeathCause {
+    fn default() -> Self {
+        DeathCause::Unknown
+    }
+}
+
+impl AutoByteEncodeProperties for DeathCause {}
+impl TryFrom<Scalar> for DeathCause {
+    type Error = Scalar;
+    fn try_from(s: Scalar) -> Result<DeathCause, Scalar> {
+        let cause = match s {
+            Scalar::Integer(0) => DeathCause::Unknown,
+            Scalar::Integer(1) => DeathCause::Decay,
+            Scalar::Integer(2) => DeathCause::Melee,
+            Scalar::Integer(3) => DeathCause::Ranged,
+            Scalar::Integer(4) => DeathCause::Tower,
+            Scalar::Integer(5) => DeathCause::Deleted,
+            _ => return Err(s),
+        };
+        Ok(cause)
+    }
+}
+
+/// The source of the last damage the entity took.
+/// Recorded by the systems dealing damage, so the death system can tell why an entity died.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LastDamageComponent(pub DeathCause);
+impl<Id: TableId> Component<Id> for LastDamageComponent {
+    type Table = BTreeTable<Id, Self>;
+}
+
+/// What the dead entity used to be
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RemainsSummary {
+    pub bot: bool,
+    pub structure: Option<StructureKind>,
+    pub hp_max: u16,
+    pub melee: bool,
+    pub ranged: bool,
+    pub heal: bool,
+    pub armour: u16,
+}
+
+/// Left behind by dead bots and structures, removed after `time_remaining` ticks.
+/// Remains are walkable, they are not registered in the entity position table.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RemainsComponent {
+    /// Id of the dead entity
+    pub entity_id: EntityId,
+    pub owner_id: Option<UserId>,
+    pub cause: DeathCause,
+    pub summary: RemainsSummary,
+    pub time_remaining: u16,
+}
+impl<Id: TableId> Component<Id> for RemainsComponent {
+    type Table = BTreeTable<Id, Self>;
+}
@@ -1,14 +1,29 @@
-use crate::tables::{btree::BTreeTable, Component, TableId};
+use crate::indices::{EntityId, WorldPosition};
+use crate::tables::{btree::BTreeTable, Component, RoomMortonTable, TableId};
 use cao_lang::{prelude::Scalar, traits::AutoByteEncodeProperties};
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 
-#[derive(Debug, Serialize, Clone, Copy, Deserialize)]
+#[derive(Debug, Serialize, Clone, Copy, Deserialize, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[serde(rename_all = "camelCase")]
 #[repr(u8)]
 pub enum Resource {
     Empty = 0,
     Energy = 1,
+    Iron = 2,
+    Crystal = 3,
+}
+
+impl Resource {
+    /// Amount of the resource a bot mines from a resource node in a single tick
+    pub fn mining_rate(self) -> u16 {
+        match self {
+            Resource::Empty => 0,
+            Resource::Energy => 10,
+            Resource::Iron => 5,
+            Resource::Crystal => 2,
+        }
+    }
 }
 
 impl Default for Resource {
@@ -28,6 +43,8 @@ impl TryFrom<Scalar> for Resource {
                 }
                 match i {
                     1 => Ok(Resource::Energy),
+                    2 => Ok(Resource::Iron),
+                    3 => Ok(Resource::Crystal),
                     _ => Err(s),
                 }
             }
@@ -36,6 +53,7 @@ impl TryFrom<Scalar> for Resource {
     }
 }
 
+/// Marks a resource node. The amount left in the node is stored in its `ResourceAmountComponent`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ResourceComponent(pub Resource);
@@ -48,3 +66,35 @@ impl Default for ResourceComponent {
         Self(Resource::Energy)
     }
 }
+
+/// Amount of the resource left in a resource node
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceAmountComponent {
+    pub amount: u16,
+    pub amount_max: u16,
+}
+impl<Id: TableId> Component<Id> for ResourceAmountComponent {
+    type Table = BTreeTable<Id, Self>;
+}
+
+/// Resources lying on the ground. The resources are held in the entity's `CarryComponent`.
+/// Piles lose some of their resources every few ticks, see the pile system.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcePileComponent {
+    /// Ticks until the next decay
+    pub time_remaining: u8,
+}
+impl<Id: TableId> Component<Id> for ResourcePileComponent {
+    type Table = BTreeTable<Id, Self>;
+}
+
+/// Resource piles by position. Maintained by the positions system.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PileComponent(pub EntityId);
+
+impl Component<WorldPosition> for PileComponent {
+    type Table = RoomMortonTable<Self>;
+}
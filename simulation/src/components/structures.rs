@@ -0,0 +1,115 @@
+use crate::indices::{EntityId, WorldPosition};
+use crate::tables::{btree::BTreeTable, Component, RoomMortonTable, TableId};
+use cao_lang::{prelude::Scalar, traits::AutoByteEncodeProperties};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// Type of a structure, see `crate::structures` for their properties
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[repr(i32)]
+pub enum StructureKind {
+    Spawn = 1,
+    /// Holds resources, a valid dropoff target
+    Storage = 2,
+    /// Shoots hostile bots in range, using energy
+    Tower = 3,
+    /// Blocks movement
+    Wall = 4,
+    /// Walkable, cheaper movement
+    Road = 5,
+    /// Claims the room for its owner, see `RoomControllerComponent`
+    Controller = 6,
+}
+
+impl StructureKind {
+    /// Walkable structures are not registered in the entity position table, so bots may move onto
+    /// them
+    pub fn is_walkable(self) -> bool {
+        matches!(self, StructureKind::Road)
+    }
+}
+
+impl<Id: TableId> Component<Id> for StructureKind {
+    type Table = BTreeTable<Id, Self>;
+}
+
+impl AutoByteEncodeProperties for StructureKind {}
+impl TryFrom<Scalar> for StructureKind {
+    type Error = Scalar;
+    fn try_from(s: Scalar) -> Result<StructureKind, Scalar> {
+        let kind = match s {
+            Scalar::Integer(1) => StructureKind::Spawn,
+            Scalar::Integer(2) => StructureKind::Storage,
+            Scalar::Integer(3) => StructureKind::Tower,
+            Scalar::Integer(4) => StructureKind::Wall,
+            Scalar::Integer(5) => StructureKind::Road,
+            Scalar::Integer(6) => StructureKind::Controller,
+            _ => return Err(s),
+        };
+        Ok(kind)
+    }
+}
+
+/// A structure under construction. Bots build it by spending the energy they carry.
+/// Replaced by the structure once `progress` reaches `progress_max`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConstructionSite {
+    pub kind: StructureKind,
+    pub progress: u32,
+    pub progress_max: u32,
+}
+
+impl<Id: TableId> Component<Id> for ConstructionSite {
+    type Table = BTreeTable<Id, Self>;
+}
+
+/// Automated ranged defence
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TowerComponent {
+    pub range: u32,
+    pub damage: u16,
+    /// Energy consumed by a single shot
+    pub energy_per_shot: u16,
+}
+
+impl<Id: TableId> Component<Id> for TowerComponent {
+    type Table = BTreeTable<Id, Self>;
+}
+
+/// Placed in a room when it is claimed. Its level limits the structures that may be placed in the
+/// room. Bots upgrade it by dropping off energy, the controller levels up once `progress` reaches
+/// `progress_max`.
+///
+/// The room is released if its controller is lost.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomControllerComponent {
+    pub level: u16,
+    pub progress: u32,
+    pub progress_max: u32,
+}
+
+impl<Id: TableId> Component<Id> for RoomControllerComponent {
+    type Table = BTreeTable<Id, Self>;
+}
+
+/// Roads by position. Maintained by the positions system.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RoadComponent(pub EntityId);
+
+impl Component<WorldPosition> for RoadComponent {
+    type Table = RoomMortonTable<Self>;
+}
+
+/// Construction sites by position. Maintained by the positions system.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteComponent(pub EntityId);
+
+impl Component<WorldPosition> for SiteComponent {
+    type Table = RoomMortonTable<Self>;
+}
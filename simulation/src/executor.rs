@@ -5,8 +5,8 @@ use slog::{debug, info, o, Logger};
 use crate::{
     components::EntityScript, intents, map_generation::generate_full_map,
     map_generation::overworld::OverworldGenerationParams,
-    map_generation::room::RoomGenerationParams, map_generation::MapGenError, prelude::EntityId,
-    prelude::FromWorldMut, world::init_inmemory_storage, world::World,
+    map_generation::room::RoomGenerationParams, map_generation::MapGenError, npc,
+    prelude::EntityId, prelude::FromWorldMut, world::init_inmemory_storage, world::World,
 };
 use crate::{profile, systems::execute_world_update, systems::script_execution::execute_scripts};
 
@@ -50,7 +50,10 @@ impl Executor for SimpleExecutor {
             scripts_table.iter().map(|(id, x)| (id, *x)).collect();
 
         debug!(logger, "Executing scripts");
-        let intents = execute_scripts(executions.as_slice(), world);
+        let mut intents = execute_scripts(executions.as_slice(), world);
+
+        debug!(logger, "Executing the built-in AI");
+        intents.extend(npc::invader_intents(world));
 
         debug!(logger, "Got {} intents", intents.len());
         intents::move_into_storage(world, intents);
@@ -98,6 +101,7 @@ fn execute_map_generation(
         .with_radius(radius)
         .with_chance_plain(0.33)
         .with_chance_wall(0.33)
+        .with_chance_swamp(0.05)
         .with_plain_dilation(2)
         .build()
         .unwrap();
@@ -0,0 +1,406 @@
+//! Run Cao-lang programs against a small fixture world, without a running worker.
+//!
+//! Intended to be used for testing scripts. See the `cao-script-test` binary for a CLI.
+//!
+//! ## Fixture format
+//!
+//! ```json
+//! {
+//!     "roomRadius": 8,
+//!     "rooms": [
+//!         {
+//!             "room": { "q": 0, "r": 0 },
+//!             "defaultTerrain": "plain",
+//!             "terrain": [ [ { "q": 8, "r": 9 }, "wall" ] ]
+//!         }
+//!     ],
+//!     "entities": [
+//!         {
+//!             "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 8, "r": 8 } },
+//!             "owner": "7d5b8d0e-7cbb-4c82-8c4b-3b1d5a1f1c3e",
+//!             "bot": true,
+//!             "carry": { "contents": { "energy": 20 }, "carryMax": 50 }
+//!         }
+//!     ]
+//! }
+//! ```
+//!
+//! Entities receive their ids in the order they are declared, starting from 0.
+use crate::components::*;
+use crate::geometry::{Axial, Hexagon};
+use crate::indices::{ConfigKey, EntityId, Room, ScriptId, UserId, WorldPosition};
+use crate::intents::BotIntents;
+use crate::storage::views::UnwrapView;
+use crate::systems::script_execution::{execute_single_script, ScriptExecutionData};
+use crate::tables::morton_hierarchy::ExtendFailure;
+use crate::terrain::TileTerrainType;
+use crate::world::World;
+use crate::Time;
+use cao_lang::prelude::*;
+use serde::{Deserialize, Serialize};
+use slog::{debug, Logger};
+use std::convert::TryFrom;
+use std::pin::Pin;
+use thiserror::Error;
+
+pub use crate::systems::script_execution::ExecutionError as ScriptExecutionError;
+
+#[derive(Debug, Error)]
+pub enum HarnessError {
+    #[error("Failed to compile the program: {0}")]
+    Compilation(String),
+    #[error("Failed to build the fixture world: {0}")]
+    Fixture(#[from] ExtendFailure),
+    #[error("Room {0:?} could not be inserted into the fixture world")]
+    InvalidRoom(Axial),
+    #[error("Entity {0:?} is not declared in the fixture")]
+    EntityNotFound(EntityId),
+    #[error("Script execution failed: {0}")]
+    Execution(#[from] ScriptExecutionError),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fixture {
+    #[serde(default = "default_room_radius")]
+    pub room_radius: u32,
+    pub rooms: Vec<FixtureRoom>,
+    #[serde(default)]
+    pub entities: Vec<FixtureEntity>,
+    /// The tick to run the script in
+    #[serde(default)]
+    pub time: u64,
+}
+
+fn default_room_radius() -> u32 {
+    8
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixtureRoom {
+    pub room: Axial,
+    #[serde(default)]
+    pub owner: Option<UserId>,
+    /// Terrain of every tile in the room that's not listed in `terrain`
+    #[serde(default)]
+    pub default_terrain: TileTerrainType,
+    #[serde(default)]
+    pub terrain: Vec<(Axial, TileTerrainType)>,
+}
+
+/// Components of a single entity. Only the given components are inserted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixtureEntity {
+    pub pos: WorldPosition,
+    #[serde(default)]
+    pub owner: Option<UserId>,
+    #[serde(default)]
+    pub bot: bool,
+    #[serde(default)]
+    pub structure: bool,
+    pub kind: Option<StructureKind>,
+    pub hp: Option<HpComponent>,
+    pub decay: Option<DecayComponent>,
+    pub carry: Option<CarryComponent>,
+    pub energy: Option<EnergyComponent>,
+    pub resource: Option<Resource>,
+    pub resource_amount: Option<ResourceAmountComponent>,
+    pub spawn: Option<SpawnComponent>,
+    pub melee: Option<MeleeAttackComponent>,
+    pub ranged: Option<RangedAttackComponent>,
+    pub armour: Option<ArmourComponent>,
+    pub heal: Option<HealComponent>,
+    pub fatigue: Option<FatigueComponent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptTestOutput {
+    pub intents: BotIntents,
+    pub logs: Vec<String>,
+}
+
+/// Build a World from the fixture
+pub fn build_world(
+    logger: impl Into<Option<Logger>>,
+    fixture: &Fixture,
+) -> Result<Pin<Box<World>>, HarnessError> {
+    let mut world = World::new(logger);
+
+    let radius = fixture.room_radius;
+    let center = Axial::new(radius as i32, radius as i32);
+    world.config.room_properties.value = Some(RoomProperties { radius, center });
+    world.resources.time.value = Some(Time(fixture.time));
+
+    let bounds = Hexagon {
+        center,
+        radius: radius as i32,
+    };
+    for room in fixture.rooms.iter() {
+        let room_id = Room(room.room);
+        world
+            .unsafe_view::<Room, RoomComponent>()
+            .insert(room_id, RoomComponent)
+            .map_err(|_| HarnessError::InvalidRoom(room.room))?;
+        world
+            .unsafe_view::<Room, RoomConnections>()
+            .insert(room_id, RoomConnections::default())
+            .map_err(|_| HarnessError::InvalidRoom(room.room))?;
+        if let Some(owner_id) = room.owner {
+            world
+                .unsafe_view::<Room, OwnedEntity>()
+                .insert(room_id, OwnedEntity { owner_id })
+                .map_err(|_| HarnessError::InvalidRoom(room.room))?;
+        }
+
+        let mut terrain = world.unsafe_view::<WorldPosition, TerrainComponent>();
+        for pos in bounds.iter_points() {
+            let tile = room
+                .terrain
+                .iter()
+                .find(|(p, _)| *p == pos)
+                .map(|(_, t)| *t)
+                .unwrap_or(room.default_terrain);
+            terrain.insert(
+                WorldPosition {
+                    room: room.room,
+                    pos,
+                },
+                TerrainComponent(tile),
+            )?;
+        }
+    }
+    world
+        .unsafe_view::<WorldPosition, EntityComponent>()
+        .extend_rooms(fixture.rooms.iter().map(|r| Room(r.room)))?;
+
+    for entity in fixture.entities.iter() {
+        let id = world.insert_entity();
+        insert_entity(&mut *world, id, entity)?;
+    }
+
+    Ok(world)
+}
+
+fn insert_entity(
+    world: &mut World,
+    id: EntityId,
+    entity: &FixtureEntity,
+) -> Result<(), HarnessError> {
+    world
+        .unsafe_view::<EntityId, PositionComponent>()
+        .insert_or_update(id, PositionComponent(entity.pos));
+    world
+        .unsafe_view::<WorldPosition, EntityComponent>()
+        .insert(entity.pos, EntityComponent(id))?;
+    if let Some(owner_id) = entity.owner {
+        world
+            .unsafe_view::<EntityId, OwnedEntity>()
+            .insert_or_update(id, OwnedEntity { owner_id });
+    }
+    if entity.bot {
+        world.unsafe_view::<EntityId, Bot>().insert(id);
+    }
+    if entity.structure {
+        world.unsafe_view::<EntityId, Structure>().insert(id);
+    }
+    if let Some(kind) = entity.kind {
+        world
+            .unsafe_view::<EntityId, StructureKind>()
+            .insert_or_update(id, kind);
+    }
+    if let Some(hp) = entity.hp {
+        world
+            .unsafe_view::<EntityId, HpComponent>()
+            .insert_or_update(id, hp);
+    }
+    if let Some(decay) = entity.decay {
+        world
+            .unsafe_view::<EntityId, DecayComponent>()
+            .insert_or_update(id, decay);
+    }
+    if let Some(ref carry) = entity.carry {
+        world
+            .unsafe_view::<EntityId, CarryComponent>()
+            .insert_or_update(id, carry.clone());
+    }
+    if let Some(energy) = entity.energy {
+        world
+            .unsafe_view::<EntityId, EnergyComponent>()
+            .insert_or_update(id, energy);
+    }
+    if let Some(resource) = entity.resource {
+        world
+            .unsafe_view::<EntityId, ResourceComponent>()
+            .insert_or_update(id, ResourceComponent(resource));
+    }
+    if let Some(amount) = entity.resource_amount {
+        world
+            .unsafe_view::<EntityId, ResourceAmountComponent>()
+            .insert_or_update(id, amount);
+    }
+    if let Some(ref spawn) = entity.spawn {
+        world
+            .unsafe_view::<EntityId, SpawnComponent>()
+            .insert_or_update(id, spawn.clone());
+        world
+            .unsafe_view::<EntityId, SpawnQueueComponent>()
+            .insert_or_update(id, SpawnQueueComponent::default());
+    }
+    if let Some(melee) = entity.melee {
+        world
+            .unsafe_view::<EntityId, MeleeAttackComponent>()
+            .insert_or_update(id, melee);
+    }
+    if let Some(ranged) = entity.ranged {
+        world
+            .unsafe_view::<EntityId, RangedAttackComponent>()
+            .insert_or_update(id, ranged);
+    }
+    if let Some(ref armour) = entity.armour {
+        world
+            .unsafe_view::<EntityId, ArmourComponent>()
+            .insert_or_update(id, armour.clone());
+    }
+    if let Some(heal) = entity.heal {
+        world
+            .unsafe_view::<EntityId, HealComponent>()
+            .insert_or_update(id, heal);
+    }
+    if let Some(fatigue) = entity.fatigue {
+        world
+            .unsafe_view::<EntityId, FatigueComponent>()
+            .insert_or_update(id, fatigue);
+    }
+    Ok(())
+}
+
+/// Compile `program` and run it once, for `entity_id` in the world described by `fixture`.
+///
+/// The entity should have an owner, because most of the game API requires one.
+pub fn run_script(
+    logger: impl Into<Option<Logger>>,
+    program: CompilationUnit,
+    fixture: &Fixture,
+    entity_id: EntityId,
+) -> Result<ScriptTestOutput, HarnessError> {
+    let mut world = build_world(logger, fixture)?;
+    let logger = world.logger.clone();
+
+    if entity_id.0 as usize >= fixture.entities.len() {
+        return Err(HarnessError::EntityNotFound(entity_id));
+    }
+
+    debug!(logger, "compiling program");
+    let program = compile(
+        Some(logger.clone()),
+        program,
+        CompileOptions::new().with_breadcrumbs(true),
+    )
+    .map_err(|err| HarnessError::Compilation(format!("{:?}", err)))?;
+
+    let script_id = ScriptId(uuid::Uuid::new_v4());
+    world
+        .unsafe_view::<ScriptId, ScriptComponent>()
+        .insert_or_update(script_id, ScriptComponent(program));
+
+    let world = &*world;
+    let conf = UnwrapView::<ConfigKey, game_config::GameConfig>::new(world);
+    let owner_id = world
+        .view::<EntityId, OwnedEntity>()
+        .reborrow()
+        .get_by_id(&entity_id)
+        .map(|OwnedEntity { owner_id }| *owner_id);
+
+    let mut vm = VM::new(
+        logger.clone(),
+        ScriptExecutionData::unsafe_default(logger.clone()),
+    );
+    vm.max_iter =
+        i32::try_from(conf.execution_limit).expect("Expected execution_limit to fit into 31 bits");
+    crate::scripting_api::make_import().execute_imports(&mut vm);
+
+    let intents = execute_single_script(
+        &logger,
+        entity_id,
+        script_id,
+        owner_id,
+        conf.user_cpu_budget,
+        world,
+        &mut vm,
+    )?;
+    let logs = intents
+        .log_intent
+        .as_ref()
+        .map(|log| log.payload.clone())
+        .unwrap_or_default();
+
+    Ok(ScriptTestOutput { intents, logs })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{setup_testing, test_logger};
+
+    fn fixture() -> Fixture {
+        serde_json::from_str(
+            r#"{
+                "roomRadius": 4,
+                "rooms": [ { "room": { "q": 0, "r": 0 } } ],
+                "entities": [
+                    {
+                        "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 4, "r": 4 } },
+                        "owner": "7d5b8d0e-7cbb-4c82-8c4b-3b1d5a1f1c3e",
+                        "bot": true
+                    }
+                ]
+            }"#,
+        )
+        .expect("Failed to parse fixture")
+    }
+
+    #[test]
+    fn can_run_a_logging_script() {
+        setup_testing();
+
+        let program: CompilationUnit = serde_json::from_str(
+            r#"{
+                "lanes": [
+                    {
+                        "name": "main",
+                        "cards": [
+                            { "StringLiteral": "hello" },
+                            { "Call": "Console Log" }
+                        ]
+                    }
+                ]
+            }"#,
+        )
+        .expect("Failed to parse program");
+
+        let output = run_script(test_logger(), program, &fixture(), EntityId(0))
+            .expect("Failed to run the script");
+
+        assert_eq!(output.intents.entity_id, EntityId(0));
+        assert_eq!(output.logs.len(), 1);
+        assert!(output.logs[0].ends_with("says hello"), "{:?}", output.logs);
+    }
+
+    #[test]
+    fn missing_entity_is_an_error() {
+        setup_testing();
+
+        let program: CompilationUnit =
+            serde_json::from_str(r#"{ "lanes": [ { "name": "main", "cards": [] } ] }"#)
+                .expect("Failed to parse program");
+
+        let res = run_script(test_logger(), program, &fixture(), EntityId(3));
+        assert!(matches!(
+            res,
+            Err(HarnessError::EntityNotFound(EntityId(3)))
+        ));
+    }
+}
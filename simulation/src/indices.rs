@@ -30,6 +30,7 @@ pub struct ScriptId(pub uuid::Uuid);
     Debug, Clone, Default, Ord, PartialOrd, Eq, PartialEq, Copy, Hash, Serialize, Deserialize,
 )]
 pub struct UserId(pub uuid::Uuid);
+impl AutoByteEncodeProperties for UserId {}
 
 impl SerialId for IntentId {
     fn next(&self) -> Self {
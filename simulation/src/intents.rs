@@ -1,28 +1,37 @@
 //! Actions, world updates the clients _intend_ to execute.
 //!
 mod attack_intent;
+mod build_intent;
 mod delete_entity_intent;
 mod dropoff_intent;
+mod heal_intent;
 mod log_intent;
 mod mine_intent;
 mod move_intent;
 mod pathcache_intent;
 mod spawn_intent;
+mod transfer_intent;
 
 pub use self::attack_intent::*;
+pub use self::build_intent::*;
 pub use self::delete_entity_intent::*;
 pub use self::dropoff_intent::*;
+pub use self::heal_intent::*;
 pub use self::log_intent::*;
 pub use self::mine_intent::*;
 pub use self::move_intent::*;
 pub use self::pathcache_intent::*;
 pub use self::spawn_intent::*;
+pub use self::transfer_intent::*;
 
-use crate::components::ScriptHistoryEntry;
+use crate::components::{PositionComponent, ScriptHistoryEntry};
 use crate::indices::{EmptyKey, EntityId};
 use crate::prelude::World;
+use crate::scripting_api::OperationResult;
+use crate::storage::views::View;
 use crate::tables::{unique::UniqueTable, Component};
 use serde::{Deserialize, Serialize};
+use slog::{debug, Logger};
 
 impl BotIntents {
     pub fn with_log<S: Into<String>>(
@@ -45,6 +54,29 @@ impl BotIntents {
     }
 }
 
+/// Check that `to` is within `range` of `from`, in the same room
+fn check_range(
+    logger: &Logger,
+    from: EntityId,
+    to: EntityId,
+    range: u32,
+    positions: View<EntityId, PositionComponent>,
+) -> OperationResult {
+    let nearby = positions.get_by_id(&from).and_then(|from_pos| {
+        positions.get_by_id(&to).map(|to_pos| {
+            to_pos.0.room == from_pos.0.room && to_pos.0.pos.hex_distance(from_pos.0.pos) <= range
+        })
+    });
+    match nearby {
+        None => {
+            debug!(logger, "{:?} or {:?} has no position component", from, to);
+            OperationResult::InvalidInput
+        }
+        Some(false) => OperationResult::NotInRange,
+        Some(true) => OperationResult::Ok,
+    }
+}
+
 /// Implements the SOA style intents container.
 ///
 /// Assumes that the Intents were registered in World. (see the data_store module)
@@ -114,5 +146,13 @@ intents!(
     mut_path_cache_intent: MutPathCacheIntent,
     script_history_intent: ScriptHistoryEntry,
     melee_attack_intent: MeleeIntent,
+    ranged_attack_intent: RangedIntent,
+    heal_intent: HealIntent,
+    repair_intent: RepairIntent,
+    transfer_intent: TransferIntent,
+    drop_intent: DropIntent,
+    pickup_intent: PickupIntent,
     delete_entity_intent: DeleteEntityIntent,
+    build_intent: BuildIntent,
+    construction_site_intent: ConstructionSiteIntent,
 );
@@ -1,4 +1,7 @@
-use crate::components::{HpComponent, MeleeAttackComponent, OwnedEntity, PositionComponent};
+use crate::combat::MELEE_RANGE;
+use crate::components::{
+    HpComponent, MeleeAttackComponent, OwnedEntity, PositionComponent, RangedAttackComponent,
+};
 use crate::indices::{EntityId, UserId};
 use crate::scripting_api::OperationResult;
 use crate::storage::views::View;
@@ -11,6 +14,12 @@ pub struct MeleeIntent {
     pub defender: EntityId,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RangedIntent {
+    pub attacker: EntityId,
+    pub defender: EntityId,
+}
+
 type CheckInput<'a> = (
     View<'a, EntityId, OwnedEntity>,
     View<'a, EntityId, PositionComponent>,
@@ -68,9 +77,75 @@ pub fn check_melee_intent(
         debug!(logger, "Attacker and defender are not in the same room");
         return OperationResult::InvalidTarget;
     }
-    if attack_pos.0.pos.hex_distance(defend_pos.0.pos) > 1 {
+    if attack_pos.0.pos.hex_distance(defend_pos.0.pos) > MELEE_RANGE {
         debug!(logger, "Attacker is out of melee range");
         return OperationResult::NotInRange;
     }
     OperationResult::Ok
 }
+
+type RangedCheckInput<'a> = (
+    View<'a, EntityId, OwnedEntity>,
+    View<'a, EntityId, PositionComponent>,
+    View<'a, EntityId, RangedAttackComponent>,
+    View<'a, EntityId, HpComponent>,
+);
+
+/// `attacker` must be owned by the user.
+/// `attacker` must have `RangedAttackComponent`
+/// `defender` must have `HpComponent`
+/// `defender` must be within the range of the attacker
+pub fn check_ranged_intent(
+    logger: &Logger,
+    intent: &RangedIntent,
+    user_id: UserId,
+    (owner_table, pos_table, ranged_table, hp_table): RangedCheckInput,
+) -> OperationResult {
+    let logger = logger.new(slog::o!(
+            "attacker" => intent.attacker.0,
+            "defender" => intent.defender.0,
+    ));
+    trace!(logger, "check_ranged_intent");
+
+    if owner_table
+        .get_by_id(&intent.attacker)
+        .map(|o| o.owner_id != user_id)
+        .unwrap_or(true)
+    {
+        return OperationResult::NotOwner;
+    }
+    let attack = match ranged_table.get_by_id(&intent.attacker) {
+        Some(x) => x,
+        None => {
+            debug!(logger, "attacker has no RangedAttackComponent");
+            return OperationResult::InvalidInput;
+        }
+    };
+    if !hp_table.contains_id(&intent.defender) {
+        debug!(logger, "defender has no HpComponent");
+        return OperationResult::InvalidTarget;
+    }
+    let attack_pos = match pos_table.get_by_id(&intent.attacker) {
+        Some(x) => x,
+        None => {
+            debug!(logger, "attacker has no PositionComponent");
+            return OperationResult::InvalidInput;
+        }
+    };
+    let defend_pos = match pos_table.get_by_id(&intent.defender) {
+        Some(x) => x,
+        None => {
+            debug!(logger, "defender has no PositionComponent");
+            return OperationResult::InvalidTarget;
+        }
+    };
+    if attack_pos.0.room != defend_pos.0.room {
+        debug!(logger, "Attacker and defender are not in the same room");
+        return OperationResult::InvalidTarget;
+    }
+    if attack_pos.0.pos.hex_distance(defend_pos.0.pos) > attack.range {
+        debug!(logger, "Attacker is out of range");
+        return OperationResult::NotInRange;
+    }
+    OperationResult::Ok
+}
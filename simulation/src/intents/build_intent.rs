@@ -0,0 +1,139 @@
+use crate::components::{
+    Bot, CarryComponent, ConstructionSite, OwnedEntity, PositionComponent, Resource, StructureKind,
+};
+use crate::indices::{EntityId, UserId, WorldPosition};
+use crate::scripting_api::OperationResult;
+use crate::storage::views::View;
+use crate::structures::{check_site_placement, PlaceStructureError, PlacementCheckInput};
+use serde::{Deserialize, Serialize};
+use slog::{debug, Logger};
+
+pub const BUILD_RANGE: u32 = 1;
+/// Bots may place construction sites this far from themselves
+pub const SITE_PLACEMENT_RANGE: u32 = 3;
+/// Maximum amount of energy a bot spends on building in a single tick
+pub const BUILD_POWER: u16 = 10;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BuildIntent {
+    pub bot: EntityId,
+    pub site: EntityId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstructionSiteIntent {
+    /// The bot placing the site
+    pub bot: EntityId,
+    pub owner_id: UserId,
+    pub kind: StructureKind,
+    pub position: WorldPosition,
+}
+
+type CheckInput<'a> = (
+    View<'a, EntityId, Bot>,
+    View<'a, EntityId, OwnedEntity>,
+    View<'a, EntityId, PositionComponent>,
+    View<'a, EntityId, CarryComponent>,
+    View<'a, EntityId, ConstructionSite>,
+);
+
+/// A valid build intent has the following characteristics:
+/// - the bot is owned by the user
+/// - the bot is carrying energy
+/// - the site is a construction site of the user
+/// - the site is within build range
+pub fn check_build_intent(
+    logger: &Logger,
+    intent: &BuildIntent,
+    user_id: UserId,
+    (bots, owners, positions, carry, sites): CheckInput,
+) -> OperationResult {
+    let is_owned = |id: &EntityId| {
+        owners
+            .get_by_id(id)
+            .map(|owner| owner.owner_id == user_id)
+            .unwrap_or(false)
+    };
+    if bots.get_by_id(&intent.bot).is_none() {
+        return OperationResult::InvalidInput;
+    }
+    if !is_owned(&intent.bot) {
+        return OperationResult::NotOwner;
+    }
+    if carry
+        .get_by_id(&intent.bot)
+        .map(|carry| carry.get(Resource::Energy) == 0)
+        .unwrap_or(true)
+    {
+        return OperationResult::Empty;
+    }
+    if sites.get_by_id(&intent.site).is_none() {
+        debug!(logger, "{:?} is not a construction site", intent.site);
+        return OperationResult::InvalidTarget;
+    }
+    if !is_owned(&intent.site) {
+        return OperationResult::NotOwner;
+    }
+
+    let nearby = positions.get_by_id(&intent.bot).and_then(|botpos| {
+        positions.get_by_id(&intent.site).map(|sitepos| {
+            sitepos.0.room == botpos.0.room
+                && sitepos.0.pos.hex_distance(botpos.0.pos) <= BUILD_RANGE
+        })
+    });
+    match nearby {
+        None => {
+            debug!(
+                logger,
+                "Bot or site has no position components {:?}", intent
+            );
+            OperationResult::InvalidInput
+        }
+        Some(false) => OperationResult::NotInRange,
+        Some(true) => OperationResult::Ok,
+    }
+}
+
+/// A valid construction site intent has the following characteristics:
+/// - the bot is owned by the user
+/// - the position is within `SITE_PLACEMENT_RANGE` of the bot, in the same room
+/// - the position must be valid for the structure, see `check_site_placement`
+pub fn check_construction_site_intent(
+    logger: &Logger,
+    intent: &ConstructionSiteIntent,
+    views: PlacementCheckInput,
+) -> OperationResult {
+    let PlacementCheckInput {
+        positions, owners, ..
+    } = views;
+    let is_owned = owners
+        .get_by_id(&intent.bot)
+        .map(|owner| owner.owner_id == intent.owner_id)
+        .unwrap_or(false);
+    if !is_owned {
+        return OperationResult::NotOwner;
+    }
+    let nearby = positions
+        .get_by_id(&intent.bot)
+        .map(|PositionComponent(pos)| {
+            pos.room == intent.position.room
+                && pos.pos.hex_distance(intent.position.pos) <= SITE_PLACEMENT_RANGE
+        });
+    match nearby {
+        None => {
+            debug!(logger, "Bot has no position component {:?}", intent);
+            return OperationResult::InvalidInput;
+        }
+        Some(false) => return OperationResult::NotInRange,
+        Some(true) => {}
+    }
+
+    match check_site_placement(intent.owner_id, intent.kind, intent.position, views) {
+        Ok(_) => OperationResult::Ok,
+        Err(PlaceStructureError::TooManySites { .. }) => OperationResult::Full,
+        Err(err) => {
+            debug!(logger, "Invalid construction site {:?}: {}", intent, err);
+            OperationResult::InvalidTarget
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::components::{
     Bot, CarryComponent, EnergyComponent, OwnedEntity, PositionComponent, Resource,
+    RoomControllerComponent, Structure,
 };
 use crate::indices::{EntityId, UserId};
 use crate::scripting_api::OperationResult;
@@ -24,18 +25,24 @@ type CheckInput<'a> = (
     View<'a, EntityId, PositionComponent>,
     View<'a, EntityId, CarryComponent>,
     View<'a, EntityId, EnergyComponent>,
+    View<'a, EntityId, Structure>,
+    View<'a, EntityId, RoomControllerComponent>,
 );
 
 /// A valid dropoff intent has the following characteristics:
 /// - the bot is owned by the user
 /// - the bot is carrying resource of type `ty`
-/// - the target is not full
+/// - the target can hold `ty` and is not full
 /// - the target is within dropoff range
+///
+/// Energy dropped off at a room controller of the user upgrades the controller.
+/// Energy is stored in the `EnergyComponent` of the target, if it has one. Otherwise resources
+/// are stored in the `CarryComponent` of the target structure.
 pub fn check_dropoff_intent(
     logger: &Logger,
     intent: &DropoffIntent,
     userid: UserId,
-    (bots, owners, positions, carry, energy): CheckInput,
+    (bots, owners, positions, carry, energy, structures, controllers): CheckInput,
 ) -> OperationResult {
     let id = intent.bot;
     match bots.get_by_id(&id) {
@@ -50,7 +57,7 @@ pub fn check_dropoff_intent(
 
     if carry
         .get_by_id(&id)
-        .map(|carry| carry.carry == 0)
+        .map(|carry| carry.get(intent.ty) == 0)
         .unwrap_or(true)
     {
         return OperationResult::Empty;
@@ -73,16 +80,39 @@ pub fn check_dropoff_intent(
         }
         Some(false) => OperationResult::NotInRange,
         Some(true) => {
-            let capacity = energy.get_by_id(&target);
-            if capacity.is_none() {
-                debug!(logger, "Target has no energy component {:?}", intent);
-                return OperationResult::InvalidInput;
+            if let Some(controller) = controllers.get_by_id(&target) {
+                if intent.ty != Resource::Energy {
+                    return OperationResult::InvalidInput;
+                }
+                let owner_id = owners.get_by_id(&target);
+                if owner_id.map(|id| id.owner_id != userid).unwrap_or(true) {
+                    return OperationResult::NotOwner;
+                }
+                return if controller.progress < controller.progress_max {
+                    OperationResult::Ok
+                } else {
+                    OperationResult::Full
+                };
             }
-            let capacity = capacity.unwrap();
-            if capacity.energy < capacity.energy_max {
-                OperationResult::Ok
-            } else {
-                OperationResult::Full
+            if intent.ty == Resource::Energy {
+                if let Some(capacity) = energy.get_by_id(&target) {
+                    return if capacity.energy < capacity.energy_max {
+                        OperationResult::Ok
+                    } else {
+                        OperationResult::Full
+                    };
+                }
+            }
+            match carry
+                .get_by_id(&target)
+                .filter(|_| structures.contains_id(&target))
+            {
+                Some(store) if store.is_full() => OperationResult::Full,
+                Some(_) => OperationResult::Ok,
+                None => {
+                    debug!(logger, "Target can not store {:?} {:?}", intent.ty, intent);
+                    OperationResult::InvalidInput
+                }
             }
         }
     }
@@ -0,0 +1,136 @@
+use super::check_range;
+use crate::components::{
+    Bot, CarryComponent, HealComponent, HpComponent, OwnedEntity, PositionComponent, Resource,
+    Structure,
+};
+use crate::indices::{EntityId, UserId};
+use crate::scripting_api::OperationResult;
+use crate::storage::views::View;
+use serde::{Deserialize, Serialize};
+use slog::{debug, Logger};
+
+pub const HEAL_RANGE: u32 = 1;
+pub const REPAIR_RANGE: u32 = 3;
+/// Maximum amount of hp a bot repairs in a single tick
+pub const REPAIR_POWER: u16 = 50;
+/// Hp repaired for a single unit of energy
+pub const REPAIR_HP_PER_ENERGY: u16 = 5;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HealIntent {
+    pub healer: EntityId,
+    pub target: EntityId,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepairIntent {
+    pub bot: EntityId,
+    pub structure: EntityId,
+}
+
+type HealCheckInput<'a> = (
+    View<'a, EntityId, Bot>,
+    View<'a, EntityId, OwnedEntity>,
+    View<'a, EntityId, PositionComponent>,
+    View<'a, EntityId, HealComponent>,
+    View<'a, EntityId, HpComponent>,
+);
+
+/// A valid heal intent has the following characteristics:
+/// - the healer is owned by the user and has a `HealComponent`
+/// - the target is a damaged bot
+/// - the target is within heal range
+pub fn check_heal_intent(
+    logger: &Logger,
+    intent: &HealIntent,
+    user_id: UserId,
+    (bots, owners, positions, heals, hps): HealCheckInput,
+) -> OperationResult {
+    if owners
+        .get_by_id(&intent.healer)
+        .map(|o| o.owner_id != user_id)
+        .unwrap_or(true)
+    {
+        return OperationResult::NotOwner;
+    }
+    if heals.get_by_id(&intent.healer).is_none() {
+        debug!(logger, "{:?} has no HealComponent", intent.healer);
+        return OperationResult::InvalidInput;
+    }
+    if bots.get_by_id(&intent.target).is_none() {
+        debug!(logger, "{:?} is not a bot", intent.target);
+        return OperationResult::InvalidTarget;
+    }
+    match hps.get_by_id(&intent.target) {
+        Some(hp) if hp.hp >= hp.hp_max => return OperationResult::Full,
+        Some(_) => {}
+        None => {
+            debug!(logger, "{:?} has no HpComponent", intent.target);
+            return OperationResult::InvalidTarget;
+        }
+    }
+    check_range(logger, intent.healer, intent.target, HEAL_RANGE, positions)
+}
+
+type RepairCheckInput<'a> = (
+    View<'a, EntityId, Bot>,
+    View<'a, EntityId, OwnedEntity>,
+    View<'a, EntityId, PositionComponent>,
+    View<'a, EntityId, CarryComponent>,
+    View<'a, EntityId, Structure>,
+    View<'a, EntityId, HpComponent>,
+);
+
+/// A valid repair intent has the following characteristics:
+/// - the bot is owned by the user
+/// - the bot is carrying energy
+/// - the target is a damaged structure of the user
+/// - the target is within repair range
+pub fn check_repair_intent(
+    logger: &Logger,
+    intent: &RepairIntent,
+    user_id: UserId,
+    (bots, owners, positions, carry, structures, hps): RepairCheckInput,
+) -> OperationResult {
+    let is_owned = |id: &EntityId| {
+        owners
+            .get_by_id(id)
+            .map(|owner| owner.owner_id == user_id)
+            .unwrap_or(false)
+    };
+    if bots.get_by_id(&intent.bot).is_none() {
+        return OperationResult::InvalidInput;
+    }
+    if !is_owned(&intent.bot) {
+        return OperationResult::NotOwner;
+    }
+    if carry
+        .get_by_id(&intent.bot)
+        .map(|carry| carry.get(Resource::Energy) == 0)
+        .unwrap_or(true)
+    {
+        return OperationResult::Empty;
+    }
+    if !structures.contains_id(&intent.structure) {
+        debug!(logger, "{:?} is not a structure", intent.structure);
+        return OperationResult::InvalidTarget;
+    }
+    if !is_owned(&intent.structure) {
+        return OperationResult::NotOwner;
+    }
+    match hps.get_by_id(&intent.structure) {
+        Some(hp) if hp.hp >= hp.hp_max => return OperationResult::Full,
+        Some(_) => {}
+        None => {
+            debug!(logger, "{:?} has no HpComponent", intent.structure);
+            return OperationResult::InvalidTarget;
+        }
+    }
+    check_range(
+        logger,
+        intent.bot,
+        intent.structure,
+        REPAIR_RANGE,
+        positions,
+    )
+}
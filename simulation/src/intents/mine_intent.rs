@@ -1,7 +1,8 @@
 use crate::components;
-use crate::indices::{EntityId, UserId};
+use crate::indices::{ConfigKey, EntityId, Room, UserId};
+use crate::room_rules::check_mine_rules;
 use crate::scripting_api::OperationResult;
-use crate::storage::views::View;
+use crate::storage::views::{UnwrapView, View};
 use crate::tables::traits::Table;
 use serde::{Deserialize, Serialize};
 use slog::{debug, Logger};
@@ -17,15 +18,27 @@ type CheckInput<'a> = (
     View<'a, EntityId, components::OwnedEntity>,
     View<'a, EntityId, components::PositionComponent>,
     View<'a, EntityId, components::ResourceComponent>,
-    View<'a, EntityId, components::EnergyComponent>,
+    View<'a, EntityId, components::ResourceAmountComponent>,
     View<'a, EntityId, components::CarryComponent>,
+    View<'a, Room, components::OwnedEntity>,
+    UnwrapView<'a, ConfigKey, components::game_config::GameConfig>,
 );
 
+/// The room of the resource must allow the user to mine, see `room_rules::check_mine_rules`
 pub fn check_mine_intent(
     logger: &Logger,
     intent: &MineIntent,
     userid: UserId,
-    (bots_table, owner_ids_table, positions_table, resources_table, energy_table, carry_table): CheckInput,
+    (
+        bots_table,
+        owner_ids_table,
+        positions_table,
+        resources_table,
+        amount_table,
+        carry_table,
+        room_owners,
+        config,
+    ): CheckInput,
 ) -> OperationResult {
     let bot = intent.bot;
     match bots_table.get_by_id(&bot) {
@@ -57,7 +70,7 @@ pub fn check_mine_intent(
 
     match carry_table.get_by_id(&bot) {
         Some(carry) => {
-            if carry.carry >= carry.carry_max {
+            if carry.is_full() {
                 debug!(logger, "{:?} is full", bot);
                 return OperationResult::Full;
             }
@@ -72,25 +85,33 @@ pub fn check_mine_intent(
         return OperationResult::NotInRange;
     }
 
+    if let Err(err) = check_mine_rules(
+        &config.room_rules,
+        userid,
+        Room(mineralpos.0.room),
+        room_owners,
+    ) {
+        debug!(logger, "{:?} can not be mined: {}", target, err);
+        return OperationResult::InvalidTarget;
+    }
+
     match resources_table.get_by_id(&target) {
-        Some(components::ResourceComponent(components::Resource::Energy)) => {
-            match energy_table.get_by_id(&target) {
-                Some(energy) => {
-                    if energy.energy > 0 {
-                        OperationResult::Ok
-                    } else {
-                        OperationResult::Empty
-                    }
-                }
-                None => {
-                    debug!(logger, "Mineral has no energy component!");
-                    OperationResult::InvalidInput
-                }
-            }
-        }
-        Some(_) | None => {
+        Some(components::ResourceComponent(components::Resource::Empty)) | None => {
             debug!(logger, "{:?} is not a resource!", target);
             OperationResult::InvalidInput
         }
+        Some(components::ResourceComponent(_)) => match amount_table.get_by_id(&target) {
+            Some(amount) => {
+                if amount.amount > 0 {
+                    OperationResult::Ok
+                } else {
+                    OperationResult::Empty
+                }
+            }
+            None => {
+                debug!(logger, "Mineral has no amount component!");
+                OperationResult::InvalidInput
+            }
+        },
     }
 }
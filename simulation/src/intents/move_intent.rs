@@ -22,13 +22,14 @@ type CheckInput<'a> = (
     View<'a, EntityId, components::Bot>,
     View<'a, WorldPosition, components::TerrainComponent>,
     View<'a, WorldPosition, EntityComponent>,
+    View<'a, EntityId, components::FatigueComponent>,
 );
 
 pub fn check_move_intent(
     logger: &Logger,
     intent: &MoveIntent,
     user_id: UserId,
-    (owner_ids, positions, bots, terrain, entity_positions): CheckInput,
+    (owner_ids, positions, bots, terrain, entity_positions, fatigue): CheckInput,
 ) -> OperationResult {
     let id = intent.bot;
     match bots.get_by_id(&id) {
@@ -41,6 +42,15 @@ pub fn check_move_intent(
         None => return OperationResult::InvalidInput,
     };
 
+    if fatigue
+        .get_by_id(&id)
+        .map(|f| f.fatigue > 0)
+        .unwrap_or(false)
+    {
+        trace!(logger, "Bot is fatigued");
+        return OperationResult::Fatigued;
+    }
+
     let pos = match positions.get_by_id(&id) {
         Some(pos) => pos,
         None => {
@@ -67,7 +77,11 @@ pub fn check_move_intent(
         debug!(logger, "Position is occupied by terrain");
         return OperationResult::InvalidInput;
     }
-    if let Some(entity) = entity_positions.get_by_id(&intent.position) {
+    // bots may move out of the way in the same tick, the move system resolves those
+    if let Some(entity) = entity_positions
+        .get_by_id(&intent.position)
+        .filter(|EntityComponent(id)| bots.get_by_id(id).is_none())
+    {
         debug!(
             logger,
             "Position is occupied by another entity {:?}", entity
@@ -1,8 +1,56 @@
-use crate::indices::{EntityId, UserId};
+use crate::components::{game_config::GameConfig, OwnedEntity, SpawnQueueComponent};
+use crate::indices::{ConfigKey, EntityId, UserId};
+use crate::scripting_api::OperationResult;
+use crate::storage::views::{UnwrapView, View};
 use serde::{Deserialize, Serialize};
+use slog::{debug, Logger};
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// Maximum number of bots waiting in a spawn queue
+pub const SPAWN_QUEUE_LEN: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpawnIntent {
     pub spawn_id: EntityId,
     pub owner_id: Option<UserId>,
+    /// Name of the bot template, see `GameConfig::bot_templates`
+    pub template: String,
+    /// The group the new bot joins, see `BotGroups`
+    pub group: Option<String>,
+}
+
+type CheckInput<'a> = (
+    View<'a, EntityId, OwnedEntity>,
+    View<'a, EntityId, SpawnQueueComponent>,
+    UnwrapView<'a, ConfigKey, GameConfig>,
+);
+
+/// `spawn_id` must be a spawn owned by the user.
+/// `template` must be a bot template of the game config.
+/// The queue of the spawn must not be full.
+pub fn check_spawn_intent(
+    logger: &Logger,
+    intent: &SpawnIntent,
+    user_id: UserId,
+    (owners, spawn_queues, conf): CheckInput,
+) -> OperationResult {
+    match owners.get_by_id(&intent.spawn_id) {
+        Some(owner) if owner.owner_id == user_id => {}
+        Some(_) => return OperationResult::NotOwner,
+        None => {
+            debug!(logger, "Spawn {:?} has no owner", intent.spawn_id);
+            return OperationResult::InvalidTarget;
+        }
+    }
+    if !conf.bot_templates.contains_key(&intent.template) {
+        debug!(logger, "Bot template {:?} does not exist", intent.template);
+        return OperationResult::InvalidInput;
+    }
+    match spawn_queues.get_by_id(&intent.spawn_id) {
+        Some(queue) if queue.queue.len() >= SPAWN_QUEUE_LEN => OperationResult::Full,
+        Some(_) => OperationResult::Ok,
+        None => {
+            debug!(logger, "{:?} is not a spawn", intent.spawn_id);
+            OperationResult::InvalidTarget
+        }
+    }
 }
@@ -0,0 +1,163 @@
+use super::check_range;
+use crate::components::{
+    Bot, CarryComponent, OwnedEntity, PositionComponent, Resource, ResourcePileComponent,
+};
+use crate::indices::{EntityId, UserId};
+use crate::scripting_api::OperationResult;
+use crate::storage::views::View;
+use serde::{Deserialize, Serialize};
+use slog::{debug, Logger};
+
+pub const TRANSFER_RANGE: u32 = 1;
+pub const PICKUP_RANGE: u32 = 1;
+
+/// Move resources from a bot to another one
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TransferIntent {
+    pub bot: EntityId,
+    pub target: EntityId,
+    pub amount: u16,
+    pub ty: Resource,
+}
+
+/// Drop resources on the ground, adding them to the pile at the bot's position
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DropIntent {
+    pub bot: EntityId,
+    pub amount: u16,
+    pub ty: Resource,
+}
+
+/// Pick up as much of a pile as the bot can carry
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PickupIntent {
+    pub bot: EntityId,
+    pub pile: EntityId,
+}
+
+type TransferCheckInput<'a> = (
+    View<'a, EntityId, Bot>,
+    View<'a, EntityId, OwnedEntity>,
+    View<'a, EntityId, PositionComponent>,
+    View<'a, EntityId, CarryComponent>,
+);
+
+/// A valid transfer intent has the following characteristics:
+/// - the bot is owned by the user
+/// - the bot is carrying resource of type `ty`
+/// - the target is another bot, that is not full
+/// - the target is within transfer range
+pub fn check_transfer_intent(
+    logger: &Logger,
+    intent: &TransferIntent,
+    user_id: UserId,
+    (bots, owners, positions, carry): TransferCheckInput,
+) -> OperationResult {
+    if let Some(res) = check_bot(intent.bot, user_id, bots, owners) {
+        return res;
+    }
+    if carry
+        .get_by_id(&intent.bot)
+        .map(|carry| carry.get(intent.ty) == 0)
+        .unwrap_or(true)
+    {
+        return OperationResult::Empty;
+    }
+    if intent.bot == intent.target || bots.get_by_id(&intent.target).is_none() {
+        debug!(logger, "{:?} is not a valid transfer target", intent.target);
+        return OperationResult::InvalidTarget;
+    }
+    match carry.get_by_id(&intent.target) {
+        Some(target) if target.is_full() => return OperationResult::Full,
+        Some(_) => {}
+        None => {
+            debug!(logger, "{:?} has no carry component", intent.target);
+            return OperationResult::InvalidTarget;
+        }
+    }
+    check_range(logger, intent.bot, intent.target, TRANSFER_RANGE, positions)
+}
+
+type DropCheckInput<'a> = (
+    View<'a, EntityId, Bot>,
+    View<'a, EntityId, OwnedEntity>,
+    View<'a, EntityId, CarryComponent>,
+);
+
+/// A valid drop intent has the following characteristics:
+/// - the bot is owned by the user
+/// - the bot is carrying resource of type `ty`
+pub fn check_drop_intent(
+    _logger: &Logger,
+    intent: &DropIntent,
+    user_id: UserId,
+    (bots, owners, carry): DropCheckInput,
+) -> OperationResult {
+    if let Some(res) = check_bot(intent.bot, user_id, bots, owners) {
+        return res;
+    }
+    if carry
+        .get_by_id(&intent.bot)
+        .map(|carry| carry.get(intent.ty) == 0)
+        .unwrap_or(true)
+    {
+        return OperationResult::Empty;
+    }
+    OperationResult::Ok
+}
+
+type PickupCheckInput<'a> = (
+    View<'a, EntityId, Bot>,
+    View<'a, EntityId, OwnedEntity>,
+    View<'a, EntityId, PositionComponent>,
+    View<'a, EntityId, CarryComponent>,
+    View<'a, EntityId, ResourcePileComponent>,
+);
+
+/// A valid pickup intent has the following characteristics:
+/// - the bot is owned by the user and is not full
+/// - the target is a resource pile
+/// - the pile is within pickup range
+pub fn check_pickup_intent(
+    logger: &Logger,
+    intent: &PickupIntent,
+    user_id: UserId,
+    (bots, owners, positions, carry, piles): PickupCheckInput,
+) -> OperationResult {
+    if let Some(res) = check_bot(intent.bot, user_id, bots, owners) {
+        return res;
+    }
+    match carry.get_by_id(&intent.bot) {
+        Some(carry) if carry.is_full() => return OperationResult::Full,
+        Some(_) => {}
+        None => {
+            debug!(logger, "{:?} has no carry component", intent.bot);
+            return OperationResult::InvalidInput;
+        }
+    }
+    if piles.get_by_id(&intent.pile).is_none() {
+        debug!(logger, "{:?} is not a resource pile", intent.pile);
+        return OperationResult::InvalidTarget;
+    }
+    check_range(logger, intent.bot, intent.pile, PICKUP_RANGE, positions)
+}
+
+/// Returns the result of the failed check, if any
+fn check_bot(
+    bot: EntityId,
+    user_id: UserId,
+    bots: View<EntityId, Bot>,
+    owners: View<EntityId, OwnedEntity>,
+) -> Option<OperationResult> {
+    if bots.get_by_id(&bot).is_none() {
+        return Some(OperationResult::InvalidInput);
+    }
+    if owners
+        .get_by_id(&bot)
+        .map(|owner| owner.owner_id != user_id)
+        .unwrap_or(true)
+    {
+        return Some(OperationResult::NotOwner);
+    }
+    None
+}
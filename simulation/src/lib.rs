@@ -1,17 +1,23 @@
 use prelude::{Component, World};
 use tables::{unique::UniqueTable, TableId};
 
+pub mod combat;
 pub mod components;
 pub mod executor;
 pub mod geometry;
+pub mod harness;
 pub mod indices;
 pub mod map_generation;
+pub mod npc;
 pub mod pathfinding;
 pub mod prelude;
+pub mod room_rules;
 pub mod scripting_api;
 pub mod storage;
+pub mod structures;
 pub mod tables;
 pub mod terrain;
+pub mod visibility;
 
 mod intents;
 mod systems;
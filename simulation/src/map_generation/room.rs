@@ -157,6 +157,7 @@ pub fn generate_room(
             radius: radius - 1,
             chance_plain: params.chance_plain,
             chance_wall: params.chance_wall,
+            chance_swamp: params.chance_swamp,
         },
         &gradient,
         terrain,
@@ -456,6 +457,7 @@ struct HeightMapTransformParams {
     radius: i32,
     chance_plain: f32,
     chance_wall: f32,
+    chance_swamp: f32,
 }
 
 fn transform_heightmap_into_terrain(
@@ -468,6 +470,7 @@ fn transform_heightmap_into_terrain(
         radius,
         chance_plain,
         chance_wall,
+        chance_swamp,
     }: HeightMapTransformParams,
     gradient: &MortonTable<Axial, f32>,
     mut terrain: UnsafeView<Axial, TerrainComponent>,
@@ -524,7 +527,10 @@ fn transform_heightmap_into_terrain(
             if !grad.is_finite() {
                 return None;
             }
-            let terrain = if grad <= chance_plain {
+            // swamps are the lowest lying plains
+            let terrain = if grad <= chance_swamp {
+                TileTerrainType::Swamp
+            } else if grad <= chance_plain {
                 TileTerrainType::Plain
             } else if grad <= chance_plain + chance_wall {
                 TileTerrainType::Wall
@@ -710,6 +716,7 @@ fn print_terrain(from: Axial, to: Axial, terrain: View<Axial, TerrainComponent>)
                 Some(TerrainComponent(TileTerrainType::Wall)) => print!("#"),
                 Some(TerrainComponent(TileTerrainType::Plain)) => print!("."),
                 Some(TerrainComponent(TileTerrainType::Bridge)) => print!("x"),
+                Some(TerrainComponent(TileTerrainType::Swamp)) => print!("~"),
                 None => print!(" "),
             }
         }
@@ -721,8 +728,10 @@ fn print_terrain(from: Axial, to: Axial, terrain: View<Axial, TerrainComponent>)
 mod tests {
     use super::*;
     use crate::components::EntityComponent;
+    use crate::indices::Room;
     use crate::pathfinding::find_path_in_room;
     use crate::storage::views::View;
+    use crate::tables::btree::BTreeTable;
     use crate::utils::*;
     use rand::rngs::SmallRng;
     use rand::SeedableRng;
@@ -770,7 +779,8 @@ mod tests {
             match terrain.get_by_id(&point) {
                 None => seen_empty = true,
                 Some(TerrainComponent(TileTerrainType::Plain))
-                | Some(TerrainComponent(TileTerrainType::Bridge)) => seen_plain = true,
+                | Some(TerrainComponent(TileTerrainType::Bridge))
+                | Some(TerrainComponent(TileTerrainType::Swamp)) => seen_plain = true,
                 Some(TerrainComponent(TileTerrainType::Wall)) => seen_wall = true,
             }
         }
@@ -816,6 +826,7 @@ mod tests {
         print_terrain(from, to, View::from_table(&terrain));
 
         let positions = MortonTable::<Axial, EntityComponent>::new();
+        let roads = BTreeTable::new();
         let mut path = Vec::with_capacity(1024);
 
         let first = plains.iter().next().expect("at least 1 plain");
@@ -824,9 +835,14 @@ mod tests {
             path.clear();
             if let Err(e) = find_path_in_room(
                 &logger,
+                Room(Axial::new(0, 0)),
                 *first,
                 *b,
-                (View::from_table(&positions), View::from_table(&terrain)),
+                (
+                    View::from_table(&positions),
+                    View::from_table(&terrain),
+                    View::from_table(&roads),
+                ),
                 1024,
                 &mut path,
             ) {
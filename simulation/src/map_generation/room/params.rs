@@ -3,7 +3,11 @@ use thiserror::Error;
 #[derive(Debug, Clone, Error)]
 pub enum RoomGenerationParamsError {
     #[error("Tile probabilities must be in interval [0, 1.0) and their sum must be less than 1! {self:?}")]
-    BadProbabilities { chance_plain: f32, chance_wall: f32 },
+    BadProbabilities {
+        chance_plain: f32,
+        chance_wall: f32,
+        chance_swamp: f32,
+    },
 
     #[error("Radius must be at least 4, got {radius}")]
     BadRadius { radius: u32 },
@@ -15,6 +19,8 @@ pub struct RoomGenerationParams {
     pub(crate) plain_dilation: u32,
     pub(crate) chance_plain: f32,
     pub(crate) chance_wall: f32,
+    /// Part of `chance_plain` that is turned into swamps
+    pub(crate) chance_swamp: f32,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -23,6 +29,7 @@ pub struct RoomGenerationParamsBuilder {
     pub plain_dilation: u32,
     pub chance_plain: f32,
     pub chance_wall: f32,
+    pub chance_swamp: f32,
 }
 
 impl RoomGenerationParams {
@@ -44,10 +51,14 @@ impl RoomGenerationParamsBuilder {
             || 1.0 <= self.chance_wall
             || self.chance_plain < 0.0
             || 1.0 < self.chance_wall + self.chance_plain
+            || !self.chance_swamp.is_finite()
+            || self.chance_swamp < 0.0
+            || self.chance_plain < self.chance_swamp
         {
             return Err(RoomGenerationParamsError::BadProbabilities {
                 chance_plain: self.chance_plain,
                 chance_wall: self.chance_wall,
+                chance_swamp: self.chance_swamp,
             });
         }
         if self.radius == 0 {
@@ -60,6 +71,7 @@ impl RoomGenerationParamsBuilder {
             plain_dilation: self.plain_dilation,
             chance_plain: self.chance_plain,
             chance_wall: self.chance_wall,
+            chance_swamp: self.chance_swamp,
         })
     }
 
@@ -82,4 +94,9 @@ impl RoomGenerationParamsBuilder {
         self.chance_wall = chance_wall;
         self
     }
+
+    pub fn with_chance_swamp(mut self, chance_swamp: f32) -> Self {
+        self.chance_swamp = chance_swamp;
+        self
+    }
 }
@@ -0,0 +1,219 @@
+//! The NPC faction.
+//!
+//! Invaders are spawned in wealthy rooms by the invader system, see `GameConfig::invaders`.
+//! They are owned by `npc_user_id` and go through the same intent checks and systems as the bots
+//! of the users.
+//! Invaders with an `EntityScript` run it like any other bot, the rest are driven by
+//! `invader_intents`.
+//!
+use crate::components::{
+    EntityComponent, EntityScript, HpComponent, MeleeAttackComponent, NpcComponent, OwnedEntity,
+    PositionComponent, RangedAttackComponent,
+};
+use crate::indices::{EntityId, UserId, WorldPosition};
+use crate::intents::{
+    check_melee_intent, check_ranged_intent, BotIntents, MeleeIntent, RangedIntent,
+};
+use crate::profile;
+use crate::scripting_api::bots::move_to_pos;
+use crate::scripting_api::OperationResult;
+use crate::storage::views::FromWorld;
+use crate::world::World;
+use slog::{o, trace};
+
+/// Owner of the NPC entities
+pub fn npc_user_id() -> UserId {
+    UserId(uuid::Uuid::from_u128(
+        0x4e50_4300_0000_4000_8000_0000_0000_0001,
+    ))
+}
+
+/// Built-in AI of the invaders without a script.
+/// Invaders attack the closest entity of a user in their room, approaching it if out of range.
+pub fn invader_intents(storage: &World) -> Vec<BotIntents> {
+    profile!("invader_intents");
+
+    let logger = storage.logger.new(o!("tick" => storage.time()));
+    let user_id = npc_user_id();
+    let scripts = storage.view::<EntityId, EntityScript>();
+    let positions = storage.view::<EntityId, PositionComponent>();
+    let owners = storage.view::<EntityId, OwnedEntity>();
+    let hps = storage.view::<EntityId, HpComponent>();
+    let melee = storage.view::<EntityId, MeleeAttackComponent>();
+    let ranged = storage.view::<EntityId, RangedAttackComponent>();
+    let entities_by_pos = storage.view::<WorldPosition, EntityComponent>();
+
+    let mut intents = Vec::new();
+    for (entity_id, _) in storage.view::<EntityId, NpcComponent>().iter() {
+        if scripts.contains_id(&entity_id) {
+            continue;
+        }
+        let pos = match positions.get_by_id(&entity_id) {
+            Some(PositionComponent(pos)) => *pos,
+            None => continue,
+        };
+        let target = entities_by_pos
+            .table
+            .get_by_id(&pos.room)
+            .and_then(|room| {
+                room.find_closest_by_filter(&pos.pos, |_, EntityComponent(id)| {
+                    hps.contains_id(id)
+                        && owners
+                            .get_by_id(id)
+                            .map(|OwnedEntity { owner_id }| *owner_id != user_id)
+                            .unwrap_or(false)
+                })
+            })
+            .map(|(_, target_pos, EntityComponent(id))| {
+                (
+                    *id,
+                    WorldPosition {
+                        room: pos.room,
+                        pos: target_pos,
+                    },
+                )
+            });
+        let (target, target_pos) = match target {
+            Some(target) => target,
+            None => {
+                trace!(logger, "Invader {:?} has no target", entity_id);
+                continue;
+            }
+        };
+
+        let mut intent = BotIntents {
+            entity_id,
+            ..Default::default()
+        };
+        if melee.contains(&entity_id) {
+            let melee_intent = MeleeIntent {
+                attacker: entity_id,
+                defender: target,
+            };
+            if let OperationResult::Ok =
+                check_melee_intent(&logger, &melee_intent, user_id, FromWorld::new(storage))
+            {
+                intent.melee_attack_intent = Some(melee_intent);
+            }
+        }
+        if intent.melee_attack_intent.is_none() && ranged.contains(&entity_id) {
+            let ranged_intent = RangedIntent {
+                attacker: entity_id,
+                defender: target,
+            };
+            if let OperationResult::Ok =
+                check_ranged_intent(&logger, &ranged_intent, user_id, FromWorld::new(storage))
+            {
+                intent.ranged_attack_intent = Some(ranged_intent);
+            }
+        }
+        if intent.melee_attack_intent.is_none() && intent.ranged_attack_intent.is_none() {
+            match move_to_pos(&logger, entity_id, target_pos, user_id, storage) {
+                Ok(Some((move_intent, pop_cache_intent, update_cache_intent))) => {
+                    intent.move_intent = Some(move_intent);
+                    intent.mut_path_cache_intent = pop_cache_intent;
+                    intent.update_path_cache_intent = update_cache_intent;
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    trace!(
+                        logger,
+                        "Invader {:?} can not approach {:?}: {:?}",
+                        entity_id,
+                        target,
+                        err
+                    );
+                    continue;
+                }
+            }
+        }
+        intents.push(intent);
+    }
+    intents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::game_config::GameConfig;
+    use crate::harness::{build_world, Fixture};
+    use crate::indices::ConfigKey;
+
+    #[test]
+    fn invaders_approach_then_attack_users() {
+        crate::utils::setup_testing();
+        let fixture: Fixture = serde_json::from_str(
+            r#"{
+                "roomRadius": 5,
+                "rooms": [ { "room": { "q": 0, "r": 0 }, "defaultTerrain": "plain" } ],
+                "entities": [
+                    {
+                        "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 5, "r": 5 } },
+                        "owner": "00000000-0000-0000-0000-000000000000",
+                        "bot": true,
+                        "hp": { "hp": 100, "hpMax": 100 }
+                    },
+                    {
+                        "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 2, "r": 5 } },
+                        "bot": true,
+                        "hp": { "hp": 100, "hpMax": 100 }
+                    }
+                ]
+            }"#,
+        )
+        .expect("Failed to parse fixture");
+        let mut world =
+            build_world(crate::utils::test_logger(), &fixture).expect("Failed to build the world");
+        let victim = EntityId(0);
+        let invader = EntityId(1);
+        let strength = world
+            .view::<ConfigKey, GameConfig>()
+            .unwrap_value()
+            .bot_templates["warrior"]
+            .melee_strength
+            .unwrap();
+        world
+            .unsafe_view::<EntityId, NpcComponent>()
+            .insert(invader);
+        world
+            .unsafe_view::<EntityId, OwnedEntity>()
+            .insert_or_update(
+                invader,
+                OwnedEntity {
+                    owner_id: npc_user_id(),
+                },
+            );
+        world
+            .unsafe_view::<EntityId, MeleeAttackComponent>()
+            .insert_or_update(
+                invader,
+                MeleeAttackComponent {
+                    strength,
+                    ..Default::default()
+                },
+            );
+
+        let intents = invader_intents(&*world);
+        assert_eq!(intents.len(), 1);
+        assert!(intents[0].melee_attack_intent.is_none());
+        let move_intent = intents[0].move_intent.as_ref().expect("move intent");
+        assert_eq!(move_intent.bot, invader);
+
+        // next to the victim
+        world
+            .unsafe_view::<EntityId, PositionComponent>()
+            .insert_or_update(
+                invader,
+                PositionComponent(WorldPosition {
+                    room: crate::geometry::Axial::new(0, 0),
+                    pos: crate::geometry::Axial::new(4, 5),
+                }),
+            );
+        let intents = invader_intents(&*world);
+        let melee_intent = intents[0]
+            .melee_attack_intent
+            .as_ref()
+            .expect("melee intent");
+        assert_eq!(melee_intent.defender, victim);
+    }
+}
@@ -1,4 +1,6 @@
-use crate::components::{EntityComponent, RoomConnections, RoomProperties, TerrainComponent};
+use crate::components::{
+    EntityComponent, RoadComponent, RoomConnections, RoomProperties, TerrainComponent,
+};
 use crate::geometry::Axial;
 use crate::indices::{ConfigKey, Room, RoomPosition, WorldPosition};
 use crate::map_generation::room::iter_edge;
@@ -68,6 +70,7 @@ pub enum PathFindingError {
 type FindPathTables<'a> = (
     View<'a, WorldPosition, EntityComponent>,
     View<'a, WorldPosition, TerrainComponent>,
+    View<'a, WorldPosition, RoadComponent>,
     View<'a, Room, RoomConnections>,
     View<'a, ConfigKey, RoomProperties>,
 );
@@ -81,7 +84,7 @@ pub fn find_path(
     logger: &Logger,
     from: WorldPosition,
     to: WorldPosition,
-    (positions, terrain, connections, room_properties): FindPathTables,
+    (positions, terrain, roads, connections, room_properties): FindPathTables,
     max_steps: u32,
     path: &mut Vec<RoomPosition>,
     rooms_to_visit: &mut Vec<Room>,
@@ -99,9 +102,10 @@ pub fn find_path(
     if from.room == to.room {
         find_path_in_room(
             logger,
+            Room(from.room),
             from.pos,
             to.pos,
-            (positions, terrain),
+            (positions, terrain, roads),
             max_steps,
             path,
         )
@@ -110,7 +114,7 @@ pub fn find_path(
             logger,
             from,
             to,
-            (positions, terrain, connections, room_properties),
+            (positions, terrain, roads, connections, room_properties),
             max_steps,
             path,
             rooms_to_visit,
@@ -121,6 +125,7 @@ pub fn find_path(
 type FindPathMultiRoomTables<'a> = (
     View<'a, Axial, EntityComponent>,
     View<'a, Axial, TerrainComponent>,
+    View<'a, WorldPosition, RoadComponent>,
     View<'a, Room, RoomConnections>,
     View<'a, ConfigKey, RoomProperties>,
 );
@@ -129,7 +134,7 @@ fn find_path_multiroom(
     logger: &Logger,
     from: WorldPosition,
     to: WorldPosition,
-    (positions, terrain, connections, room_properties): FindPathMultiRoomTables,
+    (positions, terrain, roads, connections, room_properties): FindPathMultiRoomTables,
     mut max_steps: u32,
     path: &mut Vec<RoomPosition>,
     rooms: &mut Vec<Room>,
@@ -193,7 +198,15 @@ fn find_path_multiroom(
     bridge.sort_unstable_by_key(|p| p.hex_distance(from.pos));
 
     'a: for p in bridge {
-        match find_path_in_room(logger, from.pos, p, (positions, terrain), max_steps, path) {
+        match find_path_in_room(
+            logger,
+            Room(from_room),
+            from.pos,
+            p,
+            (positions, terrain, roads),
+            max_steps,
+            path,
+        ) {
             Ok(_) => {
                 break 'a;
             }
@@ -303,13 +316,38 @@ fn is_walkable(point: Axial, terrain: View<Axial, TerrainComponent>) -> bool {
         .unwrap_or(false)
 }
 
+/// Fatigue of moving onto the point, see `terrain::move_cost`
+fn move_cost(
+    room: Room,
+    point: Axial,
+    terrain: View<Axial, TerrainComponent>,
+    roads: View<WorldPosition, RoadComponent>,
+) -> i32 {
+    let has_road = roads.contains_key(&WorldPosition {
+        room: room.0,
+        pos: point,
+    });
+    let tile = terrain
+        .get_by_id(&point)
+        .map(|TerrainComponent(tile)| *tile)
+        .unwrap_or_default();
+    terrain::move_cost(tile, has_road) as i32
+}
+
+type FindPathInRoomTables<'a> = (
+    View<'a, Axial, EntityComponent>,
+    View<'a, Axial, TerrainComponent>,
+    View<'a, WorldPosition, RoadComponent>,
+);
+
 /// Returns the remaining steps.
-/// Uses the A* algorithm
+/// Uses the A* algorithm, weighted by the movement cost of the tiles
 pub fn find_path_in_room(
     logger: &Logger,
+    room: Room,
     from: Axial,
     to: Axial,
-    (positions, terrain): (View<Axial, EntityComponent>, View<Axial, TerrainComponent>),
+    (positions, terrain, roads): FindPathInRoomTables,
     mut max_steps: u32,
     path: &mut Vec<RoomPosition>,
 ) -> Result<u32, PathFindingError> {
@@ -322,12 +360,19 @@ pub fn find_path_in_room(
     let mut closed_set = HashMap::<Axial, Node>::with_capacity(max_steps as usize);
     let mut open_set = BinaryHeap::with_capacity(max_steps as usize);
 
-    let mut current = Node::new(current, current, current.hex_distance(end) as i32, 0);
+    // roads are the cheapest tiles, so the heuristic never overestimates the cost
+    let heuristic = |pos: Axial| pos.hex_distance(end) as i32 * terrain::ROAD_MOVE_COST as i32;
+
+    let mut current = Node::new(current, current, heuristic(current), 0);
     closed_set.insert(current.pos, current.clone());
     open_set.push(current.clone());
 
     while current.pos != end && !open_set.is_empty() && max_steps > 0 {
         current = open_set.pop().unwrap();
+        if current.pos != from && closed_set.contains_key(&current.pos) {
+            // already expanded via a cheaper route
+            continue;
+        }
         closed_set.insert(current.pos, current.clone());
         for point in current
             .pos
@@ -346,8 +391,8 @@ pub fn find_path_in_room(
             let node = Node::new(
                 point,
                 current.pos,
-                point.hex_distance(end) as i32,
-                current.g_cost + 1,
+                heuristic(point),
+                current.g_cost + move_cost(room, point, terrain, roads),
             );
             open_set.push(node);
         }
@@ -555,6 +600,7 @@ pub fn mirrored_room_position(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tables::btree::BTreeTable;
     use crate::tables::morton::MortonTable;
     use crate::terrain::TileTerrainType;
     use slog::{o, Drain};
@@ -566,6 +612,7 @@ mod tests {
 
         let logger = slog::Logger::root(slog_stdlog::StdLog.fuse(), o!());
         let positions = MortonTable::new();
+        let roads = BTreeTable::new();
         let terrain = MortonTable::from_iterator((0..25).flat_map(|x| {
             (0..25).map(move |y| {
                 let ty = if x == 3 && y <= 5 {
@@ -582,9 +629,14 @@ mod tests {
         let mut path = vec![];
         find_path_in_room(
             &logger,
+            Room(Axial::new(0, 0)),
             from,
             to,
-            (View::from_table(&positions), View::from_table(&terrain)),
+            (
+                View::from_table(&positions),
+                View::from_table(&terrain),
+                View::from_table(&roads),
+            ),
             512,
             &mut path,
         )
@@ -609,6 +661,7 @@ mod tests {
         let to = Axial::new(7, 16);
 
         let positions = MortonTable::new();
+        let roads = BTreeTable::new();
         let mut terrain = MortonTable::new();
 
         for x in 0..25 {
@@ -623,9 +676,14 @@ mod tests {
         let logger = slog::Logger::root(slog_stdlog::StdLog.fuse(), o!());
         find_path_in_room(
             &logger,
+            Room(Axial::new(0, 0)),
             from,
             to,
-            (View::from_table(&positions), View::from_table(&terrain)),
+            (
+                View::from_table(&positions),
+                View::from_table(&terrain),
+                View::from_table(&roads),
+            ),
             512,
             &mut path,
         )
@@ -643,4 +701,47 @@ mod tests {
         }
         assert_eq!(current, to);
     }
+
+    #[test]
+    fn test_path_avoids_swamp() {
+        let from = Axial::new(0, 2);
+        let to = Axial::new(6, 2);
+
+        let logger = slog::Logger::root(slog_stdlog::StdLog.fuse(), o!());
+        let positions = MortonTable::new();
+        let roads = BTreeTable::new();
+        let is_swamp = |p: Axial| 2 <= p.q && p.q <= 4 && p.r <= 3;
+        let terrain = MortonTable::from_iterator((0..25).flat_map(|x| {
+            (0..25).map(move |y| {
+                let p = Axial::new(x, y);
+                let ty = if is_swamp(p) {
+                    TileTerrainType::Swamp
+                } else {
+                    TileTerrainType::Plain
+                };
+                (p, TerrainComponent(ty))
+            })
+        }))
+        .unwrap();
+
+        let mut path = vec![];
+        find_path_in_room(
+            &logger,
+            Room(Axial::new(0, 0)),
+            from,
+            to,
+            (
+                View::from_table(&positions),
+                View::from_table(&terrain),
+                View::from_table(&roads),
+            ),
+            512,
+            &mut path,
+        )
+        .expect("Path finding failed");
+
+        // walking around the swamp is cheaper than wading through it
+        assert!(path.iter().all(|p| !is_swamp(p.0)), "{:?}", path);
+        assert_eq!(path.first().map(|p| p.0), Some(to));
+    }
 }
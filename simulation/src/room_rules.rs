@@ -0,0 +1,156 @@
+//! Rules of room ownership, configured per world by `GameConfig::room_rules`.
+//!
+//! Rooms are owned by the users holding their controller, see `structures::place_controller`.
+//!
+use crate::components::{game_config::RoomRules, OwnedEntity, RoomConnections};
+use crate::geometry::Axial;
+use crate::indices::{Room, UserId};
+use crate::storage::views::View;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Error)]
+pub enum RoomRuleError {
+    #[error("room {room:?} is owned by another user ({owner_id:?})")]
+    EnemyRoom { room: Room, owner_id: UserId },
+
+    #[error("room {0:?} is not owned by the user")]
+    NotOwned(Room),
+
+    #[error("room {0:?} is not connected to any room of the user")]
+    NotAdjacent(Room),
+}
+
+/// May the user place structures in the room
+pub fn check_build_rules(
+    rules: &RoomRules,
+    user_id: UserId,
+    room: Room,
+    room_owners: View<Room, OwnedEntity>,
+) -> Result<(), RoomRuleError> {
+    match room_owners.get_by_id(&room) {
+        Some(OwnedEntity { owner_id }) if *owner_id == user_id => Ok(()),
+        Some(OwnedEntity { owner_id }) if !rules.build_in_enemy_rooms => {
+            Err(RoomRuleError::EnemyRoom {
+                room,
+                owner_id: *owner_id,
+            })
+        }
+        None if !rules.build_in_unowned_rooms => Err(RoomRuleError::NotOwned(room)),
+        _ => Ok(()),
+    }
+}
+
+/// May the bots of the user mine resources in the room
+pub fn check_mine_rules(
+    rules: &RoomRules,
+    user_id: UserId,
+    room: Room,
+    room_owners: View<Room, OwnedEntity>,
+) -> Result<(), RoomRuleError> {
+    match room_owners.get_by_id(&room) {
+        Some(OwnedEntity { owner_id }) if *owner_id != user_id && !rules.mine_in_enemy_rooms => {
+            Err(RoomRuleError::EnemyRoom {
+                room,
+                owner_id: *owner_id,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// May the user claim the room, given the rooms they own already.
+/// The first room of a user may be anywhere, later rooms must be connected to an owned room.
+pub fn check_claim_rules(
+    rules: &RoomRules,
+    room: Room,
+    owned_rooms: &[Room],
+    connections: View<Room, RoomConnections>,
+) -> Result<(), RoomRuleError> {
+    if !rules.claim_adjacent_rooms_only || owned_rooms.is_empty() {
+        return Ok(());
+    }
+    let is_connected = |owned: &Room| {
+        Axial::neighbour_index(room.0 - owned.0)
+            .and_then(|i| connections.get_by_id(owned).map(|c| c.0[i].is_some()))
+            .unwrap_or(false)
+    };
+    if owned_rooms.iter().any(is_connected) {
+        Ok(())
+    } else {
+        Err(RoomRuleError::NotAdjacent(room))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::RoomConnection;
+    use crate::tables::morton::MortonTable;
+
+    #[test]
+    fn enemy_rooms_are_restricted() {
+        let user_id = UserId::default();
+        let enemy_id = UserId(uuid::Uuid::new_v4());
+        let owned = Room(Axial::new(0, 0));
+        let enemy = Room(Axial::new(1, 0));
+        let unowned = Room(Axial::new(2, 0));
+
+        let mut owners = MortonTable::new();
+        owners
+            .insert(owned, OwnedEntity { owner_id: user_id })
+            .unwrap();
+        owners
+            .insert(enemy, OwnedEntity { owner_id: enemy_id })
+            .unwrap();
+        let owners = View::from_table(&owners);
+
+        let rules = RoomRules::default();
+        assert!(check_build_rules(&rules, user_id, owned, owners).is_ok());
+        assert!(check_build_rules(&rules, user_id, unowned, owners).is_ok());
+        assert!(matches!(
+            check_build_rules(&rules, user_id, enemy, owners),
+            Err(RoomRuleError::EnemyRoom { .. })
+        ));
+        assert!(check_mine_rules(&rules, user_id, unowned, owners).is_ok());
+        assert!(check_mine_rules(&rules, user_id, enemy, owners).is_err());
+
+        let rules = RoomRules {
+            build_in_unowned_rooms: false,
+            mine_in_enemy_rooms: true,
+            ..rules
+        };
+        assert!(matches!(
+            check_build_rules(&rules, user_id, unowned, owners),
+            Err(RoomRuleError::NotOwned(_))
+        ));
+        assert!(check_mine_rules(&rules, user_id, enemy, owners).is_ok());
+    }
+
+    #[test]
+    fn claims_must_be_adjacent() {
+        let rules = RoomRules::default();
+        let room = |q| Room(Axial::new(q, 0));
+
+        // room 0 is connected to room 1, but not to its other neighbour, room -1
+        let mut connections = MortonTable::new();
+        let mut to_room_1 = RoomConnections::default();
+        to_room_1.0[0] = Some(RoomConnection {
+            direction: Axial::new(1, 0),
+            offset_start: 0,
+            offset_end: 0,
+        });
+        connections.insert(room(0), to_room_1).unwrap();
+        let connections = View::from_table(&connections);
+
+        assert!(check_claim_rules(&rules, room(5), &[], connections).is_ok());
+        assert!(check_claim_rules(&rules, room(1), &[room(0)], connections).is_ok());
+        assert!(matches!(
+            check_claim_rules(&rules, room(2), &[room(0)], connections),
+            Err(RoomRuleError::NotAdjacent(_))
+        ));
+        assert!(matches!(
+            check_claim_rules(&rules, room(-1), &[room(0)], connections),
+            Err(RoomRuleError::NotAdjacent(_))
+        ));
+    }
+}
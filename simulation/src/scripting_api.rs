@@ -4,11 +4,17 @@
 //!
 pub mod bots;
 pub mod find_api;
+pub mod geometry_api;
+pub mod map_api;
+pub mod random_api;
+pub mod schema;
+pub mod spawn_api;
+pub mod validation;
 use crate::components;
 use crate::geometry::point::Axial;
-use crate::indices::{EntityId, WorldPosition};
+use crate::indices::{EntityId, UserId, WorldPosition};
 use crate::profile;
-use crate::systems::script_execution::ScriptExecutionData;
+use crate::systems::script_execution::{cards_executed, ScriptExecutionData};
 use arrayvec::ArrayString;
 use cao_lang::{prelude::*, scalar::Scalar, traits::AutoByteEncodeProperties};
 use find_api::FindConstant;
@@ -16,7 +22,7 @@ use serde::{Deserialize, Serialize};
 use slog::trace;
 use std::convert::TryFrom;
 
-#[derive(Debug, Clone, Eq, PartialEq, Copy)]
+#[derive(Debug, Clone, Eq, PartialEq, Copy, Serialize, Deserialize)]
 #[repr(i32)]
 pub enum OperationResult {
     Ok = 0,
@@ -28,6 +34,12 @@ pub enum OperationResult {
     Empty = 6,
     Full = 7,
     PathNotFound = 8,
+    /// The target position is occupied by another entity
+    Occupied = 9,
+    /// Another intent took precedence over this one
+    Conflict = 12,
+    /// The bot has to recover from its fatigue before moving again
+    Fatigued = 13,
 }
 
 impl TryFrom<Scalar> for OperationResult {
@@ -44,6 +56,9 @@ impl TryFrom<Scalar> for OperationResult {
             Scalar::Integer(6) => OperationResult::Empty,
             Scalar::Integer(7) => OperationResult::Full,
             Scalar::Integer(8) => OperationResult::PathNotFound,
+            Scalar::Integer(9) => OperationResult::Occupied,
+            Scalar::Integer(12) => OperationResult::Conflict,
+            Scalar::Integer(13) => OperationResult::Fatigued,
             _ => {
                 return Err(i);
             }
@@ -121,6 +136,16 @@ pub fn log_scalar(vm: &mut VM<ScriptExecutionData>, value: Scalar) -> Result<(),
     Ok(())
 }
 
+/// Push the remaining CPU budget (cards) of the user onto the stack
+pub fn cpu_remaining(vm: &mut VM<ScriptExecutionData>, _: ()) -> Result<(), ExecutionError> {
+    profile!("cpu_remaining");
+    let budget = vm.get_aux().cpu_budget;
+    let used = cards_executed(vm);
+    let remaining = budget.saturating_sub(used).min(std::i32::MAX as u32);
+    vm.stack_push(remaining as i32)?;
+    Ok(())
+}
+
 /// Holds data about a function
 pub struct FunctionRow {
     pub desc: SubProgram<'static>,
@@ -256,6 +281,160 @@ pub fn make_import() -> Schema {
                     FunctionWrapper::new(world_position),
                 ),
             },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Hex Distance",
+                    "Returns the distance of two points on the hex grid",
+                    SubProgramType::Function,
+                    [Axial, Axial],
+                    [i32],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("geometry_api::hex_distance").unwrap(),
+                    FunctionWrapper::new(geometry_api::hex_distance),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Neighbour",
+                    "Returns the neighbour of the point at the given index [0..6)",
+                    SubProgramType::Function,
+                    [Axial, i32],
+                    [Axial],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("geometry_api::neighbour").unwrap(),
+                    FunctionWrapper::new(geometry_api::neighbour),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Neighbour Index",
+                    "Returns the index of the neighbour the unit vector points to",
+                    SubProgramType::Function,
+                    [Axial],
+                    [OperationResult, i32],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("geometry_api::neighbour_index").unwrap(),
+                    FunctionWrapper::new(geometry_api::neighbour_index),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Direction To",
+                    "Returns the neighbour index of the first point in the direction of the second",
+                    SubProgramType::Function,
+                    [Axial, Axial],
+                    [OperationResult, i32],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("geometry_api::direction_to").unwrap(),
+                    FunctionWrapper::new(geometry_api::direction_to),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Rotate Left",
+                    "Rotates the first point around the second by 60 degrees counter-clockwise",
+                    SubProgramType::Function,
+                    [Axial, Axial],
+                    [Axial],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("geometry_api::rotate_left").unwrap(),
+                    FunctionWrapper::new(geometry_api::rotate_left),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Rotate Right",
+                    "Rotates the first point around the second by 60 degrees clockwise",
+                    SubProgramType::Function,
+                    [Axial, Axial],
+                    [Axial],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("geometry_api::rotate_right").unwrap(),
+                    FunctionWrapper::new(geometry_api::rotate_right),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Add Points",
+                    "Returns the sum of two points",
+                    SubProgramType::Function,
+                    [Axial, Axial],
+                    [Axial],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("geometry_api::add_points").unwrap(),
+                    FunctionWrapper::new(geometry_api::add_points),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Subtract Points",
+                    "Returns the first point minus the second",
+                    SubProgramType::Function,
+                    [Axial, Axial],
+                    [Axial],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("geometry_api::sub_points").unwrap(),
+                    FunctionWrapper::new(geometry_api::sub_points),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Same Position",
+                    "Returns 1 if the two WorldPositions are equal, 0 otherwise",
+                    SubProgramType::Function,
+                    [WorldPosition, WorldPosition],
+                    [i32],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("geometry_api::same_position").unwrap(),
+                    FunctionWrapper::new(geometry_api::same_position),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Same Room",
+                    "Returns 1 if the two WorldPositions are in the same room, 0 otherwise",
+                    SubProgramType::Function,
+                    [WorldPosition, WorldPosition],
+                    [i32],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("geometry_api::same_room").unwrap(),
+                    FunctionWrapper::new(geometry_api::same_room),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Position Distance",
+                    "Returns the distance of two WorldPositions in the same room",
+                    SubProgramType::Function,
+                    [WorldPosition, WorldPosition],
+                    [OperationResult, i32],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("geometry_api::position_distance").unwrap(),
+                    FunctionWrapper::new(geometry_api::position_distance),
+                ),
+            },
             FunctionRow {
                 desc: subprogram_description!(
                     "Find Closest",
@@ -284,6 +463,48 @@ pub fn make_import() -> Schema {
                     FunctionWrapper::new(bots::unload),
                 ),
             },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Transfer",
+                    "Give resources to an adjacent bot",
+                    SubProgramType::Function,
+                    [u16, components::Resource, EntityId],
+                    [OperationResult],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("bots::transfer").unwrap(),
+                    FunctionWrapper::new(bots::transfer),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Drop",
+                    "Drop resources on the ground. The pile decays over time",
+                    SubProgramType::Function,
+                    [u16, components::Resource],
+                    [OperationResult],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("bots::drop_resources").unwrap(),
+                    FunctionWrapper::new(bots::drop_resources),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Pickup",
+                    "Pick up as much of the resource pile as the bot can carry",
+                    SubProgramType::Function,
+                    [EntityId],
+                    [OperationResult],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("bots::pickup").unwrap(),
+                    FunctionWrapper::new(bots::pickup),
+                ),
+            },
             FunctionRow {
                 desc: subprogram_description!(
                     "Parse Find Constant",
@@ -298,6 +519,20 @@ pub fn make_import() -> Schema {
                     FunctionWrapper::new(find_api::parse_find_constant),
                 ),
             },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Death Cause",
+                    "Returns why the entity of the given remains died",
+                    SubProgramType::Function,
+                    [EntityId],
+                    [OperationResult, components::DeathCause],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("find_api::death_cause").unwrap(),
+                    FunctionWrapper::new(find_api::death_cause),
+                ),
+            },
             FunctionRow {
                 desc: subprogram_description!(
                     "Melee attack",
@@ -312,6 +547,258 @@ pub fn make_import() -> Schema {
                     FunctionWrapper::new(bots::melee_attack),
                 ),
             },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Ranged attack",
+                    "Attempts to shoot the target entity. Damage falls off with distance",
+                    SubProgramType::Function,
+                    [EntityId],
+                    [OperationResult],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("bots::ranged_attack").unwrap(),
+                    FunctionWrapper::new(bots::ranged_attack),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Heal",
+                    "Restore the hp of the target bot",
+                    SubProgramType::Function,
+                    [EntityId],
+                    [OperationResult],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("bots::heal").unwrap(),
+                    FunctionWrapper::new(bots::heal),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Repair",
+                    "Spend the carried energy on restoring the hp of the target structure",
+                    SubProgramType::Function,
+                    [EntityId],
+                    [OperationResult],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("bots::repair").unwrap(),
+                    FunctionWrapper::new(bots::repair),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Last Outcome",
+                    "Returns the final outcome of the bot's intent of kind `IntentKind` in the last tick",
+                    SubProgramType::Function,
+                    [bots::IntentKind],
+                    [OperationResult],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("bots::last_outcome").unwrap(),
+                    FunctionWrapper::new(bots::last_outcome),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Build",
+                    "Spend the carried energy on the progress of the construction site",
+                    SubProgramType::Function,
+                    [EntityId],
+                    [OperationResult],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("bots::build").unwrap(),
+                    FunctionWrapper::new(bots::build),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Place Construction Site",
+                    "Place a construction site of the structure kind at the position, near the bot",
+                    SubProgramType::Function,
+                    [components::StructureKind, WorldPosition],
+                    [OperationResult],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("bots::place_construction_site").unwrap(),
+                    FunctionWrapper::new(bots::place_construction_site),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Spawn",
+                    "Queue a bot of the given template in the spawn",
+                    SubProgramType::Function,
+                    [EntityId, String],
+                    [OperationResult],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("spawn_api::spawn").unwrap(),
+                    FunctionWrapper::new(spawn_api::spawn),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Spawn In Group",
+                    "Queue a bot of the given template in the spawn. The bot joins the group when spawned",
+                    SubProgramType::Function,
+                    [EntityId, String, String],
+                    [OperationResult],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("spawn_api::spawn_in_group").unwrap(),
+                    FunctionWrapper::new(spawn_api::spawn_in_group),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Terrain At",
+                    "Returns the `TileTerrainType` at the given WorldPosition",
+                    SubProgramType::Function,
+                    [WorldPosition],
+                    [OperationResult, i32],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("map_api::terrain_at").unwrap(),
+                    FunctionWrapper::new(map_api::terrain_at),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Is Walkable",
+                    "Returns 1 if bots can walk on the terrain at the given WorldPosition, 0 otherwise",
+                    SubProgramType::Function,
+                    [WorldPosition],
+                    [OperationResult, i32],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("map_api::is_walkable").unwrap(),
+                    FunctionWrapper::new(map_api::is_walkable),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Is Occupied",
+                    "Returns 1 if an entity stands on the given WorldPosition, 0 otherwise",
+                    SubProgramType::Function,
+                    [WorldPosition],
+                    [OperationResult, i32],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("map_api::is_occupied").unwrap(),
+                    FunctionWrapper::new(map_api::is_occupied),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Room Owner",
+                    "Returns the owner of the given room",
+                    SubProgramType::Function,
+                    [Axial],
+                    [OperationResult, UserId],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("map_api::room_owner").unwrap(),
+                    FunctionWrapper::new(map_api::room_owner),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Room Connections",
+                    "Returns the connected neighbours of the given room as a bitmask of neighbour indices",
+                    SubProgramType::Function,
+                    [Axial],
+                    [OperationResult, i32],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("map_api::room_connections").unwrap(),
+                    FunctionWrapper::new(map_api::room_connections),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Room Radius",
+                    "Returns the radius of the rooms",
+                    SubProgramType::Function,
+                    [],
+                    [i32],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("map_api::room_radius").unwrap(),
+                    FunctionWrapper::new(map_api::room_radius),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Path Distance",
+                    "Returns the length of the path between two WorldPositions of the same room, using at most the given number of iterations",
+                    SubProgramType::Function,
+                    [WorldPosition, WorldPosition, i32],
+                    [OperationResult, i32],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("map_api::path_distance").unwrap(),
+                    FunctionWrapper::new(map_api::path_distance),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Random Int",
+                    "Returns a random integer in the range [low, high)",
+                    SubProgramType::Function,
+                    [i32, i32],
+                    [i32],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("random_api::random_int").unwrap(),
+                    FunctionWrapper::new(random_api::random_int),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "Random Choose",
+                    "Pops `n` values and returns one of them, chosen at random",
+                    SubProgramType::Function,
+                    [i32],
+                    [Scalar],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("random_api::random_choose").unwrap(),
+                    FunctionWrapper::new(random_api::random_choose),
+                ),
+            },
+            FunctionRow {
+                desc: subprogram_description!(
+                    "CPU Remaining",
+                    "Returns the remaining CPU budget of the user in this tick",
+                    SubProgramType::Function,
+                    [],
+                    [i32],
+                    []
+                ),
+                fo: Procedure::new(
+                    ArrayString::from("cpu_remaining").unwrap(),
+                    FunctionWrapper::new(cpu_remaining),
+                ),
+            },
         ],
     }
 }
@@ -1,11 +1,17 @@
 use super::*;
 use crate::{
-    components::{self, PathCacheComponent, Resource, TerrainComponent, PATH_CACHE_LEN},
+    components::{
+        self, IntentOutcomes, PathCacheComponent, Resource, StructureKind, TerrainComponent,
+        PATH_CACHE_LEN,
+    },
     indices::{EntityId, UserId, WorldPosition},
     intents::{
-        check_dropoff_intent, check_melee_intent, check_mine_intent, check_move_intent,
-        CachePathIntent, DropoffIntent, MeleeIntent, MineIntent, MoveIntent, MutPathCacheIntent,
-        PathCacheIntentAction,
+        check_build_intent, check_construction_site_intent, check_drop_intent,
+        check_dropoff_intent, check_heal_intent, check_melee_intent, check_mine_intent,
+        check_move_intent, check_pickup_intent, check_ranged_intent, check_repair_intent,
+        check_transfer_intent, BuildIntent, CachePathIntent, ConstructionSiteIntent, DropIntent,
+        DropoffIntent, HealIntent, MeleeIntent, MineIntent, MoveIntent, MutPathCacheIntent,
+        PathCacheIntentAction, PickupIntent, RangedIntent, RepairIntent, TransferIntent,
     },
     pathfinding, profile,
     storage::views::FromWorld,
@@ -14,6 +20,81 @@ use crate::{prelude::World, terrain::TileTerrainType};
 use slog::{debug, error, trace, warn};
 use std::convert::TryFrom;
 
+/// Kinds of intents whose outcome can be queried via `last_outcome`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(i32)]
+pub enum IntentKind {
+    Move = 1,
+    Mine = 2,
+    Dropoff = 3,
+    MeleeAttack = 4,
+    Build = 5,
+    RangedAttack = 6,
+    Heal = 7,
+    Repair = 8,
+    Transfer = 9,
+    Drop = 10,
+    Pickup = 11,
+}
+
+impl TryFrom<Scalar> for IntentKind {
+    type Error = Scalar;
+    fn try_from(i: Scalar) -> Result<Self, Scalar> {
+        let op = match i {
+            Scalar::Integer(1) => IntentKind::Move,
+            Scalar::Integer(2) => IntentKind::Mine,
+            Scalar::Integer(3) => IntentKind::Dropoff,
+            Scalar::Integer(4) => IntentKind::MeleeAttack,
+            Scalar::Integer(5) => IntentKind::Build,
+            Scalar::Integer(6) => IntentKind::RangedAttack,
+            Scalar::Integer(7) => IntentKind::Heal,
+            Scalar::Integer(8) => IntentKind::Repair,
+            Scalar::Integer(9) => IntentKind::Transfer,
+            Scalar::Integer(10) => IntentKind::Drop,
+            Scalar::Integer(11) => IntentKind::Pickup,
+            _ => return Err(i),
+        };
+        Ok(op)
+    }
+}
+
+impl AutoByteEncodeProperties for IntentKind {}
+
+/// Push the final outcome of the bot's last tick's intent of the given kind.
+/// Pushes `Empty` if the bot had no such intent.
+pub fn last_outcome(
+    vm: &mut VM<ScriptExecutionData>,
+    kind: IntentKind,
+) -> Result<(), ExecutionError> {
+    profile!("last_outcome");
+
+    let aux = vm.get_aux();
+    trace!(aux.logger, "last_outcome: {:?}", kind);
+
+    let outcome = aux
+        .storage()
+        .view::<EntityId, IntentOutcomes>()
+        .reborrow()
+        .get_by_id(&aux.entity_id)
+        .and_then(|outcomes| match kind {
+            IntentKind::Move => outcomes.move_intent,
+            IntentKind::Mine => outcomes.mine_intent,
+            IntentKind::Dropoff => outcomes.dropoff_intent,
+            IntentKind::MeleeAttack => outcomes.melee_intent,
+            IntentKind::Build => outcomes.build_intent,
+            IntentKind::RangedAttack => outcomes.ranged_intent,
+            IntentKind::Heal => outcomes.heal_intent,
+            IntentKind::Repair => outcomes.repair_intent,
+            IntentKind::Transfer => outcomes.transfer_intent,
+            IntentKind::Drop => outcomes.drop_intent,
+            IntentKind::Pickup => outcomes.pickup_intent,
+        })
+        .unwrap_or(OperationResult::Empty);
+
+    vm.stack_push(outcome)?;
+    Ok(())
+}
+
 pub fn melee_attack(
     vm: &mut VM<ScriptExecutionData>,
     target: Pointer,
@@ -47,6 +128,163 @@ pub fn melee_attack(
     Ok(())
 }
 
+pub fn ranged_attack(
+    vm: &mut VM<ScriptExecutionData>,
+    target: Pointer,
+) -> Result<(), ExecutionError> {
+    profile!("ranged-attack");
+
+    let aux = vm.get_aux();
+    let logger = &aux.logger;
+    trace!(logger, "ranged_attack");
+
+    let target: EntityId = vm.get_value(target).ok_or_else(|| {
+        warn!(logger, "ranged_attack called without a target");
+        ExecutionError::invalid_argument("ranged_attack called without a target".to_owned())
+    })?;
+
+    let storage = aux.storage();
+    let entity_id = aux.entity_id;
+    let user_id = aux.user_id.expect("user_id to be set");
+
+    let intent = RangedIntent {
+        attacker: entity_id,
+        defender: target,
+    };
+
+    let res = check_ranged_intent(logger, &intent, user_id, FromWorld::new(storage));
+
+    if let OperationResult::Ok = res {
+        vm.get_aux_mut().intents.ranged_attack_intent = Some(intent);
+    }
+    vm.stack_push(res)?;
+    Ok(())
+}
+
+/// Restore the hp of the target bot
+pub fn heal(vm: &mut VM<ScriptExecutionData>, target: Pointer) -> Result<(), ExecutionError> {
+    profile!("heal");
+
+    let aux = vm.get_aux();
+    let logger = &aux.logger;
+
+    let target: EntityId = vm.get_value(target).ok_or_else(|| {
+        warn!(logger, "heal called without a target");
+        ExecutionError::invalid_argument("heal called without a target".to_owned())
+    })?;
+    trace!(logger, "heal: target: {:?}, {}", target, aux);
+
+    let storage = aux.storage();
+    let user_id = aux.user_id.expect("user_id to be set");
+
+    let intent = HealIntent {
+        healer: aux.entity_id,
+        target,
+    };
+    let res = check_heal_intent(logger, &intent, user_id, FromWorld::new(storage));
+    if let OperationResult::Ok = res {
+        vm.get_aux_mut().intents.heal_intent = Some(intent);
+    }
+    vm.stack_push(res)?;
+    Ok(())
+}
+
+/// Spend the carried energy on restoring the hp of the target structure
+pub fn repair(vm: &mut VM<ScriptExecutionData>, target: Pointer) -> Result<(), ExecutionError> {
+    profile!("repair");
+
+    let aux = vm.get_aux();
+    let logger = &aux.logger;
+
+    let structure: EntityId = vm.get_value(target).ok_or_else(|| {
+        warn!(logger, "repair called without a structure");
+        ExecutionError::invalid_argument("repair called without a structure".to_owned())
+    })?;
+    trace!(logger, "repair: structure: {:?}, {}", structure, aux);
+
+    let storage = aux.storage();
+    let user_id = aux.user_id.expect("user_id to be set");
+
+    let intent = RepairIntent {
+        bot: aux.entity_id,
+        structure,
+    };
+    let res = check_repair_intent(logger, &intent, user_id, FromWorld::new(storage));
+    if let OperationResult::Ok = res {
+        vm.get_aux_mut().intents.repair_intent = Some(intent);
+    }
+    vm.stack_push(res)?;
+    Ok(())
+}
+
+/// Spend the carried energy on the progress of a construction site
+pub fn build(vm: &mut VM<ScriptExecutionData>, site: Pointer) -> Result<(), ExecutionError> {
+    profile!("build");
+
+    let aux = vm.get_aux();
+    let logger = &aux.logger;
+
+    let site: EntityId = vm.get_value(site).ok_or_else(|| {
+        warn!(logger, "build called without a construction site");
+        ExecutionError::invalid_argument("build called without a construction site".to_owned())
+    })?;
+    trace!(logger, "build: site: {:?}, {}", site, aux);
+
+    let storage = aux.storage();
+    let user_id = aux.user_id.expect("user_id to be set");
+
+    let intent = BuildIntent {
+        bot: aux.entity_id,
+        site,
+    };
+    let checkresult = check_build_intent(logger, &intent, user_id, FromWorld::new(storage));
+    if let OperationResult::Ok = checkresult {
+        vm.get_aux_mut().intents.build_intent = Some(intent);
+    }
+    vm.stack_push(checkresult)?;
+    Ok(())
+}
+
+/// Request a new construction site of the given structure kind, near the bot
+pub fn place_construction_site(
+    vm: &mut VM<ScriptExecutionData>,
+    (kind, position): (StructureKind, Pointer),
+) -> Result<(), ExecutionError> {
+    profile!("place_construction_site");
+
+    let aux = vm.get_aux();
+    let logger = &aux.logger;
+
+    let position: WorldPosition = vm.get_value(position).ok_or_else(|| {
+        warn!(logger, "place_construction_site called without a position");
+        ExecutionError::invalid_argument(
+            "place_construction_site called without a position".to_owned(),
+        )
+    })?;
+    trace!(
+        logger,
+        "place_construction_site: kind: {:?} position: {:?}",
+        kind,
+        position
+    );
+
+    let storage = aux.storage();
+    let owner_id = aux.user_id.expect("user_id to be set");
+
+    let intent = ConstructionSiteIntent {
+        bot: aux.entity_id,
+        owner_id,
+        kind,
+        position,
+    };
+    let checkresult = check_construction_site_intent(logger, &intent, FromWorld::new(storage));
+    if let OperationResult::Ok = checkresult {
+        vm.get_aux_mut().intents.construction_site_intent = Some(intent);
+    }
+    vm.stack_push(checkresult)?;
+    Ok(())
+}
+
 pub fn unload(
     vm: &mut VM<ScriptExecutionData>,
     (amount, ty, target): (i32, Resource, Pointer),
@@ -94,6 +332,105 @@ pub fn unload(
     Ok(())
 }
 
+/// Give resources to an adjacent bot
+pub fn transfer(
+    vm: &mut VM<ScriptExecutionData>,
+    (amount, ty, target): (i32, Resource, Pointer),
+) -> Result<(), ExecutionError> {
+    profile!("transfer");
+    let aux = vm.get_aux();
+    let logger = &aux.logger;
+
+    let amount = TryFrom::try_from(amount).map_err(|e| {
+        ExecutionError::invalid_argument(format!("transfer called with invalid amount: {}", e))
+    })?;
+    let target: EntityId = vm.get_value(target).ok_or_else(|| {
+        warn!(logger, "transfer called without a target");
+        ExecutionError::invalid_argument("transfer called without a target".to_owned())
+    })?;
+    trace!(
+        logger,
+        "transfer: amount: {} type: {:?} target: {:?}, {}",
+        amount,
+        ty,
+        target,
+        aux
+    );
+
+    let storage = aux.storage();
+    let user_id = aux.user_id.expect("user_id to be set");
+
+    let intent = TransferIntent {
+        bot: aux.entity_id,
+        target,
+        amount,
+        ty,
+    };
+    let checkresult = check_transfer_intent(logger, &intent, user_id, FromWorld::new(storage));
+    if let OperationResult::Ok = checkresult {
+        vm.get_aux_mut().intents.transfer_intent = Some(intent);
+    }
+    vm.stack_push(checkresult)?;
+    Ok(())
+}
+
+/// Drop resources on the ground, where other bots may pick them up
+pub fn drop_resources(
+    vm: &mut VM<ScriptExecutionData>,
+    (amount, ty): (i32, Resource),
+) -> Result<(), ExecutionError> {
+    profile!("drop_resources");
+    let aux = vm.get_aux();
+    let logger = &aux.logger;
+
+    let amount = TryFrom::try_from(amount).map_err(|e| {
+        ExecutionError::invalid_argument(format!("drop called with invalid amount: {}", e))
+    })?;
+    trace!(logger, "drop: amount: {} type: {:?}, {}", amount, ty, aux);
+
+    let storage = aux.storage();
+    let user_id = aux.user_id.expect("user_id to be set");
+
+    let intent = DropIntent {
+        bot: aux.entity_id,
+        amount,
+        ty,
+    };
+    let checkresult = check_drop_intent(logger, &intent, user_id, FromWorld::new(storage));
+    if let OperationResult::Ok = checkresult {
+        vm.get_aux_mut().intents.drop_intent = Some(intent);
+    }
+    vm.stack_push(checkresult)?;
+    Ok(())
+}
+
+/// Pick up as much of the resource pile as the bot can carry
+pub fn pickup(vm: &mut VM<ScriptExecutionData>, pile: Pointer) -> Result<(), ExecutionError> {
+    profile!("pickup");
+    let aux = vm.get_aux();
+    let logger = &aux.logger;
+
+    let pile: EntityId = vm.get_value(pile).ok_or_else(|| {
+        warn!(logger, "pickup called without a pile");
+        ExecutionError::invalid_argument("pickup called without a pile".to_owned())
+    })?;
+    trace!(logger, "pickup: pile: {:?}, {}", pile, aux);
+
+    let storage = aux.storage();
+    let user_id = aux.user_id.expect("user_id to be set");
+
+    let intent = PickupIntent {
+        bot: aux.entity_id,
+        pile,
+    };
+    let checkresult = check_pickup_intent(logger, &intent, user_id, FromWorld::new(storage));
+    if let OperationResult::Ok = checkresult {
+        vm.get_aux_mut().intents.pickup_intent = Some(intent);
+    }
+    vm.stack_push(checkresult)?;
+    Ok(())
+}
+
 pub fn mine_resource(
     vm: &mut VM<ScriptExecutionData>,
     target: Pointer,
@@ -226,13 +563,13 @@ pub fn move_bot_to_position(
     Ok(())
 }
 
-type MoveToPosIntent = (
+pub(crate) type MoveToPosIntent = (
     MoveIntent,
     Option<MutPathCacheIntent>,
     Option<CachePathIntent>,
 );
 
-fn move_to_pos(
+pub(crate) fn move_to_pos(
     logger: &slog::Logger,
     bot: EntityId,
     to: WorldPosition,
@@ -250,6 +587,18 @@ fn move_to_pos(
             OperationResult::InvalidInput
         })?;
 
+    // don't waste cpu on pathfinding if the bot can not move anyway
+    if storage
+        .view::<EntityId, components::FatigueComponent>()
+        .reborrow()
+        .get_by_id(&bot)
+        .map(|f| f.fatigue > 0)
+        .unwrap_or(false)
+    {
+        trace!(logger, "Bot {:?} is fatigued", bot);
+        return Err(OperationResult::Fatigued);
+    }
+
     // attempt to use the cached path
     // which requires non-empty cache with a valid next step
     match storage
@@ -1,7 +1,9 @@
 use super::*;
 use crate::components::{EntityComponent, PositionComponent};
-use crate::indices::WorldPosition;
+use crate::indices::{Room, WorldPosition};
 use crate::profile;
+use crate::storage::views::FromWorld;
+use crate::visibility::compute_room_visibility;
 use crate::world::World;
 use cao_lang::prelude::*;
 use slog::{trace, warn};
@@ -13,6 +15,8 @@ pub enum FindConstant {
     Resource = 1,
     Spawn = 2,
     EnemyBot = 3,
+    ResourcePile = 4,
+    Remains = 5,
 }
 
 impl TryFrom<Scalar> for FindConstant {
@@ -22,6 +26,8 @@ impl TryFrom<Scalar> for FindConstant {
             Scalar::Integer(1) => FindConstant::Resource,
             Scalar::Integer(2) => FindConstant::Spawn,
             Scalar::Integer(3) => FindConstant::EnemyBot,
+            Scalar::Integer(4) => FindConstant::ResourcePile,
+            Scalar::Integer(5) => FindConstant::Remains,
             _ => return Err(i),
         };
         Ok(op)
@@ -45,6 +51,8 @@ pub fn parse_find_constant(
         "Resource" => FindConstant::Resource,
         "Spawn" => FindConstant::Spawn,
         "EnemyBot" => FindConstant::EnemyBot,
+        "ResourcePile" => FindConstant::ResourcePile,
+        "Remains" => FindConstant::Remains,
         _ => {
             trace!(
                 logger,
@@ -93,6 +101,37 @@ pub fn find_closest_by_range(
     param.execute(vm, position)
 }
 
+/// Push the `DeathCause` recorded by the remains and `Ok`, or `InvalidTarget` if the entity is not
+/// a remains
+pub fn death_cause(
+    vm: &mut VM<ScriptExecutionData>,
+    remains: Pointer,
+) -> Result<(), ExecutionError> {
+    profile!("death_cause");
+    let aux = vm.get_aux();
+    let logger = &aux.logger;
+
+    let remains: EntityId = vm.get_value(remains).ok_or_else(|| {
+        warn!(logger, "death_cause called without an entity");
+        ExecutionError::invalid_argument("death_cause called without an entity".to_owned())
+    })?;
+    trace!(logger, "death_cause {:?}", remains);
+
+    let cause = aux
+        .storage()
+        .view::<EntityId, components::RemainsComponent>()
+        .get_by_id(&remains)
+        .map(|remains| remains.cause);
+    match cause {
+        Some(cause) => {
+            vm.stack_push(cause as i32)?;
+            vm.stack_push(OperationResult::Ok)?;
+        }
+        None => vm.stack_push(OperationResult::InvalidTarget)?,
+    }
+    Ok(())
+}
+
 impl FindConstant {
     pub fn execute(
         self,
@@ -104,27 +143,59 @@ impl FindConstant {
 
         let storage = vm.get_aux().storage();
         let user_id = vm.get_aux().user_id;
+        // users only find what they can see
+        let visibility = user_id.map(|user_id| {
+            compute_room_visibility(user_id, Room(position.room), FromWorld::new(storage))
+        });
+        let visible = |pos: WorldPosition| {
+            visibility
+                .as_ref()
+                .map(|visibility| visibility.is_visible(pos))
+                .unwrap_or(true)
+        };
         let candidate = match self {
             FindConstant::Resource => {
                 let resources = storage.view::<EntityId, components::ResourceComponent>();
-                find_closest_entity_impl(logger, storage, position, |id| resources.contains(&id))
+                find_closest_entity_impl(logger, storage, position, |pos, id| {
+                    visible(pos) && resources.contains(&id)
+                })
             }
             FindConstant::Spawn => {
                 let owner = storage.view::<EntityId, components::OwnedEntity>();
                 let spawns = storage.view::<EntityId, components::SpawnComponent>();
-                find_closest_entity_impl(logger, storage, position, |id| {
-                    spawns.contains(&id)
+                find_closest_entity_impl(logger, storage, position, |pos, id| {
+                    visible(pos)
+                        && spawns.contains(&id)
                         && owner.get_by_id(&id).map(|owner_id| owner_id.owner_id) == user_id
                 })
             }
             FindConstant::EnemyBot => {
                 let owner = storage.view::<EntityId, components::OwnedEntity>();
                 let bots = storage.view::<EntityId, components::Bot>();
-                find_closest_entity_impl(logger, storage, position, |id| {
-                    bots.contains_id(&id)
+                find_closest_entity_impl(logger, storage, position, |pos, id| {
+                    visible(pos)
+                        && bots.contains_id(&id)
                         && owner.get_by_id(&id).map(|owner_id| owner_id.owner_id) != user_id
                 })
             }
+            FindConstant::ResourcePile => {
+                let piles = storage.view::<EntityId, components::ResourcePileComponent>();
+                Ok(find_closest_unindexed(
+                    storage,
+                    position,
+                    piles.iter().map(|(id, _)| id),
+                    visible,
+                ))
+            }
+            FindConstant::Remains => {
+                let remains = storage.view::<EntityId, components::RemainsComponent>();
+                Ok(find_closest_unindexed(
+                    storage,
+                    position,
+                    remains.iter().map(|(id, _)| id),
+                    visible,
+                ))
+            }
         }?;
         match candidate {
             Some(entity) => {
@@ -141,6 +212,22 @@ impl FindConstant {
     }
 }
 
+/// Piles and remains are not registered in the entity position table, so they are searched one
+/// by one
+fn find_closest_unindexed(
+    storage: &World,
+    position: WorldPosition,
+    candidates: impl Iterator<Item = EntityId>,
+    visible: impl Fn(WorldPosition) -> bool,
+) -> Option<EntityId> {
+    let positions = storage.view::<EntityId, PositionComponent>();
+    candidates
+        .filter_map(|id| positions.get_by_id(&id).map(|pos| (id, pos.0)))
+        .filter(|(_, pos)| pos.room == position.room && visible(*pos))
+        .min_by_key(|(id, pos)| (pos.pos.hex_distance(position.pos), *id))
+        .map(|(id, _)| id)
+}
+
 fn find_closest_entity_impl<F>(
     logger: &slog::Logger,
     storage: &World,
@@ -148,7 +235,7 @@ fn find_closest_entity_impl<F>(
     filter: F,
 ) -> Result<Option<EntityId>, ExecutionError>
 where
-    F: Fn(EntityId) -> bool,
+    F: Fn(WorldPosition, EntityId) -> bool,
 {
     let WorldPosition { room, pos } = position;
     let entities_by_pos = storage.view::<WorldPosition, EntityComponent>();
@@ -162,7 +249,15 @@ where
     })?;
 
     // search the whole room
-    let candidate = room.find_closest_by_filter(&pos, |_, entity| filter(entity.0));
+    let candidate = room.find_closest_by_filter(&pos, |entity_pos, entity| {
+        filter(
+            WorldPosition {
+                room: position.room,
+                pos: *entity_pos,
+            },
+            entity.0,
+        )
+    });
     let candidate = candidate.map(|(_, _, id)| id.0);
     Ok(candidate)
 }
@@ -0,0 +1,214 @@
+//! Hex geometry helpers, so scripts do not have to reimplement them
+//!
+use super::*;
+use slog::warn;
+
+fn get_point(
+    vm: &VM<ScriptExecutionData>,
+    ptr: Pointer,
+    name: &str,
+) -> Result<Axial, ExecutionError> {
+    vm.get_value(ptr).ok_or_else(|| {
+        warn!(vm.get_aux().logger, "{} called with an invalid point", name);
+        ExecutionError::invalid_argument(format!("{} called with an invalid point", name))
+    })
+}
+
+fn get_position(
+    vm: &VM<ScriptExecutionData>,
+    ptr: Pointer,
+    name: &str,
+) -> Result<WorldPosition, ExecutionError> {
+    vm.get_value(ptr).ok_or_else(|| {
+        warn!(
+            vm.get_aux().logger,
+            "{} called with an invalid position", name
+        );
+        ExecutionError::invalid_argument(format!("{} called with an invalid position", name))
+    })
+}
+
+/// Index of the neighbour of `from` that is closest to `to`. None if the points are equal.
+pub fn direction_index(from: Axial, to: Axial) -> Option<usize> {
+    if from == to {
+        return None;
+    }
+    from.hex_neighbours()
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, n)| n.hex_distance(to))
+        .map(|(i, _)| i)
+}
+
+pub fn hex_distance(
+    vm: &mut VM<ScriptExecutionData>,
+    (a, b): (Pointer, Pointer),
+) -> Result<(), ExecutionError> {
+    profile!("hex_distance");
+    let a = get_point(vm, a, "hex_distance")?;
+    let b = get_point(vm, b, "hex_distance")?;
+    vm.stack_push(a.hex_distance(b) as i32)?;
+    Ok(())
+}
+
+/// Push the neighbour of the point at the given index. See `Axial::hex_neighbours`.
+pub fn neighbour(
+    vm: &mut VM<ScriptExecutionData>,
+    (point, index): (Pointer, i32),
+) -> Result<(), ExecutionError> {
+    profile!("neighbour");
+    let point = get_point(vm, point, "neighbour")?;
+    if !(0..6).contains(&index) {
+        return Err(ExecutionError::invalid_argument(format!(
+            "neighbour called with invalid index {}",
+            index
+        )));
+    }
+    vm.set_value(point.hex_neighbours()[index as usize])?;
+    Ok(())
+}
+
+/// Push the index of the unit vector in `hex_neighbours` and `Ok`, or `InvalidInput` if the
+/// vector is not a neighbour of the origin
+pub fn neighbour_index(
+    vm: &mut VM<ScriptExecutionData>,
+    vector: Pointer,
+) -> Result<(), ExecutionError> {
+    profile!("neighbour_index");
+    let vector = get_point(vm, vector, "neighbour_index")?;
+    match Axial::neighbour_index(vector) {
+        Some(i) => {
+            vm.stack_push(i as i32)?;
+            vm.stack_push(OperationResult::Ok)?;
+        }
+        None => vm.stack_push(OperationResult::InvalidInput)?,
+    }
+    Ok(())
+}
+
+/// Push the index of the neighbour of `from` in the direction of `to` and `Ok`, or `InvalidInput`
+/// if the points are equal
+pub fn direction_to(
+    vm: &mut VM<ScriptExecutionData>,
+    (from, to): (Pointer, Pointer),
+) -> Result<(), ExecutionError> {
+    profile!("direction_to");
+    let from = get_point(vm, from, "direction_to")?;
+    let to = get_point(vm, to, "direction_to")?;
+    match direction_index(from, to) {
+        Some(i) => {
+            vm.stack_push(i as i32)?;
+            vm.stack_push(OperationResult::Ok)?;
+        }
+        None => vm.stack_push(OperationResult::InvalidInput)?,
+    }
+    Ok(())
+}
+
+/// Rotate the point 60 degrees to the left around the center
+pub fn rotate_left(
+    vm: &mut VM<ScriptExecutionData>,
+    (point, center): (Pointer, Pointer),
+) -> Result<(), ExecutionError> {
+    profile!("rotate_left");
+    let point = get_point(vm, point, "rotate_left")?;
+    let center = get_point(vm, center, "rotate_left")?;
+    vm.set_value(point.rotate_left_around(center))?;
+    Ok(())
+}
+
+/// Rotate the point 60 degrees to the right around the center
+pub fn rotate_right(
+    vm: &mut VM<ScriptExecutionData>,
+    (point, center): (Pointer, Pointer),
+) -> Result<(), ExecutionError> {
+    profile!("rotate_right");
+    let point = get_point(vm, point, "rotate_right")?;
+    let center = get_point(vm, center, "rotate_right")?;
+    vm.set_value(point.rotate_right_around(center))?;
+    Ok(())
+}
+
+pub fn add_points(
+    vm: &mut VM<ScriptExecutionData>,
+    (a, b): (Pointer, Pointer),
+) -> Result<(), ExecutionError> {
+    profile!("add_points");
+    let a = get_point(vm, a, "add_points")?;
+    let b = get_point(vm, b, "add_points")?;
+    vm.set_value(a + b)?;
+    Ok(())
+}
+
+pub fn sub_points(
+    vm: &mut VM<ScriptExecutionData>,
+    (a, b): (Pointer, Pointer),
+) -> Result<(), ExecutionError> {
+    profile!("sub_points");
+    let a = get_point(vm, a, "sub_points")?;
+    let b = get_point(vm, b, "sub_points")?;
+    vm.set_value(a - b)?;
+    Ok(())
+}
+
+/// Push 1 if the two WorldPositions are equal, 0 otherwise
+pub fn same_position(
+    vm: &mut VM<ScriptExecutionData>,
+    (a, b): (Pointer, Pointer),
+) -> Result<(), ExecutionError> {
+    profile!("same_position");
+    let a = get_position(vm, a, "same_position")?;
+    let b = get_position(vm, b, "same_position")?;
+    vm.stack_push((a == b) as i32)?;
+    Ok(())
+}
+
+/// Push 1 if the two WorldPositions are in the same room, 0 otherwise
+pub fn same_room(
+    vm: &mut VM<ScriptExecutionData>,
+    (a, b): (Pointer, Pointer),
+) -> Result<(), ExecutionError> {
+    profile!("same_room");
+    let a = get_position(vm, a, "same_room")?;
+    let b = get_position(vm, b, "same_room")?;
+    vm.stack_push((a.room == b.room) as i32)?;
+    Ok(())
+}
+
+/// Push the distance of two WorldPositions in the same room and `Ok`, or `InvalidInput` if they
+/// are in different rooms
+pub fn position_distance(
+    vm: &mut VM<ScriptExecutionData>,
+    (a, b): (Pointer, Pointer),
+) -> Result<(), ExecutionError> {
+    profile!("position_distance");
+    let a = get_position(vm, a, "position_distance")?;
+    let b = get_position(vm, b, "position_distance")?;
+    if a.room != b.room {
+        vm.stack_push(OperationResult::InvalidInput)?;
+        return Ok(());
+    }
+    vm.stack_push(a.pos.hex_distance(b.pos) as i32)?;
+    vm.stack_push(OperationResult::Ok)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn direction_index_points_towards_the_target() {
+        let from = Axial::new(3, 3);
+
+        assert_eq!(direction_index(from, from), None);
+        for (i, n) in from.hex_neighbours().iter().enumerate() {
+            assert_eq!(direction_index(from, *n), Some(i));
+        }
+
+        let to = Axial::new(3, -5);
+        let i = direction_index(from, to).expect("direction");
+        let next = from.hex_neighbours()[i];
+        assert_eq!(next.hex_distance(to) + 1, from.hex_distance(to));
+    }
+}
@@ -0,0 +1,428 @@
+//! Map queries: terrain, occupancy, rooms and path distances
+//!
+//! Like finds, queries of users are limited by their fog of war, see `crate::visibility`.
+//! Terrain and rooms may be queried in explored rooms, occupancy only at visible positions.
+//!
+use super::*;
+use crate::components::{
+    EntityComponent, ExploredRooms, OwnedEntity, RoomConnections, RoomProperties, TerrainComponent,
+};
+use crate::indices::{ConfigKey, Room};
+use crate::pathfinding::{self, PathFindingError};
+use crate::storage::views::FromWorld;
+use crate::terrain;
+use crate::visibility::{compute_room_visibility, is_visible};
+use slog::{trace, warn};
+
+/// Upper bound of the iterations a single `path_distance` query may use, regardless of the
+/// requested amount
+pub const MAX_PATH_DISTANCE_ITER: u32 = 2000;
+
+fn get_position(
+    vm: &VM<ScriptExecutionData>,
+    pos: Pointer,
+    name: &str,
+) -> Result<WorldPosition, ExecutionError> {
+    vm.get_value(pos).ok_or_else(|| {
+        warn!(vm.get_aux().logger, "{} called without a position", name);
+        ExecutionError::invalid_argument(format!("{} called without a position", name))
+    })
+}
+
+fn get_room(
+    vm: &VM<ScriptExecutionData>,
+    room: Pointer,
+    name: &str,
+) -> Result<Room, ExecutionError> {
+    vm.get_value(room).map(Room).ok_or_else(|| {
+        warn!(vm.get_aux().logger, "{} called without a room", name);
+        ExecutionError::invalid_argument(format!("{} called without a room", name))
+    })
+}
+
+/// Has the user running the script explored the room, or sees it in this tick.
+/// Scripts not run on behalf of a user may query any room.
+fn can_see_room(aux: &ScriptExecutionData, room: Room) -> bool {
+    let user_id = match aux.user_id {
+        Some(user_id) => user_id,
+        None => return true,
+    };
+    let storage = aux.storage();
+    let explored = storage
+        .view::<UserId, ExploredRooms>()
+        .get_by_id(&user_id)
+        .map(|ExploredRooms(rooms)| rooms.contains(&room))
+        .unwrap_or(false);
+    explored
+        || compute_room_visibility(user_id, room, FromWorld::new(storage))
+            .seen_rooms()
+            .next()
+            .is_some()
+}
+
+/// Does the user running the script see the position.
+/// Scripts not run on behalf of a user see every position.
+fn can_see_position(aux: &ScriptExecutionData, pos: WorldPosition) -> bool {
+    aux.user_id
+        .map(|user_id| is_visible(user_id, pos, FromWorld::new(aux.storage())))
+        .unwrap_or(true)
+}
+
+/// Push `InvalidTarget` and return false if the user running the script has not explored the room
+fn check_room(
+    vm: &mut VM<ScriptExecutionData>,
+    room: Room,
+    name: &str,
+) -> Result<bool, ExecutionError> {
+    if can_see_room(vm.get_aux(), room) {
+        return Ok(true);
+    }
+    trace!(
+        vm.get_aux().logger,
+        "{} called on unexplored room {:?}",
+        name,
+        room
+    );
+    vm.stack_push(OperationResult::InvalidTarget)?;
+    Ok(false)
+}
+
+/// Push the `TileTerrainType` at the position and `Ok`, or `InvalidInput` if the position is not
+/// on the map. Pushes `InvalidTarget` if the room is not explored.
+pub fn terrain_at(vm: &mut VM<ScriptExecutionData>, pos: Pointer) -> Result<(), ExecutionError> {
+    profile!("terrain_at");
+    let pos = get_position(vm, pos, "terrain_at")?;
+    trace!(vm.get_aux().logger, "terrain_at {:?}", pos);
+    if !check_room(vm, Room(pos.room), "terrain_at")? {
+        return Ok(());
+    }
+
+    let tile = vm
+        .get_aux()
+        .storage()
+        .view::<WorldPosition, TerrainComponent>()
+        .get_by_id(&pos)
+        .map(|TerrainComponent(tile)| *tile);
+    match tile {
+        Some(tile) => {
+            vm.stack_push(tile as i32)?;
+            vm.stack_push(OperationResult::Ok)?;
+        }
+        None => vm.stack_push(OperationResult::InvalidInput)?,
+    }
+    Ok(())
+}
+
+/// Push 1 if bots can walk on the terrain of the position, 0 otherwise, then `Ok`.
+/// Pushes `InvalidInput` if the position is not on the map, `InvalidTarget` if the room is not
+/// explored.
+///
+/// Does not consider entities occupying the tile, see `is_occupied`.
+pub fn is_walkable(vm: &mut VM<ScriptExecutionData>, pos: Pointer) -> Result<(), ExecutionError> {
+    profile!("is_walkable");
+    let pos = get_position(vm, pos, "is_walkable")?;
+    trace!(vm.get_aux().logger, "is_walkable {:?}", pos);
+    if !check_room(vm, Room(pos.room), "is_walkable")? {
+        return Ok(());
+    }
+
+    let walkable = vm
+        .get_aux()
+        .storage()
+        .view::<WorldPosition, TerrainComponent>()
+        .get_by_id(&pos)
+        .map(|TerrainComponent(tile)| terrain::is_walkable(*tile));
+    match walkable {
+        Some(walkable) => {
+            vm.stack_push(walkable as i32)?;
+            vm.stack_push(OperationResult::Ok)?;
+        }
+        None => vm.stack_push(OperationResult::InvalidInput)?,
+    }
+    Ok(())
+}
+
+/// Push 1 if an entity stands on the position, 0 otherwise, then `Ok`.
+/// Pushes `InvalidInput` if the position is not on the map, `InvalidTarget` if the position is not
+/// visible.
+pub fn is_occupied(vm: &mut VM<ScriptExecutionData>, pos: Pointer) -> Result<(), ExecutionError> {
+    profile!("is_occupied");
+    let pos = get_position(vm, pos, "is_occupied")?;
+    trace!(vm.get_aux().logger, "is_occupied {:?}", pos);
+    if !can_see_position(vm.get_aux(), pos) {
+        trace!(vm.get_aux().logger, "is_occupied called on hidden position");
+        vm.stack_push(OperationResult::InvalidTarget)?;
+        return Ok(());
+    }
+
+    let storage = vm.get_aux().storage();
+    if storage
+        .view::<WorldPosition, TerrainComponent>()
+        .get_by_id(&pos)
+        .is_none()
+    {
+        vm.stack_push(OperationResult::InvalidInput)?;
+        return Ok(());
+    }
+    let occupied = storage
+        .view::<WorldPosition, EntityComponent>()
+        .get_by_id(&pos)
+        .is_some();
+    vm.stack_push(occupied as i32)?;
+    vm.stack_push(OperationResult::Ok)?;
+    Ok(())
+}
+
+/// Push the owner of the room and `Ok`, or `Empty` if the room has no owner.
+/// Pushes `InvalidTarget` if the room is not explored.
+pub fn room_owner(vm: &mut VM<ScriptExecutionData>, room: Pointer) -> Result<(), ExecutionError> {
+    profile!("room_owner");
+    let room = get_room(vm, room, "room_owner")?;
+    trace!(vm.get_aux().logger, "room_owner {:?}", room);
+    if !check_room(vm, room, "room_owner")? {
+        return Ok(());
+    }
+
+    let owner = vm
+        .get_aux()
+        .storage()
+        .view::<Room, OwnedEntity>()
+        .get_by_id(&room)
+        .map(|OwnedEntity { owner_id }| *owner_id);
+    match owner {
+        Some(owner_id) => {
+            vm.set_value(owner_id)?;
+            vm.stack_push(OperationResult::Ok)?;
+        }
+        None => vm.stack_push(OperationResult::Empty)?,
+    }
+    Ok(())
+}
+
+/// Push the connections of the room as a bitmask and `Ok`, or `InvalidInput` if the room does not
+/// exist. Pushes `InvalidTarget` if the room is not explored.
+///
+/// Bit `i` is set if the room is connected to its neighbour in the direction of `Axial::neighbour`
+/// index `i`.
+pub fn room_connections(
+    vm: &mut VM<ScriptExecutionData>,
+    room: Pointer,
+) -> Result<(), ExecutionError> {
+    profile!("room_connections");
+    let room = get_room(vm, room, "room_connections")?;
+    trace!(vm.get_aux().logger, "room_connections {:?}", room);
+    if !check_room(vm, room, "room_connections")? {
+        return Ok(());
+    }
+
+    let mask = vm
+        .get_aux()
+        .storage()
+        .view::<Room, RoomConnections>()
+        .get_by_id(&room)
+        .map(|RoomConnections(connections)| {
+            connections
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| c.is_some())
+                .fold(0, |mask, (i, _)| mask | (1 << i))
+        });
+    match mask {
+        Some(mask) => {
+            vm.stack_push(mask)?;
+            vm.stack_push(OperationResult::Ok)?;
+        }
+        None => vm.stack_push(OperationResult::InvalidInput)?,
+    }
+    Ok(())
+}
+
+/// Push the radius of the rooms
+pub fn room_radius(vm: &mut VM<ScriptExecutionData>, _: ()) -> Result<(), ExecutionError> {
+    profile!("room_radius");
+    let radius = vm
+        .get_aux()
+        .storage()
+        .view::<ConfigKey, RoomProperties>()
+        .value
+        .as_ref()
+        .map(|props| props.radius)
+        .unwrap_or(0);
+    vm.stack_push(radius as i32)?;
+    Ok(())
+}
+
+/// Push the length of the shortest path between two positions of the same room and `Ok`.
+///
+/// Uses at most `max_iter` pathfinding iterations, capped by `MAX_PATH_DISTANCE_ITER`.
+/// Pushes `PathNotFound` if no path was found within the limit, `InvalidInput` if the positions
+/// are in different or non-existent rooms, `InvalidTarget` if the room is not explored.
+pub fn path_distance(
+    vm: &mut VM<ScriptExecutionData>,
+    (from, to, max_iter): (Pointer, Pointer, i32),
+) -> Result<(), ExecutionError> {
+    profile!("path_distance");
+    let from = get_position(vm, from, "path_distance")?;
+    let to = get_position(vm, to, "path_distance")?;
+    let max_iter = (max_iter.max(0) as u32).min(MAX_PATH_DISTANCE_ITER);
+    if !check_room(vm, Room(from.room), "path_distance")? {
+        return Ok(());
+    }
+
+    let aux = vm.get_aux();
+    let logger = &aux.logger;
+    trace!(
+        logger,
+        "path_distance from {:?} to {:?} max_iter: {}",
+        from,
+        to,
+        max_iter
+    );
+
+    if from.room != to.room {
+        trace!(
+            logger,
+            "path_distance called with positions in different rooms"
+        );
+        vm.stack_push(OperationResult::InvalidInput)?;
+        return Ok(());
+    }
+
+    let mut path = Vec::with_capacity(max_iter as usize);
+    let res = pathfinding::find_path(
+        logger,
+        from,
+        to,
+        FromWorld::new(aux.storage()),
+        max_iter,
+        &mut path,
+        &mut Vec::new(),
+    );
+    match res {
+        Ok(_) => {
+            vm.stack_push(path.len() as i32)?;
+            vm.stack_push(OperationResult::Ok)?;
+        }
+        Err(PathFindingError::NotFound { .. }) | Err(PathFindingError::Unreachable) => {
+            vm.stack_push(OperationResult::PathNotFound)?
+        }
+        Err(err) => {
+            trace!(logger, "path_distance failed {:?}", err);
+            vm.stack_push(OperationResult::InvalidInput)?
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::{build_world, Fixture};
+    use crate::utils::{setup_testing, test_logger};
+
+    fn fixture() -> Fixture {
+        serde_json::from_str(
+            r#"{
+                "roomRadius": 3,
+                "rooms": [
+                    {
+                        "room": { "q": 0, "r": 0 },
+                        "terrain": [
+                            [ { "q": 3, "r": 2 }, "wall" ],
+                            [ { "q": 4, "r": 2 }, "wall" ],
+                            [ { "q": 2, "r": 3 }, "wall" ]
+                        ]
+                    }
+                ]
+            }"#,
+        )
+        .expect("Failed to parse fixture")
+    }
+
+    fn push_pos(vm: &mut VM<ScriptExecutionData>, q: i32, r: i32) -> Pointer {
+        let pos = WorldPosition {
+            room: Axial::new(0, 0),
+            pos: Axial::new(q, r),
+        };
+        vm.set_value(pos).expect("Failed to set position");
+        match vm.stack_pop() {
+            Scalar::Pointer(p) => p,
+            res => panic!("Expected pointer, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn path_distance_walks_around_walls() {
+        setup_testing();
+        let world = build_world(test_logger(), &fixture()).expect("Failed to build world");
+
+        let data = ScriptExecutionData::new(
+            test_logger(),
+            &*world.as_ref(),
+            Default::default(),
+            EntityId(0),
+            None,
+        );
+        let mut vm = VM::new(test_logger(), data);
+
+        let from = push_pos(&mut vm, 3, 3);
+        let to = push_pos(&mut vm, 3, 1);
+
+        path_distance(&mut vm, (from, to, 100)).expect("path_distance failed");
+
+        assert_eq!(
+            OperationResult::try_from(vm.stack_pop()),
+            Ok(OperationResult::Ok)
+        );
+        let distance = vm.stack_pop();
+        // the direct route is blocked, so the path is longer than the hex distance
+        assert!(
+            matches!(distance, Scalar::Integer(d) if d > 2),
+            "{:?}",
+            distance
+        );
+    }
+
+    #[test]
+    fn is_occupied_is_limited_to_visible_positions() {
+        setup_testing();
+        let mut fixture = fixture();
+        fixture.entities = serde_json::from_str(
+            r#"[
+                {
+                    "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 3, "r": 3 } },
+                    "owner": "00000000-0000-0000-0000-000000000000",
+                    "bot": true
+                }
+            ]"#,
+        )
+        .expect("Failed to parse entities");
+        let world = build_world(test_logger(), &fixture).expect("Failed to build world");
+
+        let is_occupied_as = |user_id| {
+            let data = ScriptExecutionData::new(
+                test_logger(),
+                &*world.as_ref(),
+                Default::default(),
+                EntityId(0),
+                user_id,
+            );
+            let mut vm = VM::new(test_logger(), data);
+            let pos = push_pos(&mut vm, 3, 3);
+            is_occupied(&mut vm, pos).expect("is_occupied failed");
+            OperationResult::try_from(vm.stack_pop())
+        };
+
+        assert_eq!(
+            is_occupied_as(Some(UserId::default())),
+            Ok(OperationResult::Ok)
+        );
+        assert_eq!(is_occupied_as(None), Ok(OperationResult::Ok));
+        // the room is not seen by the other user
+        let other = UserId(uuid::Uuid::from_u128(1));
+        assert_eq!(
+            is_occupied_as(Some(other)),
+            Ok(OperationResult::InvalidTarget)
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! Deterministic random numbers for scripts.
+//!
+//! Every script run gets its own generator, seeded by the world seed, the tick and the entity.
+//! See `ScriptExecutionData::rng`.
+//!
+use super::*;
+use rand::Rng;
+
+/// Push a random integer in the range `[low, high)`
+pub fn random_int(
+    vm: &mut VM<ScriptExecutionData>,
+    (low, high): (i32, i32),
+) -> Result<(), ExecutionError> {
+    profile!("random_int");
+    trace!(vm.get_aux().logger, "random_int {} {}", low, high);
+    if low >= high {
+        return Err(ExecutionError::invalid_argument(format!(
+            "random_int called with an empty range [{}, {})",
+            low, high
+        )));
+    }
+    let value = vm.get_aux_mut().rng.gen_range(low, high);
+    vm.stack_push(value)?;
+    Ok(())
+}
+
+/// Pop `n` values off the stack and push one of them back, chosen at random
+pub fn random_choose(vm: &mut VM<ScriptExecutionData>, n: i32) -> Result<(), ExecutionError> {
+    profile!("random_choose");
+    trace!(vm.get_aux().logger, "random_choose {}", n);
+    if n <= 0 {
+        return Err(ExecutionError::invalid_argument(format!(
+            "random_choose called with invalid number of values {}",
+            n
+        )));
+    }
+    let choice = vm.get_aux_mut().rng.gen_range(0, n);
+    let mut chosen = None;
+    for i in 0..n {
+        let value = vm.stack_pop();
+        if i == choice {
+            chosen = Some(value);
+        }
+    }
+    if let Some(value) = chosen {
+        vm.stack_push(value)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::init_inmemory_storage;
+
+    fn roll(storage: &crate::prelude::World, entity_id: EntityId) -> Vec<i32> {
+        let logger = crate::utils::test_logger();
+        let data =
+            ScriptExecutionData::new(logger.clone(), storage, Default::default(), entity_id, None);
+        let mut vm = VM::new(logger, data);
+        (0..8)
+            .map(|_| {
+                random_int(&mut vm, (0, 1000)).expect("random_int");
+                match vm.stack_pop() {
+                    Scalar::Integer(i) => i,
+                    res => panic!("Expected an integer, got {:?}", res),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn random_is_reproducible_per_entity_and_tick() {
+        crate::utils::setup_testing();
+        let mut storage = init_inmemory_storage(crate::utils::test_logger());
+        storage
+            .config
+            .game_config
+            .value
+            .as_mut()
+            .unwrap()
+            .world_seed = 42;
+
+        let a = roll(&*storage, EntityId(1));
+        assert_eq!(a, roll(&*storage, EntityId(1)));
+        assert_ne!(a, roll(&*storage, EntityId(2)));
+
+        storage.post_process();
+        assert_ne!(a, roll(&*storage, EntityId(1)));
+    }
+}
@@ -0,0 +1,305 @@
+//! Machine readable description of the scripting API.
+//!
+//! Clients may use this to generate card editors, and compare `SchemaDocument::hash` to detect
+//! changes of the import set.
+//!
+use super::bots::IntentKind;
+use super::find_api::FindConstant;
+use super::{make_import, OperationResult};
+use crate::components::{DeathCause, Resource, StructureKind};
+use crate::terrain::TileTerrainType;
+use cao_lang::prelude::Scalar;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::Debug;
+
+/// Version of the document format. Bump on breaking changes of `SchemaDocument`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Enum values are discovered by decoding the integers in `0..=ENUM_SCAN_MAX`
+const ENUM_SCAN_MAX: i32 = u8::MAX as i32;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaDocument {
+    pub version: u32,
+    /// Hash of the cards and types, in hex. Changes if any card is added, removed, or its
+    /// signature changes, or if the values of a type change
+    pub hash: String,
+    pub cards: Vec<CardSchema>,
+    /// JSON Schema of the parameter types, by type name
+    pub types: BTreeMap<String, TypeSchema>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardSchema {
+    pub name: String,
+    pub description: String,
+    pub ty: String,
+    /// Type names of the inputs, see `SchemaDocument::types`
+    pub input: Vec<String>,
+    /// Type names of the outputs, see `SchemaDocument::types`
+    pub output: Vec<String>,
+    pub constants: Vec<String>,
+}
+
+/// Subset of JSON Schema used to describe the parameter types
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeSchema {
+    /// `None` if any value is accepted
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub ty: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<i64>,
+    #[serde(rename = "enum", skip_serializing_if = "Option::is_none")]
+    pub enum_values: Option<Vec<i32>>,
+    /// Names of the `enum_values`, in the same order
+    #[serde(rename = "x-enumNames", skip_serializing_if = "Option::is_none")]
+    pub enum_names: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub properties: Option<BTreeMap<&'static str, TypeSchema>>,
+}
+
+impl TypeSchema {
+    fn of_type(ty: &'static str) -> Self {
+        Self {
+            ty: Some(ty),
+            ..Default::default()
+        }
+    }
+
+    fn integer(minimum: i64, maximum: i64) -> Self {
+        Self {
+            minimum: Some(minimum),
+            maximum: Some(maximum),
+            ..Self::of_type("integer")
+        }
+    }
+
+    /// Lists the values scripts may pass as `T`, using the same conversion the imports use to
+    /// decode their parameters
+    fn enumeration<T: TryFrom<Scalar> + Debug + Copy>(value: impl Fn(T) -> i32) -> Self {
+        let variants = (0..=ENUM_SCAN_MAX)
+            .filter_map(|i| T::try_from(Scalar::Integer(i)).ok())
+            .collect::<Vec<_>>();
+        Self {
+            enum_values: Some(variants.iter().map(|v| value(*v)).collect()),
+            enum_names: Some(variants.iter().map(|v| format!("{:?}", v)).collect()),
+            ..Self::of_type("integer")
+        }
+    }
+
+    fn object(properties: Vec<(&'static str, TypeSchema)>) -> Self {
+        Self {
+            properties: Some(properties.into_iter().collect()),
+            ..Self::of_type("object")
+        }
+    }
+
+    fn axial() -> Self {
+        Self::object(vec![
+            ("q", Self::of_type("integer")),
+            ("r", Self::of_type("integer")),
+        ])
+    }
+}
+
+/// Returns `None` for types that are not known by the schema
+pub fn type_schema(type_name: &str) -> Option<TypeSchema> {
+    // type names may or may not contain the module path
+    let name = type_name.rsplit("::").next().unwrap_or(type_name).trim();
+    let schema = match name {
+        "i32" => TypeSchema::integer(i32::MIN as i64, i32::MAX as i64),
+        "u16" => TypeSchema::integer(0, u16::MAX as i64),
+        "u32" => TypeSchema::integer(0, u32::MAX as i64),
+        "f32" | "f64" => TypeSchema::of_type("number"),
+        "String" | "&str" | "str" => TypeSchema::of_type("string"),
+        "Scalar" => TypeSchema::default(),
+        "EntityId" => TypeSchema::integer(0, i32::MAX as i64),
+        "UserId" => TypeSchema {
+            format: Some("uuid"),
+            ..TypeSchema::of_type("string")
+        },
+        "Axial" | "Room" | "RoomPosition" => TypeSchema::axial(),
+        "WorldPosition" => TypeSchema::object(vec![
+            ("room", TypeSchema::axial()),
+            ("roomPos", TypeSchema::axial()),
+        ]),
+        "OperationResult" => TypeSchema::enumeration(|v: OperationResult| v as i32),
+        "FindConstant" => TypeSchema::enumeration(|v: FindConstant| v as i32),
+        "DeathCause" => TypeSchema::enumeration(|v: DeathCause| v as i32),
+        "Resource" => TypeSchema::enumeration(|v: Resource| v as i32),
+        "IntentKind" => TypeSchema::enumeration(|v: IntentKind| v as i32),
+        "StructureKind" => TypeSchema::enumeration(|v: StructureKind| v as i32),
+        "TileTerrainType" => TypeSchema::enumeration(|v: TileTerrainType| v as i32),
+        _ => return None,
+    };
+    Some(schema)
+}
+
+/// FNV-1a, so the hash is stable across builds and platforms
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn hash_type(mut hash: u64, schema: &TypeSchema) -> u64 {
+    hash = fnv1a(hash, schema.ty.unwrap_or("").as_bytes());
+    hash = fnv1a(hash, schema.format.unwrap_or("").as_bytes());
+    for bound in schema.minimum.iter().chain(schema.maximum.iter()) {
+        hash = fnv1a(hash, &bound.to_le_bytes());
+    }
+    for value in schema.enum_values.iter().flatten() {
+        hash = fnv1a(hash, b"v");
+        hash = fnv1a(hash, &value.to_le_bytes());
+    }
+    for name in schema.enum_names.iter().flatten() {
+        hash = fnv1a(hash, b"n");
+        hash = fnv1a(hash, name.as_bytes());
+    }
+    for (name, property) in schema.properties.iter().flatten() {
+        hash = fnv1a(hash, b"p");
+        hash = fnv1a(hash, name.as_bytes());
+        hash = hash_type(hash, property);
+    }
+    fnv1a(hash, &[0])
+}
+
+fn hash_schema(cards: &[CardSchema], types: &BTreeMap<String, TypeSchema>) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325;
+    for card in cards {
+        hash = fnv1a(hash, card.name.as_bytes());
+        hash = fnv1a(hash, card.ty.as_bytes());
+        for ty in card.input.iter() {
+            hash = fnv1a(hash, b"i");
+            hash = fnv1a(hash, ty.as_bytes());
+        }
+        for ty in card.output.iter() {
+            hash = fnv1a(hash, b"o");
+            hash = fnv1a(hash, ty.as_bytes());
+        }
+        for constant in card.constants.iter() {
+            hash = fnv1a(hash, b"c");
+            hash = fnv1a(hash, constant.as_bytes());
+        }
+        hash = fnv1a(hash, &[0]);
+    }
+    for (name, schema) in types.iter() {
+        hash = fnv1a(hash, b"t");
+        hash = fnv1a(hash, name.as_bytes());
+        hash = hash_type(hash, schema);
+    }
+    hash
+}
+
+/// Describe the game imports and the basic Cao-lang instructions
+pub fn export_schema() -> SchemaDocument {
+    let schema = make_import();
+    let basic_descs = cao_lang::compiler::description::get_instruction_descriptions();
+
+    let to_strings = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    let mut cards = schema
+        .imports()
+        .iter()
+        .map(|import| &import.desc)
+        .chain(basic_descs.iter())
+        .map(|desc| CardSchema {
+            name: desc.name.to_string(),
+            description: desc.description.to_string(),
+            ty: desc.ty.as_str().to_string(),
+            input: to_strings(&*desc.input),
+            output: to_strings(&*desc.output),
+            constants: to_strings(&*desc.constants),
+        })
+        .collect::<Vec<_>>();
+    cards.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let types: BTreeMap<_, _> = cards
+        .iter()
+        .flat_map(|card| card.input.iter().chain(card.output.iter()))
+        .filter_map(|ty| type_schema(ty).map(|schema| (ty.clone(), schema)))
+        .collect();
+
+    SchemaDocument {
+        version: SCHEMA_VERSION,
+        hash: format!("{:016x}", hash_schema(&cards, &types)),
+        cards,
+        types,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_parameters_have_types() {
+        let schema = make_import();
+        for import in schema.imports() {
+            for ty in import.desc.input.iter().chain(import.desc.output.iter()) {
+                assert!(
+                    type_schema(ty).is_some(),
+                    "{}: type {} has no schema",
+                    import.desc.name,
+                    ty
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn enum_values_are_read_from_the_enums() {
+        let schema = type_schema("OperationResult").unwrap();
+        let values = schema.enum_values.unwrap();
+        let names = schema.enum_names.unwrap();
+        assert_eq!(values.len(), names.len());
+        assert_eq!(values.first(), Some(&(OperationResult::Ok as i32)));
+        assert_eq!(values.last(), Some(&(OperationResult::Fatigued as i32)));
+        assert_eq!(names.last().map(|n| n.as_str()), Some("Fatigued"));
+        for (value, name) in values.iter().zip(names.iter()) {
+            let decoded = OperationResult::try_from(Scalar::Integer(*value)).unwrap();
+            assert_eq!(&format!("{:?}", decoded), name);
+        }
+    }
+
+    #[test]
+    fn hash_includes_type_values() {
+        let document = export_schema();
+        let mut types = document.types.clone();
+        let enumeration = types
+            .values_mut()
+            .find(|schema| schema.enum_values.is_some())
+            .expect("no enum in the schema");
+        enumeration
+            .enum_values
+            .as_mut()
+            .unwrap()
+            .push(ENUM_SCAN_MAX + 1);
+
+        assert_eq!(
+            format!("{:016x}", hash_schema(&document.cards, &document.types)),
+            document.hash
+        );
+        assert_ne!(
+            hash_schema(&document.cards, &document.types),
+            hash_schema(&document.cards, &types)
+        );
+    }
+
+    #[test]
+    fn hash_is_stable() {
+        let a = export_schema();
+        let b = export_schema();
+        assert_eq!(a.hash, b.hash);
+        assert_eq!(a.cards.len(), b.cards.len());
+    }
+}
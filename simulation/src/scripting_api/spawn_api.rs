@@ -0,0 +1,77 @@
+//! Request new bots from spawns.
+//!
+//! The bodies of the bots are given by the templates of the game config, see
+//! `GameConfig::bot_templates`.
+//!
+use super::*;
+use crate::intents::{check_spawn_intent, SpawnIntent};
+use crate::storage::views::FromWorld;
+use slog::warn;
+
+fn get_string(
+    vm: &VM<ScriptExecutionData>,
+    ptr: Pointer,
+    name: &str,
+) -> Result<String, ExecutionError> {
+    vm.get_value_in_place::<&str>(ptr)
+        .map(|s| s.to_owned())
+        .ok_or_else(|| {
+            ExecutionError::invalid_argument(format!("spawn called with an invalid {}", name))
+        })
+}
+
+fn request_spawn(
+    vm: &mut VM<ScriptExecutionData>,
+    spawn_id: Pointer,
+    template: Pointer,
+    group: Option<Pointer>,
+) -> Result<(), ExecutionError> {
+    let spawn_id: EntityId = vm.get_value(spawn_id).ok_or_else(|| {
+        warn!(vm.get_aux().logger, "spawn called without a spawn");
+        ExecutionError::invalid_argument("spawn called without a spawn".to_owned())
+    })?;
+    let template = get_string(vm, template, "template")?;
+    let group = group.map(|g| get_string(vm, g, "group")).transpose()?;
+
+    let aux = vm.get_aux();
+    let logger = &aux.logger;
+    trace!(
+        logger,
+        "spawn: spawn: {:?} template: {:?} group: {:?}",
+        spawn_id,
+        template,
+        group
+    );
+
+    let user_id = aux.user_id.expect("user_id to be set");
+    let intent = SpawnIntent {
+        spawn_id,
+        owner_id: Some(user_id),
+        template,
+        group,
+    };
+    let checkresult = check_spawn_intent(logger, &intent, user_id, FromWorld::new(aux.storage()));
+    if let OperationResult::Ok = checkresult {
+        vm.get_aux_mut().intents.spawn_intent = Some(intent);
+    }
+    vm.stack_push(checkresult)?;
+    Ok(())
+}
+
+/// Queue a bot of the given template in a spawn of the user
+pub fn spawn(
+    vm: &mut VM<ScriptExecutionData>,
+    (spawn_id, template): (Pointer, Pointer),
+) -> Result<(), ExecutionError> {
+    profile!("spawn");
+    request_spawn(vm, spawn_id, template, None)
+}
+
+/// Queue a bot of the given template in a spawn of the user. The bot joins the group when spawned
+pub fn spawn_in_group(
+    vm: &mut VM<ScriptExecutionData>,
+    (spawn_id, template, group): (Pointer, Pointer, Pointer),
+) -> Result<(), ExecutionError> {
+    profile!("spawn_in_group");
+    request_spawn(vm, spawn_id, template, Some(group))
+}
@@ -0,0 +1,210 @@
+//! Check programs against the import schema, before they are accepted.
+//!
+//! Calls to functions the VM does not register would only fail at runtime otherwise.
+//!
+use super::make_import;
+use cao_lang::prelude::{Card, CompilationUnit};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error;
+
+/// Imports that pop a number of values given by their first argument, on top of their inputs
+const VARIADIC_IMPORTS: &[&str] = &["Random Choose"];
+
+#[derive(Debug, Clone, Serialize, Error)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum CardError {
+    #[error("Lane {lane} card {card}: unknown function {name:?}")]
+    #[serde(rename_all = "camelCase")]
+    UnknownFunction {
+        lane: usize,
+        card: usize,
+        name: String,
+    },
+    #[error(
+        "Lane {lane} card {card}: {name:?} takes {expected} arguments, but at most {available} are available"
+    )]
+    #[serde(rename_all = "camelCase")]
+    MissingArguments {
+        lane: usize,
+        card: usize,
+        name: String,
+        expected: usize,
+        available: usize,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Error)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationErrors {
+    pub validation_errors: Vec<CardError>,
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, err) in self.validation_errors.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", err)?;
+        }
+        Ok(())
+    }
+}
+
+struct Arity {
+    input: usize,
+    output: usize,
+    variadic: bool,
+}
+
+/// Check that every `Call` card calls an import, and that the calls have enough arguments.
+///
+/// Stack depths are only tracked in the first lane, until the first card of unknown effect.
+/// Only programs that are certain to fail are rejected.
+pub fn validate_program(cu: &CompilationUnit) -> Result<(), ValidationErrors> {
+    let schema = make_import();
+    let arities = schema
+        .imports()
+        .iter()
+        .map(|import| {
+            (
+                import.desc.name,
+                Arity {
+                    input: import.desc.input.len(),
+                    output: import.desc.output.len(),
+                    variadic: VARIADIC_IMPORTS.contains(&import.desc.name),
+                },
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut errors = Vec::new();
+    for (lane_index, lane) in cu.lanes.iter().enumerate() {
+        // None if the depth of the stack is not known
+        let mut depth = if lane_index == 0 { Some(0) } else { None };
+        for (card_index, card) in lane.cards.iter().enumerate() {
+            let call = match card {
+                Card::Call(call) => call.0.as_str(),
+                Card::ScalarInt(_) | Card::ScalarFloat(_) | Card::StringLiteral(_) => {
+                    depth = depth.map(|d| d + 1);
+                    continue;
+                }
+                _ => {
+                    depth = None;
+                    continue;
+                }
+            };
+            let arity = match arities.get(call) {
+                Some(arity) => arity,
+                None => {
+                    errors.push(CardError::UnknownFunction {
+                        lane: lane_index,
+                        card: card_index,
+                        name: call.to_owned(),
+                    });
+                    depth = None;
+                    continue;
+                }
+            };
+            depth = match depth {
+                Some(available) if available < arity.input => {
+                    errors.push(CardError::MissingArguments {
+                        lane: lane_index,
+                        card: card_index,
+                        name: call.to_owned(),
+                        expected: arity.input,
+                        available,
+                    });
+                    None
+                }
+                // the number of values popped is only known at runtime
+                Some(_) if arity.variadic => None,
+                Some(available) => Some(available - arity.input + arity.output),
+                None => None,
+            };
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationErrors {
+            validation_errors: errors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(cards: &str) -> CompilationUnit {
+        let cu = format!(
+            r#"{{ "lanes": [ {{ "name": "main", "cards": {} }} ] }}"#,
+            cards
+        );
+        serde_json::from_str(&cu).expect("Failed to parse program")
+    }
+
+    #[test]
+    fn accepts_known_calls() {
+        let cu = program(r#"[ { "StringLiteral": "hello" }, { "Call": "Console Log" } ]"#);
+        validate_program(&cu).expect("Expected the program to be valid");
+    }
+
+    #[test]
+    fn reports_unknown_calls_and_missing_arguments() {
+        let cu = program(
+            r#"[
+                { "Call": "Console Log" },
+                { "Call": "Launch Rockets" }
+            ]"#,
+        );
+        let errors = validate_program(&cu)
+            .expect_err("Expected validation errors")
+            .validation_errors;
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(matches!(
+            errors[0],
+            CardError::MissingArguments {
+                card: 0,
+                expected: 1,
+                available: 0,
+                ..
+            }
+        ));
+        assert!(
+            matches!(errors[1], CardError::UnknownFunction { card: 1, ref name, .. } if name == "Launch Rockets")
+        );
+    }
+
+    #[test]
+    fn variadic_calls_are_accepted() {
+        let cu = program(
+            r#"[
+                { "ScalarInt": 1 },
+                { "ScalarInt": 2 },
+                { "ScalarInt": 3 },
+                { "ScalarInt": 3 },
+                { "Call": "Random Choose" },
+                { "Call": "Log Scalar" }
+            ]"#,
+        );
+        validate_program(&cu).expect("Expected the program to be valid");
+
+        let cu = program(r#"[ { "Call": "Random Choose" } ]"#);
+        let errors = validate_program(&cu)
+            .expect_err("Expected validation errors")
+            .validation_errors;
+        assert!(matches!(
+            errors[0],
+            CardError::MissingArguments {
+                expected: 1,
+                available: 0,
+                ..
+            }
+        ));
+    }
+}
@@ -22,6 +22,12 @@
 /// let entity_1 = store.insert_entity();
 /// let entity_2 = store.insert_entity();
 ///
+/// let carry = |amount| {
+///     let mut carry = CarryComponent::new(69);
+///     carry.add(Resource::Energy, amount);
+///     carry
+/// };
+///
 /// query!(
 ///     mutate
 ///     store
@@ -29,9 +35,9 @@
 ///         EntityId, Bot, .insert(entity_1);
 ///         EntityId, Bot, .insert(entity_2);
 ///         EntityId, CarryComponent,
-///                  .insert_or_update(entity_1, CarryComponent{carry: 12, carry_max: 69});
+///                  .insert_or_update(entity_1, carry(12));
 ///         EntityId, CarryComponent,
-///                  .insert_or_update(entity_2, CarryComponent{carry: 0, carry_max: 69});
+///                  .insert_or_update(entity_2, carry(0));
 ///     }
 /// );
 /// ```
@@ -80,6 +86,12 @@ macro_rules! query {
 ///
 /// // Initialize entities ...
 ///
+/// let carry = |amount| {
+///     let mut carry = CarryComponent::new(69);
+///     carry.add(Resource::Energy, amount);
+///     carry
+/// };
+///
 /// query!(
 ///    mutate
 ///    store
@@ -94,11 +106,11 @@ macro_rules! query {
 ///        // notice how entity_3 is not a bot, but has carry
 ///
 ///        EntityId, CarryComponent,
-///                 .insert_or_update(entity_1, CarryComponent{carry: 12, carry_max: 69});
+///                 .insert_or_update(entity_1, carry(12));
 ///        EntityId, CarryComponent,
-///                 .insert_or_update(entity_2, CarryComponent{carry: 30, carry_max: 69});
+///                 .insert_or_update(entity_2, carry(30));
 ///        EntityId, CarryComponent,
-///                 .insert_or_update(entity_3, CarryComponent{carry: 40, carry_max: 69});
+///                 .insert_or_update(entity_3, carry(40));
 ///    }
 /// );
 ///
@@ -115,7 +127,7 @@ macro_rules! query {
 ///     // we'll extract the carry amount
 ///     //
 ///     // pos_components are default (0,0), we access them for demo purposes...
-///     .map(|(id, (bot, pos, car))|{ car.carry as i32 + pos.0.pos.q })
+///     .map(|(id, (bot, pos, car))|{ car.total() as i32 + pos.0.pos.q })
 ///     .sum();
 ///
 /// assert_eq!(res, 42); // entity_1 carry + entity_2 carry
@@ -141,6 +153,12 @@ macro_rules! query {
 /// let entity_2 = store.insert_entity();
 /// let entity_3 = store.insert_entity();
 ///
+/// let carry = |amount| {
+///     let mut carry = CarryComponent::new(69);
+///     carry.add(Resource::Energy, amount);
+///     carry
+/// };
+///
 /// query!(
 ///     mutate
 ///     store
@@ -155,11 +173,11 @@ macro_rules! query {
 ///         // notice how entity_3 is not a bot, but has carry
 ///
 ///         EntityId, CarryComponent,
-///                  .insert_or_update(entity_1, CarryComponent{carry: 12, carry_max: 69});
+///                  .insert_or_update(entity_1, carry(12));
 ///         EntityId, CarryComponent,
-///                  .insert_or_update(entity_2, CarryComponent{carry: 30, carry_max: 69});
+///                  .insert_or_update(entity_2, carry(30));
 ///         EntityId, CarryComponent,
-///                  .insert_or_update(entity_3, CarryComponent{carry: 40, carry_max: 69});
+///                  .insert_or_update(entity_3, carry(40));
 ///     }
 /// );
 ///
@@ -176,7 +194,7 @@ macro_rules! query {
 ///     //
 ///     // pos_components are default (0,0), we access them for demo purposes...
 ///     .map(|(id, (_bot_component, pos_component, carry_component))| {
-///         carry_component.carry as i32 + pos_component.0.pos.q
+///         carry_component.total() as i32 + pos_component.0.pos.q
 ///     })
 ///     .sum();
 ///
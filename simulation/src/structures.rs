@@ -0,0 +1,803 @@
+//! Placement of structures.
+//!
+//! Every structure has `Structure`, `StructureKind`, `PositionComponent` and `OwnedEntity`
+//! components, the rest depend on their kind.
+//!
+//! Structures may be placed instantly, or as construction sites, that bots build by spending
+//! energy. See `BuildIntent`.
+//!
+//! Claimed rooms have a controller. The level of the controller limits the number of structures
+//! of each kind in its room, see `structure_limit`. Rooms without a controller are limited as if
+//! their controller was of level 0.
+//!
+use crate::components::*;
+use crate::indices::{ConfigKey, EntityId, Room, UserId, WorldPosition};
+use crate::room_rules::{check_build_rules, RoomRuleError};
+use crate::storage::views::{FromWorld, FromWorldMut, UnsafeView, UnwrapView, View};
+use crate::tables::Table;
+use crate::world::World;
+use thiserror::Error;
+
+pub const SPAWN_ENERGY: u16 = 500;
+pub const SPAWN_ENERGY_REGEN: u16 = 5;
+pub const STORAGE_CAPACITY: u16 = 2000;
+pub const STORAGE_HP: u16 = 1000;
+pub const TOWER_ENERGY: u16 = 500;
+pub const TOWER_HP: u16 = 1000;
+pub const TOWER: TowerComponent = TowerComponent {
+    range: 6,
+    damage: 20,
+    energy_per_shot: 10,
+};
+pub const WALL_HP: u16 = 5000;
+pub const ROAD_HP: u16 = 100;
+pub const CONTROLLER_HP: u16 = 5000;
+pub const CONTROLLER_MAX_LEVEL: u16 = 5;
+/// Total controller progress needed for each user level above 1
+pub const USER_LEVEL_PROGRESS: u32 = 5000;
+
+#[derive(Debug, Clone, Error)]
+pub enum PlaceStructureError {
+    #[error("user {user_id:?} already has a spawn ({spawn_id:?})!")]
+    UserHasSpawn { user_id: UserId, spawn_id: EntityId },
+
+    #[error("position {0:?} is not valid!")]
+    InvalidPosition(WorldPosition),
+
+    #[error("position {0:?} is taken!")]
+    TakenPosition(WorldPosition),
+
+    #[error("structures of kind {0:?} can not be placed")]
+    InvalidKind(StructureKind),
+
+    #[error("the controller of room {room:?} allows at most {limit} structures of kind {kind:?}")]
+    StructureLimit {
+        room: Room,
+        kind: StructureKind,
+        limit: u32,
+    },
+
+    #[error("room {0:?} already has a controller")]
+    RoomHasController(Room),
+
+    #[error("room {0:?} has no free position for a controller")]
+    NoFreePosition(Room),
+
+    #[error("user {user_id:?} already has {limit} construction sites")]
+    TooManySites { user_id: UserId, limit: u32 },
+
+    #[error("{0}")]
+    RoomRules(RoomRuleError),
+}
+
+/// Energy needed to build a structure of the given kind from a construction site
+pub fn construction_cost(kind: StructureKind) -> u32 {
+    match kind {
+        StructureKind::Spawn => 3000,
+        StructureKind::Storage => 1000,
+        StructureKind::Tower => 1500,
+        StructureKind::Wall => 500,
+        StructureKind::Road => 100,
+        // controllers are placed by claiming the room, see `place_controller`
+        StructureKind::Controller => 0,
+    }
+}
+
+/// Energy needed to upgrade a controller of the given level to the next level
+pub fn controller_progress_max(level: u16) -> u32 {
+    1000u32 << level.max(1).min(CONTROLLER_MAX_LEVEL).saturating_sub(1)
+}
+
+/// Energy delivered to the controller over all of its levels
+pub fn controller_total_progress(controller: &RoomControllerComponent) -> u32 {
+    (1..controller.level)
+        .map(controller_progress_max)
+        .sum::<u32>()
+        + controller.progress
+}
+
+/// The user level reached by the given total progress of the controllers of the user
+pub fn user_level(total_progress: u32) -> u16 {
+    let level = 1 + total_progress / USER_LEVEL_PROGRESS;
+    level.min(u16::MAX as u32) as u16
+}
+
+/// Maximum number of structures of the given kind, including construction sites, in a room whose
+/// controller is of the given level
+pub fn structure_limit(level: u16, kind: StructureKind) -> u32 {
+    match (kind, level) {
+        (StructureKind::Controller, _) => 1,
+        (StructureKind::Spawn, _) => 1,
+        (StructureKind::Storage, 0..=1) => 0,
+        (StructureKind::Storage, 2..=3) => 1,
+        (StructureKind::Storage, _) => 2,
+        (StructureKind::Tower, 0..=1) => 0,
+        (StructureKind::Tower, 2) => 1,
+        (StructureKind::Tower, 3) => 2,
+        (StructureKind::Tower, _) => 3,
+        (StructureKind::Wall, 0..=1) => 0,
+        (StructureKind::Wall, 2) => 10,
+        (StructureKind::Wall, 3) => 25,
+        (StructureKind::Wall, _) => 50,
+        (StructureKind::Road, 0..=1) => 10,
+        (StructureKind::Road, 2) => 20,
+        (StructureKind::Road, 3) => 50,
+        (StructureKind::Road, _) => 100,
+    }
+}
+
+/// Views used to validate the placement of structures and construction sites
+#[derive(Clone, Copy)]
+pub struct PlacementCheckInput<'a> {
+    pub terrain: View<'a, WorldPosition, TerrainComponent>,
+    pub entities_by_pos: View<'a, WorldPosition, EntityComponent>,
+    pub roads: View<'a, WorldPosition, RoadComponent>,
+    pub sites_by_pos: View<'a, WorldPosition, SiteComponent>,
+    pub structures: View<'a, EntityId, Structure>,
+    pub spawns: View<'a, EntityId, SpawnComponent>,
+    pub sites: View<'a, EntityId, ConstructionSite>,
+    pub positions: View<'a, EntityId, PositionComponent>,
+    pub owners: View<'a, EntityId, OwnedEntity>,
+    pub kinds: View<'a, EntityId, StructureKind>,
+    pub controllers: View<'a, EntityId, RoomControllerComponent>,
+    pub room_owners: View<'a, Room, OwnedEntity>,
+    pub config: UnwrapView<'a, ConfigKey, game_config::GameConfig>,
+}
+
+impl<'a> FromWorld<'a> for PlacementCheckInput<'a> {
+    fn new(w: &'a World) -> Self {
+        Self {
+            terrain: FromWorld::new(w),
+            entities_by_pos: FromWorld::new(w),
+            roads: FromWorld::new(w),
+            sites_by_pos: FromWorld::new(w),
+            structures: FromWorld::new(w),
+            spawns: FromWorld::new(w),
+            sites: FromWorld::new(w),
+            positions: FromWorld::new(w),
+            owners: FromWorld::new(w),
+            kinds: FromWorld::new(w),
+            controllers: FromWorld::new(w),
+            room_owners: FromWorld::new(w),
+            config: FromWorld::new(w),
+        }
+    }
+}
+
+/// - Controllers may only be placed by claiming the room
+/// - The room must allow the user to build, see `room_rules::check_build_rules`
+/// - The terrain at `position` must be walkable
+/// - There may be no construction site at `position`
+/// - Roads may not be placed on other structures, but may be placed under bots
+/// - Other structures need a free position
+/// - A user may only have 1 spawn, including spawns under construction
+/// - The controller of the room limits the number of structures of each kind. Rooms without a
+///   controller get the limits of level 0
+pub fn check_structure_placement(
+    owner_id: UserId,
+    kind: StructureKind,
+    position: WorldPosition,
+    PlacementCheckInput {
+        terrain,
+        entities_by_pos,
+        roads,
+        sites_by_pos,
+        structures,
+        spawns,
+        sites,
+        owners,
+        kinds,
+        controllers,
+        room_owners,
+        config,
+        ..
+    }: PlacementCheckInput,
+) -> Result<(), PlaceStructureError> {
+    if kind == StructureKind::Controller {
+        return Err(PlaceStructureError::InvalidKind(kind));
+    }
+
+    check_build_rules(
+        &config.room_rules,
+        owner_id,
+        Room(position.room),
+        room_owners,
+    )
+    .map_err(PlaceStructureError::RoomRules)?;
+
+    let is_valid_terrain = terrain
+        .get_by_id(&position)
+        .map(|TerrainComponent(t)| t.is_walkable())
+        .unwrap_or(false);
+    if !is_valid_terrain {
+        return Err(PlaceStructureError::InvalidPosition(position));
+    }
+
+    // the site index is rebuilt once per tick, completed sites are structures still standing there
+    let has_site = sites_by_pos
+        .get_by_id(&position)
+        .map(|SiteComponent(id)| structures.contains_id(id))
+        .unwrap_or(false);
+    if has_site {
+        return Err(PlaceStructureError::TakenPosition(position));
+    }
+
+    let occupant = entities_by_pos
+        .get_by_id(&position)
+        .map(|EntityComponent(id)| *id);
+    match kind {
+        StructureKind::Road => {
+            let has_road = roads.contains_key(&position);
+            let has_structure = occupant
+                .map(|id| structures.contains_id(&id))
+                .unwrap_or(false);
+            if has_road || has_structure {
+                return Err(PlaceStructureError::TakenPosition(position));
+            }
+        }
+        _ => {
+            if occupant.is_some() {
+                return Err(PlaceStructureError::TakenPosition(position));
+            }
+        }
+    }
+
+    if kind == StructureKind::Spawn {
+        // a player may only have 1 spawn atm
+        let is_owned = |id: &EntityId| {
+            owners
+                .get_by_id(id)
+                .map(|owner| owner.owner_id == owner_id)
+                .unwrap_or(false)
+        };
+        let spawn_id = spawns
+            .iter()
+            .map(|(id, _)| id)
+            .chain(
+                sites
+                    .iter()
+                    .filter(|(_, site)| site.kind == StructureKind::Spawn)
+                    .map(|(id, _)| id),
+            )
+            .find(is_owned);
+        if let Some(spawn_id) = spawn_id {
+            return Err(PlaceStructureError::UserHasSpawn {
+                user_id: owner_id,
+                spawn_id,
+            });
+        }
+    }
+
+    // structures and sites of the room, an entity may be in more than one of the indices
+    let room = position.room;
+    let mut room_entities: Vec<EntityId> = entities_by_pos
+        .table
+        .get_by_id(&room)
+        .into_iter()
+        .flat_map(|entities| entities.iter().map(|(_, EntityComponent(id))| *id))
+        .chain(
+            roads
+                .table
+                .get_by_id(&room)
+                .into_iter()
+                .flat_map(|roads| roads.iter().map(|(_, RoadComponent(id))| *id)),
+        )
+        .chain(
+            sites_by_pos
+                .table
+                .get_by_id(&room)
+                .into_iter()
+                .flat_map(|sites| sites.iter().map(|(_, SiteComponent(id))| *id)),
+        )
+        .collect();
+    room_entities.sort_unstable();
+    room_entities.dedup();
+
+    let level = room_entities
+        .iter()
+        .find_map(|id| controllers.get_by_id(id))
+        .map(|controller| controller.level)
+        .unwrap_or(0);
+    let limit = structure_limit(level, kind);
+    let count = room_entities
+        .iter()
+        .filter(|id| {
+            kinds
+                .get_by_id(id)
+                .copied()
+                .or_else(|| sites.get_by_id(id).map(|site| site.kind))
+                == Some(kind)
+        })
+        .count();
+    if count as u32 >= limit {
+        return Err(PlaceStructureError::StructureLimit {
+            room: Room(position.room),
+            kind,
+            limit,
+        });
+    }
+    Ok(())
+}
+
+/// Validate the placement, then create a structure of the given kind
+pub fn place_structure(
+    storage: &mut World,
+    owner_id: UserId,
+    kind: StructureKind,
+    position: WorldPosition,
+) -> Result<EntityId, PlaceStructureError> {
+    check_structure_placement(owner_id, kind, position, FromWorld::new(storage as &_))?;
+    Ok(insert_structure(storage, owner_id, kind, position))
+}
+
+/// Claim the room for the user by placing a controller at the free position closest to the
+/// center of the room
+pub fn place_controller(
+    storage: &mut World,
+    owner_id: UserId,
+    room: Room,
+) -> Result<EntityId, PlaceStructureError> {
+    let entities_by_pos = storage.view::<WorldPosition, EntityComponent>();
+    let controllers = storage.view::<EntityId, RoomControllerComponent>();
+    let has_controller = entities_by_pos
+        .table
+        .get_by_id(&room.0)
+        .map(|entities| {
+            entities
+                .iter()
+                .any(|(_, EntityComponent(id))| controllers.contains(id))
+        })
+        .unwrap_or(false);
+    if has_controller {
+        return Err(PlaceStructureError::RoomHasController(room));
+    }
+
+    let center = storage
+        .view::<ConfigKey, RoomProperties>()
+        .value
+        .as_ref()
+        .map(|props| props.center)
+        .unwrap_or_default();
+    let sites_by_pos = storage.view::<WorldPosition, SiteComponent>();
+    let roads = storage.view::<WorldPosition, RoadComponent>();
+    let position = storage
+        .view::<WorldPosition, TerrainComponent>()
+        .iter_rooms()
+        .find(|(r, _)| *r == room)
+        .and_then(|(_, terrain)| {
+            terrain.find_closest_by_filter(&center, |pos, TerrainComponent(tile)| {
+                let pos = WorldPosition {
+                    room: room.0,
+                    pos: *pos,
+                };
+                tile.is_walkable()
+                    && !entities_by_pos.contains_key(&pos)
+                    && !roads.contains_key(&pos)
+                    && !sites_by_pos.contains_key(&pos)
+            })
+        })
+        .map(|(_, pos, _)| WorldPosition { room: room.0, pos })
+        .ok_or(PlaceStructureError::NoFreePosition(room))?;
+
+    Ok(insert_structure(
+        storage,
+        owner_id,
+        StructureKind::Controller,
+        position,
+    ))
+}
+
+/// Expects the placement to be validated
+fn insert_structure(
+    storage: &mut World,
+    owner_id: UserId,
+    kind: StructureKind,
+    position: WorldPosition,
+) -> EntityId {
+    let entity_id = storage.insert_entity();
+    insert_structure_components(entity_id, kind, FromWorldMut::new(storage));
+
+    storage
+        .unsafe_view::<EntityId, PositionComponent>()
+        .insert_or_update(entity_id, PositionComponent(position));
+    storage
+        .unsafe_view::<EntityId, OwnedEntity>()
+        .insert_or_update(entity_id, OwnedEntity { owner_id });
+    if kind.is_walkable() {
+        storage
+            .unsafe_view::<WorldPosition, RoadComponent>()
+            .insert(position, RoadComponent(entity_id))
+            .expect("Failed to insert road");
+    } else {
+        storage
+            .unsafe_view::<WorldPosition, EntityComponent>()
+            .insert(position, EntityComponent(entity_id))
+            // expect that position validity is confirmed at this point
+            .expect("Failed to insert position");
+    }
+
+    entity_id
+}
+
+/// Same as `check_structure_placement`, but users may have at most
+/// `GameConfig::max_construction_sites` sites
+pub fn check_site_placement(
+    owner_id: UserId,
+    kind: StructureKind,
+    position: WorldPosition,
+    views: PlacementCheckInput,
+) -> Result<(), PlaceStructureError> {
+    check_structure_placement(owner_id, kind, position, views)?;
+
+    let PlacementCheckInput {
+        sites,
+        owners,
+        config,
+        ..
+    } = views;
+    let limit = config.max_construction_sites;
+    let count = sites
+        .iter()
+        .filter(|(id, _)| {
+            owners
+                .get_by_id(id)
+                .map(|owner| owner.owner_id == owner_id)
+                .unwrap_or(false)
+        })
+        .count();
+    if count as u32 >= limit {
+        return Err(PlaceStructureError::TooManySites {
+            user_id: owner_id,
+            limit,
+        });
+    }
+    Ok(())
+}
+
+/// Validate the placement, then create a construction site of a structure of the given kind
+pub fn place_construction_site(
+    storage: &mut World,
+    owner_id: UserId,
+    kind: StructureKind,
+    position: WorldPosition,
+) -> Result<EntityId, PlaceStructureError> {
+    check_site_placement(owner_id, kind, position, FromWorld::new(storage as &_))?;
+
+    let entity_id = storage.insert_entity();
+    insert_construction_site(
+        entity_id,
+        owner_id,
+        kind,
+        position,
+        FromWorldMut::new(storage),
+    );
+    Ok(entity_id)
+}
+
+pub type InsertSiteMut = (
+    UnsafeView<EntityId, ConstructionSite>,
+    UnsafeView<EntityId, Structure>,
+    UnsafeView<EntityId, PositionComponent>,
+    UnsafeView<EntityId, OwnedEntity>,
+    UnsafeView<WorldPosition, EntityComponent>,
+    UnsafeView<WorldPosition, SiteComponent>,
+);
+
+/// Sites of walkable structures do not occupy their position.
+/// Expects the placement to be validated by `check_structure_placement`.
+pub fn insert_construction_site(
+    entity_id: EntityId,
+    owner_id: UserId,
+    kind: StructureKind,
+    position: WorldPosition,
+    (
+        mut sites,
+        mut structures,
+        mut positions,
+        mut owners,
+        mut entities_by_pos,
+        mut sites_by_pos,
+    ): InsertSiteMut,
+) {
+    sites.insert_or_update(
+        entity_id,
+        ConstructionSite {
+            kind,
+            progress: 0,
+            progress_max: construction_cost(kind),
+        },
+    );
+    structures.insert(entity_id);
+    positions.insert_or_update(entity_id, PositionComponent(position));
+    owners.insert_or_update(entity_id, OwnedEntity { owner_id });
+    sites_by_pos
+        .insert(position, SiteComponent(entity_id))
+        .expect("Failed to insert site position");
+    if !kind.is_walkable() {
+        entities_by_pos
+            .insert(position, EntityComponent(entity_id))
+            .expect("Failed to insert position");
+    }
+}
+
+pub type InsertStructureMut = (
+    UnsafeView<EntityId, Structure>,
+    UnsafeView<EntityId, StructureKind>,
+    UnsafeView<EntityId, EnergyComponent>,
+    UnsafeView<EntityId, HpComponent>,
+    UnsafeView<EntityId, SpawnComponent>,
+    UnsafeView<EntityId, SpawnQueueComponent>,
+    UnsafeView<EntityId, EnergyRegenComponent>,
+    UnsafeView<EntityId, TowerComponent>,
+    UnsafeView<EntityId, CarryComponent>,
+    UnsafeView<EntityId, RoomControllerComponent>,
+);
+
+/// Insert the components of a structure of the given kind, except for its position and owner
+pub fn insert_structure_components(
+    entity_id: EntityId,
+    kind: StructureKind,
+    (
+        mut structures,
+        mut kinds,
+        mut energy,
+        mut hp,
+        mut spawns,
+        mut spawn_queues,
+        mut regens,
+        mut towers,
+        mut stores,
+        mut controllers,
+    ): InsertStructureMut,
+) {
+    let full_hp = |hp_max| HpComponent { hp: hp_max, hp_max };
+    structures.insert(entity_id);
+    kinds.insert_or_update(entity_id, kind);
+    match kind {
+        StructureKind::Spawn => {
+            spawns.insert_or_update(entity_id, SpawnComponent::default());
+            spawn_queues.insert_or_update(entity_id, SpawnQueueComponent::default());
+            regens.insert_or_update(
+                entity_id,
+                EnergyRegenComponent {
+                    amount: SPAWN_ENERGY_REGEN,
+                },
+            );
+            energy.insert_or_update(
+                entity_id,
+                EnergyComponent {
+                    energy: 0,
+                    energy_max: SPAWN_ENERGY,
+                },
+            );
+        }
+        StructureKind::Storage => {
+            stores.insert_or_update(entity_id, CarryComponent::new(STORAGE_CAPACITY));
+            hp.insert_or_update(entity_id, full_hp(STORAGE_HP));
+        }
+        StructureKind::Tower => {
+            towers.insert_or_update(entity_id, TOWER);
+            energy.insert_or_update(
+                entity_id,
+                EnergyComponent {
+                    energy: 0,
+                    energy_max: TOWER_ENERGY,
+                },
+            );
+            hp.insert_or_update(entity_id, full_hp(TOWER_HP));
+        }
+        StructureKind::Wall => {
+            hp.insert_or_update(entity_id, full_hp(WALL_HP));
+        }
+        StructureKind::Road => {
+            hp.insert_or_update(entity_id, full_hp(ROAD_HP));
+        }
+        StructureKind::Controller => {
+            controllers.insert_or_update(
+                entity_id,
+                RoomControllerComponent {
+                    level: 1,
+                    progress: 0,
+                    progress_max: controller_progress_max(1),
+                },
+            );
+            hp.insert_or_update(entity_id, full_hp(CONTROLLER_HP));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::{build_world, Fixture};
+    use crate::prelude::Axial;
+    use crate::utils::{setup_testing, test_logger};
+
+    fn world() -> std::pin::Pin<Box<World>> {
+        let fixture: Fixture = serde_json::from_str(
+            r#"{
+                "roomRadius": 5,
+                "rooms": [ { "room": { "q": 0, "r": 0 }, "defaultTerrain": "plain" } ]
+            }"#,
+        )
+        .expect("Failed to parse fixture");
+        build_world(test_logger(), &fixture).expect("Failed to build the world")
+    }
+
+    #[test]
+    fn roads_may_not_stack_but_are_walkable() {
+        setup_testing();
+        let mut world = world();
+        let owner_id = UserId::default();
+        let pos = WorldPosition {
+            room: Axial::new(0, 0),
+            pos: Axial::new(5, 5),
+        };
+
+        place_structure(&mut *world, owner_id, StructureKind::Road, pos).expect("Road");
+        assert!(world
+            .view::<WorldPosition, EntityComponent>()
+            .get_by_id(&pos)
+            .is_none());
+        assert!(matches!(
+            place_structure(&mut *world, owner_id, StructureKind::Road, pos),
+            Err(PlaceStructureError::TakenPosition(_))
+        ));
+
+        place_structure(&mut *world, owner_id, StructureKind::Spawn, pos).expect("Spawn");
+        assert!(matches!(
+            place_structure(&mut *world, owner_id, StructureKind::Tower, pos),
+            Err(PlaceStructureError::TakenPosition(_))
+        ));
+    }
+
+    #[test]
+    fn one_spawn_per_user() {
+        setup_testing();
+        let mut world = world();
+        let owner_id = UserId::default();
+        let pos = |q| WorldPosition {
+            room: Axial::new(0, 0),
+            pos: Axial::new(q, 5),
+        };
+
+        let spawn_id =
+            place_structure(&mut *world, owner_id, StructureKind::Spawn, pos(4)).expect("Spawn");
+        match place_structure(&mut *world, owner_id, StructureKind::Spawn, pos(6)) {
+            Err(PlaceStructureError::UserHasSpawn { spawn_id: id, .. }) => {
+                assert_eq!(id, spawn_id)
+            }
+            res => panic!("Expected UserHasSpawn, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn construction_sites_take_their_position() {
+        setup_testing();
+        let mut world = world();
+        let owner_id = UserId::default();
+        let pos = |q| WorldPosition {
+            room: Axial::new(0, 0),
+            pos: Axial::new(q, 5),
+        };
+
+        let site_id = place_construction_site(&mut *world, owner_id, StructureKind::Spawn, pos(4))
+            .expect("Spawn site");
+        assert!(matches!(
+            place_structure(&mut *world, owner_id, StructureKind::Road, pos(4)),
+            Err(PlaceStructureError::TakenPosition(_))
+        ));
+        // spawns under construction count towards the limit
+        match place_structure(&mut *world, owner_id, StructureKind::Spawn, pos(6)) {
+            Err(PlaceStructureError::UserHasSpawn { spawn_id, .. }) => {
+                assert_eq!(spawn_id, site_id)
+            }
+            res => panic!("Expected UserHasSpawn, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn rooms_without_a_controller_have_level_0_limits() {
+        setup_testing();
+        let mut world = world();
+        let owner_id = UserId::default();
+        let pos = |q| WorldPosition {
+            room: Axial::new(0, 0),
+            pos: Axial::new(q, 5),
+        };
+
+        assert!(matches!(
+            place_structure(&mut *world, owner_id, StructureKind::Tower, pos(4)),
+            Err(PlaceStructureError::StructureLimit { limit: 0, .. })
+        ));
+        let road_limit = structure_limit(0, StructureKind::Road);
+        for q in 0..road_limit as i32 {
+            place_structure(&mut *world, owner_id, StructureKind::Road, pos(q)).expect("Road");
+        }
+        assert!(matches!(
+            place_structure(
+                &mut *world,
+                owner_id,
+                StructureKind::Road,
+                pos(road_limit as i32)
+            ),
+            Err(PlaceStructureError::StructureLimit { .. })
+        ));
+    }
+
+    #[test]
+    fn structure_limits_are_per_room() {
+        setup_testing();
+        let fixture: Fixture = serde_json::from_str(
+            r#"{
+                "roomRadius": 5,
+                "rooms": [
+                    { "room": { "q": 0, "r": 0 }, "defaultTerrain": "plain" },
+                    { "room": { "q": 1, "r": 0 }, "defaultTerrain": "plain" }
+                ]
+            }"#,
+        )
+        .expect("Failed to parse fixture");
+        let mut world = build_world(test_logger(), &fixture).expect("Failed to build the world");
+        let owner_id = UserId::default();
+        let pos = |room, q| WorldPosition {
+            room: Axial::new(room, 0),
+            pos: Axial::new(q, 5),
+        };
+
+        let road_limit = structure_limit(0, StructureKind::Road) as i32;
+        for q in 0..road_limit - 1 {
+            place_structure(&mut *world, owner_id, StructureKind::Road, pos(0, q)).expect("Road");
+        }
+        // sites count towards the limit of their room
+        place_construction_site(
+            &mut *world,
+            owner_id,
+            StructureKind::Road,
+            pos(0, road_limit - 1),
+        )
+        .expect("Road site");
+        assert!(matches!(
+            place_structure(
+                &mut *world,
+                owner_id,
+                StructureKind::Road,
+                pos(0, road_limit)
+            ),
+            Err(PlaceStructureError::StructureLimit { .. })
+        ));
+        place_structure(&mut *world, owner_id, StructureKind::Road, pos(1, 0))
+            .expect("Road in the other room");
+    }
+
+    #[test]
+    fn construction_sites_are_limited_per_user() {
+        setup_testing();
+        let mut world = world();
+        world
+            .config
+            .game_config
+            .value
+            .as_mut()
+            .unwrap()
+            .max_construction_sites = 2;
+        let owner_id = UserId::default();
+        let pos = |q| WorldPosition {
+            room: Axial::new(0, 0),
+            pos: Axial::new(q, 5),
+        };
+
+        for q in 3..5 {
+            place_construction_site(&mut *world, owner_id, StructureKind::Road, pos(q))
+                .expect("Road site");
+        }
+        assert!(matches!(
+            place_construction_site(&mut *world, owner_id, StructureKind::Road, pos(5)),
+            Err(PlaceStructureError::TooManySites { limit: 2, .. })
+        ));
+        // the limit is per user
+        let other = UserId(uuid::Uuid::from_u128(1));
+        place_construction_site(&mut *world, other, StructureKind::Road, pos(5))
+            .expect("Road site of another user");
+    }
+}
@@ -1,20 +1,35 @@
 pub mod attack_system;
+pub mod build_intent_system;
+pub mod construction_site_intent_system;
+pub mod controller_system;
 pub mod death_system;
 pub mod decay_system;
 pub mod dropoff_intent_system;
 pub mod energy_system;
+pub mod fatigue_system;
+pub mod heal_intent_system;
+pub mod intent_outcome_system;
+pub mod invader_system;
 pub mod log_intent_system;
 pub mod log_system;
 pub mod mine_intent_system;
 pub mod mineral_system;
 pub mod move_intent_system;
 pub mod path_cache_intent_system;
+pub mod pile_system;
 pub mod positions_system;
+pub mod remains_system;
 pub mod script_execution;
 pub mod script_history_system;
+pub mod script_rollback_system;
 pub mod spawn_system;
+pub mod tower_system;
+pub mod transfer_intent_system;
+pub mod visibility_system;
 
-use crate::storage::views::{FromWorld, FromWorldMut};
+use crate::components::IntentOutcomes;
+use crate::indices::EntityId;
+use crate::storage::views::{FromWorld, FromWorldMut, UnsafeView};
 use crate::{prelude::World, profile};
 
 pub fn execute_world_update(storage: &mut World) {
@@ -28,13 +43,18 @@ fn execute_intents(storage: &mut World) {
     profile!("execute_intents");
 
     // pre processing
+    execute_update(intent_outcome_system::update, storage);
     execute_update(spawn_system::update_cont_spawns, storage);
 
     // main processing
     execute_update(attack_system::update, storage);
+    execute_update(heal_intent_system::update, storage);
     execute_update(move_intent_system::update, storage);
     execute_update(mine_intent_system::update, storage);
     execute_update(dropoff_intent_system::update, storage);
+    execute_update(transfer_intent_system::update, storage);
+    execute_update(build_intent_system::update, storage);
+    execute_update(construction_site_intent_system::update, storage);
     execute_update(spawn_system::update_spawn_intents, storage);
     execute_update(log_intent_system::update, storage);
     execute_update(path_cache_intent_system::update, storage);
@@ -46,11 +66,19 @@ fn execute_automated_systems(storage: &mut World) {
     profile!("execute_automated_systems");
 
     execute_update(decay_system::update, storage);
+    execute_update(tower_system::update, storage);
     execute_update(death_system::update, storage);
+    execute_update(controller_system::update, storage);
     execute_update(energy_system::update, storage);
+    execute_update(fatigue_system::update, storage);
     execute_update(spawn_system::update_spawns, storage);
     execute_update(mineral_system::update, storage);
+    execute_update(pile_system::update, storage);
+    execute_update(remains_system::update, storage);
+    execute_update(invader_system::update, storage);
     execute_update(positions_system::update, storage);
+    execute_update(visibility_system::update, storage);
+    execute_update(script_rollback_system::update, storage);
     execute_update(log_system::update, storage);
 }
 
@@ -65,3 +93,19 @@ where
     let c = C::new(storage as &_);
     sys(M::clone(&m), c);
 }
+
+/// Update the intent outcomes of the given entity, inserting a new row if it has none yet
+fn record_outcome(
+    outcomes: &mut UnsafeView<EntityId, IntentOutcomes>,
+    entity_id: EntityId,
+    update: impl FnOnce(&mut IntentOutcomes),
+) {
+    match outcomes.get_by_id_mut(&entity_id) {
+        Some(outcome) => update(outcome),
+        None => {
+            let mut outcome = IntentOutcomes::default();
+            update(&mut outcome);
+            outcomes.insert_or_update(entity_id, outcome);
+        }
+    }
+}
@@ -1,56 +1,277 @@
-use crate::components::{HpComponent, MeleeAttackComponent};
+//! Resolves melee and ranged attacks.
+//!
+//! The damage of every attack is computed before any of it is applied, so the outcome does not
+//! depend on the order of the intents. See the `combat` module for the damage rules.
+//! An entity taking both melee and ranged damage in the same tick records ranged as its last
+//! source of damage.
+//!
+use super::record_outcome;
+use crate::combat::{mitigate_damage, ranged_damage};
+use crate::components::{
+    ArmourComponent, DamageType, DeathCause, HpComponent, IntentOutcomes, LastDamageComponent,
+    MeleeAttackComponent, PositionComponent, RangedAttackComponent,
+};
 use crate::indices::*;
 use crate::intents::*;
 use crate::profile;
+use crate::scripting_api::OperationResult;
 use crate::storage::views::{UnsafeView, UnwrapViewMut, View, WorldLogger};
-use rayon::prelude::*;
+use crate::tables::Table;
 use slog::{debug, error, Logger};
+use std::collections::BTreeMap;
 
 type Mut = (
     UnsafeView<EntityId, HpComponent>,
     UnwrapViewMut<EmptyKey, Intents<MeleeIntent>>,
+    UnwrapViewMut<EmptyKey, Intents<RangedIntent>>,
+    UnsafeView<EntityId, IntentOutcomes>,
+    UnsafeView<EntityId, LastDamageComponent>,
+);
+type Const<'a> = (
+    View<'a, EntityId, MeleeAttackComponent>,
+    View<'a, EntityId, RangedAttackComponent>,
+    View<'a, EntityId, ArmourComponent>,
+    View<'a, EntityId, PositionComponent>,
+    WorldLogger,
 );
-type Const<'a> = (View<'a, EntityId, MeleeAttackComponent>, WorldLogger);
 
-pub fn update((mut hp_table, mut intents): Mut, (attack_table, WorldLogger(logger)): Const) {
+pub fn update(
+    (mut hp_table, mut melee_intents, mut ranged_intents, mut outcomes, mut last_damage): Mut,
+    (melee_table, ranged_table, armour_table, positions, WorldLogger(logger)): Const,
+) {
     profile!("AttackSystem update");
 
-    pre_process(&logger, &mut intents.0);
+    // intents removed by pre_process keep this outcome
+    for intent in melee_intents.iter() {
+        record_outcome(&mut outcomes, intent.attacker, |o| {
+            o.melee_intent = Some(OperationResult::Conflict)
+        });
+    }
+    for intent in ranged_intents.iter() {
+        record_outcome(&mut outcomes, intent.attacker, |o| {
+            o.ranged_intent = Some(OperationResult::Conflict)
+        });
+    }
 
-    for intent in intents.iter() {
-        let attack = match attack_table.get_by_id(&intent.attacker) {
-            Some(s) => s,
-            None => {
-                error!(logger, "Attacker has no attack component. {:?}", intent);
-                continue;
-            }
-        };
-        let hp = match hp_table.get_by_id_mut(&intent.defender) {
-            Some(s) => s,
-            None => {
-                error!(logger, "Defender has no hp component. {:?}", intent);
-                continue;
-            }
-        };
-        // hp can not fall below 0
-        hp.hp -= hp.hp.min(attack.strength);
+    pre_process(&mut melee_intents.0, |intent| intent.attacker);
+    pre_process(&mut ranged_intents.0, |intent| intent.attacker);
+
+    let hp_view = View::from_table(&*hp_table);
+    let mut damage = BTreeMap::<EntityId, (u32, DeathCause)>::new();
+
+    for intent in melee_intents.iter() {
+        let res = melee_damage(&logger, intent, &melee_table, hp_view);
+        let res = apply_armour(
+            res,
+            intent.defender,
+            DeathCause::Melee,
+            armour_table,
+            &mut damage,
+        );
+        record_outcome(&mut outcomes, intent.attacker, |o| {
+            o.melee_intent = Some(res)
+        });
+    }
+    for intent in ranged_intents.iter() {
+        let res = ranged_attack_damage(&logger, intent, &ranged_table, positions, hp_view);
+        let res = apply_armour(
+            res,
+            intent.defender,
+            DeathCause::Ranged,
+            armour_table,
+            &mut damage,
+        );
+        record_outcome(&mut outcomes, intent.attacker, |o| {
+            o.ranged_intent = Some(res)
+        });
+    }
+
+    for (defender, (amount, cause)) in damage {
+        debug!(logger, "{:?} takes {} damage", defender, amount);
+        if let Some(hp) = hp_table.get_by_id_mut(&defender) {
+            // hp can not fall below 0
+            hp.hp -= hp.hp.min(amount.min(u16::MAX as u32) as u16);
+            last_damage.insert_or_update(defender, LastDamageComponent(cause));
+        }
     }
 }
 
-fn pre_process(logger: &Logger, intents: &mut Vec<MeleeIntent>) {
-    let len = intents.len();
-    if len < 2 {
-        return;
-    }
-    // dedupe
-    intents.par_sort_unstable_by_key(|intent| intent.attacker);
-    for current in (0..len).rev() {
-        let last = current + 1;
-        let a = &intents[last];
-        let b = &intents[current];
-        if a.attacker == b.attacker {
-            debug!(logger, "Duplicated attacker, removing {:?}", a);
-            intents.swap_remove(last);
+/// Add the mitigated damage of a valid attack to the defender's total
+fn apply_armour(
+    attack: Result<(u16, DamageType), OperationResult>,
+    defender: EntityId,
+    cause: DeathCause,
+    armour_table: View<EntityId, ArmourComponent>,
+    damage: &mut BTreeMap<EntityId, (u32, DeathCause)>,
+) -> OperationResult {
+    match attack {
+        Ok((amount, ty)) => {
+            let amount = mitigate_damage(amount, ty, armour_table.get_by_id(&defender));
+            let entry = damage.entry(defender).or_insert((0, cause));
+            entry.0 += amount as u32;
+            entry.1 = cause;
+            OperationResult::Ok
         }
+        Err(res) => res,
+    }
+}
+
+fn melee_damage(
+    logger: &Logger,
+    intent: &MeleeIntent,
+    attack_table: &View<EntityId, MeleeAttackComponent>,
+    hp_table: View<EntityId, HpComponent>,
+) -> Result<(u16, DamageType), OperationResult> {
+    let attack = match attack_table.get_by_id(&intent.attacker) {
+        Some(s) => s,
+        None => {
+            error!(logger, "Attacker has no attack component. {:?}", intent);
+            return Err(OperationResult::InvalidInput);
+        }
+    };
+    if !hp_table.contains_id(&intent.defender) {
+        error!(logger, "Defender has no hp component. {:?}", intent);
+        return Err(OperationResult::InvalidTarget);
+    }
+    Ok((attack.strength, attack.damage_type))
+}
+
+fn ranged_attack_damage(
+    logger: &Logger,
+    intent: &RangedIntent,
+    attack_table: &View<EntityId, RangedAttackComponent>,
+    positions: View<EntityId, PositionComponent>,
+    hp_table: View<EntityId, HpComponent>,
+) -> Result<(u16, DamageType), OperationResult> {
+    let attack = match attack_table.get_by_id(&intent.attacker) {
+        Some(s) => s,
+        None => {
+            error!(
+                logger,
+                "Attacker has no ranged attack component. {:?}", intent
+            );
+            return Err(OperationResult::InvalidInput);
+        }
+    };
+    if !hp_table.contains_id(&intent.defender) {
+        error!(logger, "Defender has no hp component. {:?}", intent);
+        return Err(OperationResult::InvalidTarget);
+    }
+    let distance = positions.get_by_id(&intent.attacker).and_then(|attacker| {
+        positions
+            .get_by_id(&intent.defender)
+            .filter(|defender| defender.0.room == attacker.0.room)
+            .map(|defender| defender.0.pos.hex_distance(attacker.0.pos))
+    });
+    match distance {
+        Some(distance) if distance <= attack.range => {
+            Ok((ranged_damage(attack, distance), attack.damage_type))
+        }
+        _ => Err(OperationResult::NotInRange),
+    }
+}
+
+/// Every attacker may only attack once per tick
+fn pre_process<T>(intents: &mut Vec<T>, attacker: impl Fn(&T) -> EntityId) {
+    intents.sort_by_key(|intent| attacker(intent));
+    intents.dedup_by_key(|intent| attacker(intent));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indices::WorldPosition;
+    use crate::prelude::Axial;
+    use crate::storage::views::{FromWorld, FromWorldMut};
+    use crate::utils::{setup_testing, test_logger};
+    use crate::world::init_inmemory_storage;
+
+    #[test]
+    fn attacks_are_resolved_simultaneously() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+
+        let at = |q| {
+            PositionComponent(WorldPosition {
+                room: Axial::new(0, 0),
+                pos: Axial::new(q, 0),
+            })
+        };
+
+        let a = store.insert_entity();
+        let b = store.insert_entity();
+        let c = store.insert_entity();
+
+        for (id, pos) in [(a, at(0)), (b, at(1)), (c, at(3))].iter() {
+            store
+                .unsafe_view::<EntityId, PositionComponent>()
+                .insert_or_update(*id, *pos);
+            store
+                .unsafe_view::<EntityId, HpComponent>()
+                .insert_or_update(*id, HpComponent { hp: 20, hp_max: 20 });
+        }
+        store
+            .unsafe_view::<EntityId, MeleeAttackComponent>()
+            .insert_or_update(
+                a,
+                MeleeAttackComponent {
+                    strength: 30,
+                    ..Default::default()
+                },
+            );
+        store
+            .unsafe_view::<EntityId, RangedAttackComponent>()
+            .insert_or_update(
+                b,
+                RangedAttackComponent {
+                    strength: 20,
+                    range: 3,
+                    falloff: 5,
+                    damage_type: DamageType::Thermal,
+                },
+            );
+        store
+            .unsafe_view::<EntityId, ArmourComponent>()
+            .insert_or_update(
+                c,
+                ArmourComponent {
+                    armour: 1,
+                    resistances: [(DamageType::Thermal, 50)].iter().cloned().collect(),
+                },
+            );
+
+        // `b` is killed by `a`, but still gets to shoot `c` in the same tick
+        move_into_storage(
+            &mut *store,
+            vec![
+                BotIntents {
+                    melee_attack_intent: Some(MeleeIntent {
+                        attacker: a,
+                        defender: b,
+                    }),
+                    ..Default::default()
+                },
+                BotIntents {
+                    ranged_attack_intent: Some(RangedIntent {
+                        attacker: b,
+                        defender: c,
+                    }),
+                    ..Default::default()
+                },
+            ],
+        );
+        update(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
+
+        let hp = |id| {
+            store
+                .view::<EntityId, HpComponent>()
+                .get_by_id(&id)
+                .map(|hp| hp.hp)
+                .unwrap()
+        };
+        assert_eq!(hp(a), 20);
+        assert_eq!(hp(b), 0);
+        // 20 damage at range 2 falls off to 15, halved by resistance and reduced by armour
+        assert_eq!(hp(c), 20 - 6);
     }
 }
@@ -0,0 +1,166 @@
+use super::record_outcome;
+use crate::components::{CarryComponent, ConstructionSite, IntentOutcomes, Resource};
+use crate::indices::*;
+use crate::intents::*;
+use crate::profile;
+use crate::scripting_api::OperationResult;
+use crate::storage::views::{UnsafeView, UnwrapView, WorldLogger};
+use crate::structures::{insert_structure_components, InsertStructureMut};
+use crate::tables::Table;
+use slog::{debug, o, trace, warn};
+
+type Mut = (
+    UnsafeView<EntityId, ConstructionSite>,
+    UnsafeView<EntityId, CarryComponent>,
+    UnsafeView<EntityId, IntentOutcomes>,
+    InsertStructureMut,
+);
+type Const<'a> = (UnwrapView<'a, EmptyKey, Intents<BuildIntent>>, WorldLogger);
+
+/// Bots spend the energy they carry on the progress of construction sites.
+/// Completed sites are replaced by their structure, keeping the entity id.
+pub fn update(
+    (mut sites, mut carry_table, mut outcomes, structure_views): Mut,
+    (intents, WorldLogger(logger)): Const,
+) {
+    profile!("BuildSystem update");
+
+    for intent in intents.iter() {
+        let logger = logger.new(o!("entity"=>intent.bot.0));
+        trace!(logger, "Executing build intent {:?}", intent);
+
+        let carry = match carry_table.get_by_id_mut(&intent.bot) {
+            Some(x) => x,
+            None => {
+                warn!(logger, "Bot has no carry");
+                record_outcome(&mut outcomes, intent.bot, |o| {
+                    o.build_intent = Some(OperationResult::InvalidInput)
+                });
+                continue;
+            }
+        };
+        let site = match sites.get_by_id_mut(&intent.site) {
+            Some(x) => x,
+            None => {
+                // the site may have been completed by another bot in this tick
+                debug!(logger, "Construction site {:?} not found", intent.site);
+                record_outcome(&mut outcomes, intent.bot, |o| {
+                    o.build_intent = Some(OperationResult::InvalidTarget)
+                });
+                continue;
+            }
+        };
+        let energy = carry.get(Resource::Energy);
+        if energy == 0 {
+            record_outcome(&mut outcomes, intent.bot, |o| {
+                o.build_intent = Some(OperationResult::Empty)
+            });
+            continue;
+        }
+
+        let remaining = site.progress_max.saturating_sub(site.progress);
+        let amount = energy
+            .min(BUILD_POWER)
+            .min(remaining.min(BUILD_POWER as u32) as u16);
+        carry.take(Resource::Energy, amount);
+        site.progress += amount as u32;
+        record_outcome(&mut outcomes, intent.bot, |o| {
+            o.build_intent = Some(OperationResult::Ok)
+        });
+
+        if site.progress >= site.progress_max {
+            let kind = site.kind;
+            debug!(
+                logger,
+                "Construction site {:?} of {:?} completed", intent.site, kind
+            );
+            sites.delete(&intent.site);
+            insert_structure_components(intent.site, kind, structure_views);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Bot, OwnedEntity, PositionComponent, StructureKind, TowerComponent};
+    use crate::storage::views::{FromWorld, FromWorldMut};
+    use crate::structures::construction_cost;
+    use crate::utils::{setup_testing, test_logger};
+    use crate::world::init_inmemory_storage;
+
+    #[test]
+    fn completed_site_becomes_structure() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+
+        let bot_id = store.insert_entity();
+        let site_id = store.insert_entity();
+        let cost = construction_cost(StructureKind::Tower);
+
+        store.unsafe_view::<EntityId, Bot>().insert(bot_id);
+        store
+            .unsafe_view::<EntityId, CarryComponent>()
+            .insert_or_update(
+                bot_id,
+                CarryComponent {
+                    contents: [(Resource::Energy, 50)].iter().cloned().collect(),
+                    carry_max: 50,
+                },
+            );
+        store
+            .unsafe_view::<EntityId, ConstructionSite>()
+            .insert_or_update(
+                site_id,
+                ConstructionSite {
+                    kind: StructureKind::Tower,
+                    progress: cost - 5,
+                    progress_max: cost,
+                },
+            );
+        for id in [bot_id, site_id].iter() {
+            store
+                .unsafe_view::<EntityId, PositionComponent>()
+                .insert_or_update(*id, PositionComponent::default());
+            store
+                .unsafe_view::<EntityId, OwnedEntity>()
+                .insert_or_update(*id, OwnedEntity::default());
+        }
+
+        move_into_storage(
+            &mut *store,
+            vec![BotIntents {
+                build_intent: Some(BuildIntent {
+                    bot: bot_id,
+                    site: site_id,
+                }),
+                ..Default::default()
+            }],
+        );
+        update(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
+
+        assert_eq!(
+            store
+                .view::<EntityId, CarryComponent>()
+                .get_by_id(&bot_id)
+                .unwrap()
+                .get(Resource::Energy),
+            45
+        );
+        assert!(store
+            .view::<EntityId, ConstructionSite>()
+            .get_by_id(&site_id)
+            .is_none());
+        assert!(store
+            .view::<EntityId, TowerComponent>()
+            .get_by_id(&site_id)
+            .is_some());
+        assert_eq!(
+            store
+                .view::<EntityId, IntentOutcomes>()
+                .get_by_id(&bot_id)
+                .and_then(|o| o.build_intent),
+            Some(OperationResult::Ok)
+        );
+    }
+}
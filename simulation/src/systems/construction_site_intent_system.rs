@@ -0,0 +1,166 @@
+use crate::components::{
+    game_config::GameConfig, ConstructionSite, EntityComponent, OwnedEntity, PositionComponent,
+    RoadComponent, RoomControllerComponent, SiteComponent, SpawnComponent, Structure,
+    StructureKind, TerrainComponent,
+};
+use crate::indices::*;
+use crate::intents::*;
+use crate::profile;
+use crate::storage::views::{InsertEntityView, UnsafeView, UnwrapView, View, WorldLogger};
+use crate::structures::{check_site_placement, insert_construction_site, PlacementCheckInput};
+use slog::{debug, trace};
+
+type Mut = (
+    UnsafeView<EntityId, ConstructionSite>,
+    UnsafeView<EntityId, Structure>,
+    UnsafeView<EntityId, PositionComponent>,
+    UnsafeView<EntityId, OwnedEntity>,
+    UnsafeView<WorldPosition, EntityComponent>,
+    UnsafeView<WorldPosition, SiteComponent>,
+    InsertEntityView,
+);
+type Const<'a> = (
+    UnwrapView<'a, EmptyKey, Intents<ConstructionSiteIntent>>,
+    View<'a, WorldPosition, TerrainComponent>,
+    View<'a, WorldPosition, RoadComponent>,
+    View<'a, EntityId, SpawnComponent>,
+    View<'a, EntityId, StructureKind>,
+    View<'a, EntityId, RoomControllerComponent>,
+    View<'a, Room, OwnedEntity>,
+    UnwrapView<'a, ConfigKey, GameConfig>,
+    WorldLogger,
+);
+
+/// Place the construction sites requested by scripts.
+/// Placement is checked again, because intents of the same tick may conflict, or exceed the
+/// site limit of the user together.
+pub fn update(
+    (sites, structures, positions, owners, entities_by_pos, sites_by_pos, mut insert_entity): Mut,
+    (
+        intents,
+        terrain,
+        roads,
+        spawns,
+        kinds,
+        controllers,
+        room_owners,
+        config,
+        WorldLogger(logger),
+    ): Const,
+) {
+    profile!("ConstructionSiteSystem update");
+
+    for intent in intents.iter() {
+        trace!(logger, "Executing construction site intent {:?}", intent);
+
+        let check = check_site_placement(
+            intent.owner_id,
+            intent.kind,
+            intent.position,
+            PlacementCheckInput {
+                terrain,
+                entities_by_pos: View::from_table(&*entities_by_pos),
+                roads,
+                sites_by_pos: View::from_table(&*sites_by_pos),
+                structures: View::from_table(&*structures),
+                spawns,
+                sites: View::from_table(&*sites),
+                positions: View::from_table(&*positions),
+                owners: View::from_table(&*owners),
+                kinds,
+                controllers,
+                room_owners,
+                config,
+            },
+        );
+        if let Err(err) = check {
+            debug!(logger, "Construction site can not be placed: {}", err);
+            continue;
+        }
+
+        let entity_id = unsafe { insert_entity.insert_entity() };
+        insert_construction_site(
+            entity_id,
+            intent.owner_id,
+            intent.kind,
+            intent.position,
+            (
+                sites,
+                structures,
+                positions,
+                owners,
+                entities_by_pos,
+                sites_by_pos,
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Axial;
+    use crate::harness::{build_world, Fixture};
+    use crate::scripting_api::OperationResult;
+    use crate::storage::views::FromWorld;
+    use crate::structures::place_construction_site;
+    use crate::utils::{setup_testing, test_logger};
+
+    #[test]
+    fn sites_are_placed_near_the_bot() {
+        setup_testing();
+        let fixture: Fixture = serde_json::from_str(
+            r#"{
+                "roomRadius": 5,
+                "rooms": [ { "room": { "q": 0, "r": 0 }, "defaultTerrain": "plain" } ],
+                "entities": [
+                    {
+                        "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 5, "r": 5 } },
+                        "owner": "00000000-0000-0000-0000-000000000000",
+                        "bot": true
+                    }
+                ]
+            }"#,
+        )
+        .expect("Failed to parse fixture");
+        let mut world = build_world(test_logger(), &fixture).expect("Failed to build the world");
+        let logger = test_logger();
+        let owner_id = UserId::default();
+        let intent = |q| ConstructionSiteIntent {
+            bot: EntityId(0),
+            owner_id,
+            kind: StructureKind::Road,
+            position: WorldPosition {
+                room: Axial::new(0, 0),
+                pos: Axial::new(q, 5),
+            },
+        };
+
+        let check = |world: &crate::world::World, intent: &ConstructionSiteIntent| {
+            check_construction_site_intent(&logger, intent, FromWorld::new(world))
+        };
+        assert_eq!(check(&*world, &intent(7)), OperationResult::Ok);
+        assert_eq!(check(&*world, &intent(9)), OperationResult::NotInRange);
+        let other = ConstructionSiteIntent {
+            owner_id: UserId(uuid::Uuid::from_u128(1)),
+            ..intent(7)
+        };
+        assert_eq!(check(&*world, &other), OperationResult::NotOwner);
+
+        world
+            .config
+            .game_config
+            .value
+            .as_mut()
+            .unwrap()
+            .max_construction_sites = 1;
+        place_construction_site(
+            &mut *world,
+            owner_id,
+            StructureKind::Road,
+            intent(6).position,
+        )
+        .expect("Road site");
+        assert_eq!(check(&*world, &intent(7)), OperationResult::Full);
+    }
+}
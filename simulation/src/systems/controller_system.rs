@@ -0,0 +1,229 @@
+//! Levels up room controllers, raises user levels by the total progress of their controllers and
+//! releases the rooms that lost their controller.
+//!
+use crate::components::{
+    OwnedEntity, PositionComponent, RoomControllerComponent, Rooms, UserProperties,
+};
+use crate::indices::{EntityId, Room, UserId};
+use crate::profile;
+use crate::storage::views::{UnsafeView, View, WorldLogger};
+use crate::structures::{
+    controller_progress_max, controller_total_progress, user_level, CONTROLLER_MAX_LEVEL,
+};
+use crate::tables::Table;
+use slog::{debug, info};
+use std::collections::{BTreeMap, BTreeSet};
+
+type Mut = (
+    UnsafeView<EntityId, RoomControllerComponent>,
+    UnsafeView<Room, OwnedEntity>,
+    UnsafeView<UserId, Rooms>,
+    UnsafeView<UserId, UserProperties>,
+);
+type Const<'a> = (
+    View<'a, EntityId, PositionComponent>,
+    View<'a, EntityId, OwnedEntity>,
+    WorldLogger,
+);
+
+pub fn update(
+    (mut controllers, mut room_owners, mut user_rooms, mut user_props): Mut,
+    (positions, owners, WorldLogger(logger)): Const,
+) {
+    profile!("ControllerSystem update");
+
+    let mut claimed = BTreeSet::new();
+    let mut progress_by_user = BTreeMap::new();
+    for (id, controller) in controllers.iter_mut() {
+        if controller.progress >= controller.progress_max && controller.level < CONTROLLER_MAX_LEVEL
+        {
+            controller.level += 1;
+            controller.progress -= controller.progress_max;
+            controller.progress_max = controller_progress_max(controller.level);
+            debug!(
+                logger,
+                "Controller {:?} reached level {}", id, controller.level
+            );
+        }
+        if let (Some(PositionComponent(pos)), Some(OwnedEntity { owner_id })) =
+            (positions.get_by_id(&id), owners.get_by_id(&id))
+        {
+            claimed.insert((Room(pos.room), *owner_id));
+            *progress_by_user.entry(*owner_id).or_insert(0) +=
+                controller_total_progress(controller);
+        }
+    }
+
+    for (user_id, progress) in progress_by_user {
+        if let Some(props) = user_props.get_by_id_mut(&user_id) {
+            props.level = props.level.max(user_level(progress));
+        }
+    }
+
+    let released: Vec<(Room, UserId)> = room_owners
+        .iter()
+        .filter(|(room, OwnedEntity { owner_id })| !claimed.contains(&(*room, *owner_id)))
+        .map(|(room, OwnedEntity { owner_id })| (room, *owner_id))
+        .collect();
+    for (room, owner_id) in released {
+        info!(logger, "Room {:?} lost its controller, releasing it", room);
+        room_owners.delete(&room);
+        if let Some(Rooms(rooms)) = user_rooms.get_by_id_mut(&owner_id) {
+            rooms.retain(|r| *r != room);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{CarryComponent, Resource, StructureKind};
+    use crate::geometry::Axial;
+    use crate::harness::{build_world, Fixture};
+    use crate::indices::WorldPosition;
+    use crate::intents::{move_into_storage, BotIntents, DropoffIntent};
+    use crate::storage::views::{FromWorld, FromWorldMut};
+    use crate::storage::DeferredDeleteById;
+    use crate::structures::{
+        place_controller, place_structure, PlaceStructureError, USER_LEVEL_PROGRESS,
+    };
+    use crate::systems::dropoff_intent_system;
+    use crate::utils::{setup_testing, test_logger};
+    use crate::world::World;
+
+    fn world(owner_id: UserId) -> std::pin::Pin<Box<World>> {
+        let fixture: Fixture = serde_json::from_str(
+            r#"{
+                "roomRadius": 5,
+                "rooms": [ { "room": { "q": 0, "r": 0 }, "defaultTerrain": "plain" } ]
+            }"#,
+        )
+        .expect("Failed to parse fixture");
+        let mut world = build_world(test_logger(), &fixture).expect("Failed to build the world");
+        let room = Room(Axial::new(0, 0));
+        world
+            .unsafe_view::<Room, OwnedEntity>()
+            .insert(room, OwnedEntity { owner_id })
+            .unwrap();
+        world
+            .unsafe_view::<UserId, Rooms>()
+            .insert_or_update(owner_id, Rooms(vec![room]));
+        world
+            .unsafe_view::<UserId, UserProperties>()
+            .insert_or_update(owner_id, UserProperties::default());
+        world
+    }
+
+    #[test]
+    fn controller_limits_structures() {
+        setup_testing();
+        let owner_id = UserId::default();
+        let mut world = world(owner_id);
+        let room = Room(Axial::new(0, 0));
+        let pos = |q| WorldPosition {
+            room: room.0,
+            pos: Axial::new(q, 2),
+        };
+
+        place_controller(&mut *world, owner_id, room).expect("Controller");
+        assert!(matches!(
+            place_controller(&mut *world, owner_id, room),
+            Err(PlaceStructureError::RoomHasController(_))
+        ));
+        // level 1 controllers do not allow towers
+        assert!(matches!(
+            place_structure(&mut *world, owner_id, StructureKind::Tower, pos(3)),
+            Err(PlaceStructureError::StructureLimit { limit: 0, .. })
+        ));
+        place_structure(&mut *world, owner_id, StructureKind::Spawn, pos(4)).expect("Spawn");
+    }
+
+    #[test]
+    fn dropoff_upgrades_controller_and_user() {
+        setup_testing();
+        let owner_id = UserId::default();
+        let mut world = world(owner_id);
+        let room = Room(Axial::new(0, 0));
+        let controller_id = place_controller(&mut *world, owner_id, room).expect("Controller");
+        // one delivery short of level 4
+        world
+            .unsafe_view::<EntityId, RoomControllerComponent>()
+            .insert_or_update(
+                controller_id,
+                RoomControllerComponent {
+                    level: 3,
+                    progress: controller_progress_max(3) - 10,
+                    progress_max: controller_progress_max(3),
+                },
+            );
+
+        let bot_id = world.insert_entity();
+        let mut carry = CarryComponent::new(50);
+        carry.add(Resource::Energy, 50);
+        world
+            .unsafe_view::<EntityId, CarryComponent>()
+            .insert_or_update(bot_id, carry);
+        move_into_storage(
+            &mut *world,
+            vec![BotIntents {
+                dropoff_intent: Some(DropoffIntent {
+                    bot: bot_id,
+                    structure: controller_id,
+                    amount: 50,
+                    ty: Resource::Energy,
+                }),
+                ..Default::default()
+            }],
+        );
+        dropoff_intent_system::update(FromWorldMut::new(&mut *world), FromWorld::new(&*world));
+        update(FromWorldMut::new(&mut *world), FromWorld::new(&*world));
+
+        let controller = *world
+            .view::<EntityId, RoomControllerComponent>()
+            .get_by_id(&controller_id)
+            .unwrap();
+        assert_eq!(controller.level, 4);
+        assert_eq!(controller.progress, 0);
+        assert_eq!(
+            world
+                .view::<EntityId, CarryComponent>()
+                .get_by_id(&bot_id)
+                .unwrap()
+                .get(Resource::Energy),
+            40
+        );
+        // 7000 energy delivered in total
+        assert!(controller_total_progress(&controller) >= USER_LEVEL_PROGRESS);
+        assert_eq!(
+            world
+                .view::<UserId, UserProperties>()
+                .get_by_id(&owner_id)
+                .unwrap()
+                .level,
+            2
+        );
+    }
+
+    #[test]
+    fn losing_the_controller_releases_the_room() {
+        setup_testing();
+        let owner_id = UserId::default();
+        let mut world = world(owner_id);
+        let room = Room(Axial::new(0, 0));
+
+        let controller_id = place_controller(&mut *world, owner_id, room).expect("Controller");
+        update(FromWorldMut::new(&mut *world), FromWorld::new(&*world));
+        assert!(world.view::<Room, OwnedEntity>().contains_key(&room));
+
+        world.deferred_delete(controller_id);
+        world.post_process();
+        update(FromWorldMut::new(&mut *world), FromWorld::new(&*world));
+        assert!(!world.view::<Room, OwnedEntity>().contains_key(&room));
+        assert!(world
+            .view::<UserId, Rooms>()
+            .get_by_id(&owner_id)
+            .unwrap()
+            .0
+            .is_empty());
+    }
+}
@@ -1,43 +1,125 @@
+use super::pile_system::{drop_resources, DropResourcesMut};
 use crate::indices::*;
 use crate::profile;
-use crate::storage::views::{DeferredDeleteEntityView, View, WorldLogger};
+use crate::storage::views::{DeferredDeleteEntityView, UnsafeView, View, WorldLogger};
 use crate::{
-    components::HpComponent,
+    components::{
+        ArmourComponent, Bot, DeathCause, HealComponent, HpComponent, LastDamageComponent,
+        MeleeAttackComponent, OwnedEntity, PositionComponent, RangedAttackComponent,
+        RemainsComponent, RemainsSummary, StructureKind,
+    },
     intents::{DeleteEntityIntent, Intents},
     prelude::UnwrapView,
 };
 use slog::{debug, trace};
+use std::collections::BTreeMap;
 
+/// Number of ticks the remains of an entity stay in the world
+pub const REMAINS_DURATION: u16 = 50;
+
+type Mut = (
+    DeferredDeleteEntityView,
+    DropResourcesMut,
+    UnsafeView<EntityId, RemainsComponent>,
+);
+type Const<'a> = (
+    View<'a, EntityId, HpComponent>,
+    View<'a, EntityId, Bot>,
+    UnwrapView<'a, EmptyKey, Intents<DeleteEntityIntent>>,
+    SummaryInput<'a>,
+    WorldLogger,
+);
+type SummaryInput<'a> = (
+    View<'a, EntityId, StructureKind>,
+    View<'a, EntityId, OwnedEntity>,
+    View<'a, EntityId, LastDamageComponent>,
+    View<'a, EntityId, MeleeAttackComponent>,
+    View<'a, EntityId, RangedAttackComponent>,
+    View<'a, EntityId, HealComponent>,
+    View<'a, EntityId, ArmourComponent>,
+);
+
+/// Deletes entities with 0 hp and the ones scripts asked to delete.
+/// Bots dying while carrying resources leave a resource pile.
+/// Dead bots and structures leave remains, recording what they were and why they died.
 pub fn update(
-    mut delete: DeferredDeleteEntityView,
-    (hps, delete_intents, WorldLogger(logger)): (
-        View<EntityId, HpComponent>,
-        UnwrapView<EmptyKey, Intents<DeleteEntityIntent>>,
-        WorldLogger,
-    ),
+    (mut delete, drop_views, mut remains): Mut,
+    (hps, bots, delete_intents, summary_input, WorldLogger(logger)): Const,
 ) {
     profile!("DeathSystem update");
     debug!(logger, "update death system called");
 
-    hps.iter().for_each(|(id, hp)| {
-        if hp.hp == 0 {
-            trace!(logger, "Entity {:?} has died, deleting", id);
-            unsafe {
-                delete.delete_entity(id);
+    let (kinds, owners, last_damage, ..) = summary_input;
+    let DropResourcesMut {
+        carry_table,
+        mut positions,
+        mut insert_entity,
+        ..
+    } = drop_views;
+
+    let mut dead = BTreeMap::new();
+    for (id, _) in hps.iter().filter(|(_, hp)| hp.hp == 0) {
+        let cause = last_damage
+            .get_by_id(&id)
+            .map(|LastDamageComponent(cause)| *cause)
+            .unwrap_or_default();
+        dead.insert(id, cause);
+    }
+    for DeleteEntityIntent { id } in delete_intents.0.iter() {
+        dead.entry(*id).or_insert(DeathCause::Deleted);
+    }
+
+    for (id, cause) in dead {
+        trace!(logger, "Entity {:?} has died of {:?}, deleting", id, cause);
+        if let Some(pos) = positions.get_by_id(&id).map(|PositionComponent(pos)| *pos) {
+            if bots.contains_id(&id) {
+                let carry = carry_table.get_by_id(&id).filter(|c| c.total() > 0);
+                if let Some(carry) = carry {
+                    let contents = carry.contents.clone();
+                    trace!(logger, "Dropping {:?} at {:?}", contents, pos);
+                    drop_resources(pos, contents, drop_views);
+                }
+            }
+            if bots.contains_id(&id) || kinds.contains(&id) {
+                let remains_id = unsafe { insert_entity.insert_entity() };
+                positions.insert_or_update(remains_id, PositionComponent(pos));
+                remains.insert_or_update(
+                    remains_id,
+                    RemainsComponent {
+                        entity_id: id,
+                        owner_id: owners.get_by_id(&id).map(|o| o.owner_id),
+                        cause,
+                        summary: summarize(id, bots, hps, summary_input),
+                        time_remaining: REMAINS_DURATION,
+                    },
+                );
             }
         }
-    });
-
-    delete_intents
-        .0
-        .iter()
-        .for_each(|DeleteEntityIntent { id }| unsafe {
-            delete.delete_entity(*id);
-        });
+        unsafe {
+            delete.delete_entity(id);
+        }
+    }
 
     debug!(logger, "update death system done");
 }
 
+fn summarize(
+    id: EntityId,
+    bots: View<EntityId, Bot>,
+    hps: View<EntityId, HpComponent>,
+    (kinds, _, _, melee, ranged, heal, armour): SummaryInput,
+) -> RemainsSummary {
+    RemainsSummary {
+        bot: bots.contains_id(&id),
+        structure: kinds.get_by_id(&id).copied(),
+        hp_max: hps.get_by_id(&id).map(|hp| hp.hp_max).unwrap_or(0),
+        melee: melee.contains(&id),
+        ranged: ranged.contains(&id),
+        heal: heal.contains(&id),
+        armour: armour.get_by_id(&id).map(|a| a.armour).unwrap_or(0),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -136,4 +218,82 @@ mod tests {
 
         assert_eq!(entities, vec![entity_2]);
     }
+
+    #[test]
+    fn dead_bot_drops_its_carry() {
+        use crate::components::{CarryComponent, Resource, ResourcePileComponent};
+
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+
+        let bot_id = store.insert_entity();
+        let mut carry = CarryComponent::new(50);
+        carry.add(Resource::Iron, 25);
+        store.unsafe_view::<EntityId, Bot>().insert(bot_id);
+        store
+            .unsafe_view::<EntityId, CarryComponent>()
+            .insert_or_update(bot_id, carry);
+        store
+            .unsafe_view::<EntityId, PositionComponent>()
+            .insert_or_update(bot_id, PositionComponent::default());
+        store
+            .unsafe_view::<EntityId, HpComponent>()
+            .insert_or_update(bot_id, HpComponent { hp: 0, hp_max: 10 });
+
+        update(FromWorldMut::new(&mut *store), FromWorld::new(&mut *store));
+        store.post_process();
+
+        let piles: Vec<_> = store
+            .view::<EntityId, ResourcePileComponent>()
+            .iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(piles.len(), 1);
+        assert_eq!(
+            store
+                .view::<EntityId, CarryComponent>()
+                .get_by_id(&piles[0])
+                .unwrap()
+                .get(Resource::Iron),
+            25
+        );
+        assert!(!store.view::<EntityId, Bot>().contains_id(&bot_id));
+    }
+
+    #[test]
+    fn dead_bot_leaves_remains() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+
+        let bot_id = store.insert_entity();
+        let owner_id = UserId(uuid::Uuid::new_v4());
+        store.unsafe_view::<EntityId, Bot>().insert(bot_id);
+        store
+            .unsafe_view::<EntityId, OwnedEntity>()
+            .insert_or_update(bot_id, OwnedEntity { owner_id });
+        store
+            .unsafe_view::<EntityId, PositionComponent>()
+            .insert_or_update(bot_id, PositionComponent::default());
+        store
+            .unsafe_view::<EntityId, HpComponent>()
+            .insert_or_update(bot_id, HpComponent { hp: 0, hp_max: 10 });
+        store
+            .unsafe_view::<EntityId, LastDamageComponent>()
+            .insert_or_update(bot_id, LastDamageComponent(DeathCause::Melee));
+
+        update(FromWorldMut::new(&mut *store), FromWorld::new(&mut *store));
+        store.post_process();
+
+        let remains: Vec<_> = store
+            .view::<EntityId, RemainsComponent>()
+            .iter()
+            .map(|(_, remains)| remains.clone())
+            .collect();
+        assert_eq!(remains.len(), 1);
+        assert_eq!(remains[0].entity_id, bot_id);
+        assert_eq!(remains[0].owner_id, Some(owner_id));
+        assert_eq!(remains[0].cause, DeathCause::Melee);
+        assert!(remains[0].summary.bot);
+        assert_eq!(remains[0].summary.hp_max, 10);
+    }
 }
@@ -1,4 +1,4 @@
-use crate::components::{DecayComponent, HpComponent};
+use crate::components::{DeathCause, DecayComponent, HpComponent, LastDamageComponent};
 use crate::indices::EntityId;
 use crate::join;
 use crate::profile;
@@ -7,9 +7,10 @@ use crate::tables::JoinIterator;
 use slog::debug;
 
 pub fn update(
-    (mut hps, mut decays): (
+    (mut hps, mut decays, mut last_damage): (
         UnsafeView<EntityId, HpComponent>,
         UnsafeView<EntityId, DecayComponent>,
+        UnsafeView<EntityId, LastDamageComponent>,
     ),
     WorldLogger(logger): WorldLogger,
 ) {
@@ -20,7 +21,7 @@ pub fn update(
     let hps = hps.iter_mut();
     join!([decays, hps]).for_each(
         |(
-            _id,
+            id,
             (
                 DecayComponent {
                     hp_amount,
@@ -32,6 +33,7 @@ pub fn update(
         )| match time_remaining {
             0 => {
                 *hp -= *hp.min(hp_amount);
+                last_damage.insert_or_update(id, LastDamageComponent(DeathCause::Decay));
                 *time_remaining = *interval;
             }
             _ => {
@@ -1,46 +1,166 @@
-use crate::components::{CarryComponent, EnergyComponent};
+use super::record_outcome;
+use crate::components::{
+    CarryComponent, EnergyComponent, IntentOutcomes, Resource, RoomControllerComponent, Structure,
+};
 use crate::indices::*;
 use crate::intents::*;
 use crate::profile;
-use crate::storage::views::{UnsafeView, UnwrapView, WorldLogger};
+use crate::scripting_api::OperationResult;
+use crate::storage::views::{UnsafeView, UnwrapView, View, WorldLogger};
 use slog::{o, trace, warn};
 
 type Mut = (
     UnsafeView<EntityId, EnergyComponent>,
     UnsafeView<EntityId, CarryComponent>,
+    UnsafeView<EntityId, IntentOutcomes>,
+    UnsafeView<EntityId, RoomControllerComponent>,
 );
 type Const<'a> = (
     UnwrapView<'a, EmptyKey, Intents<DropoffIntent>>,
+    View<'a, EntityId, Structure>,
     WorldLogger,
 );
 
-pub fn update((mut energy_table, mut carry_table): Mut, (intents, WorldLogger(logger)): Const) {
+pub fn update(
+    (mut energy_table, mut carry_table, mut outcomes, mut controllers): Mut,
+    (intents, structures, WorldLogger(logger)): Const,
+) {
     profile!("DropoffSystem update");
 
     for intent in intents.iter() {
         let logger = logger.new(o!("entity"=>intent.bot.0));
         trace!(logger, "Executing dropoff intent {:?}", intent);
         // dropoff amount = min(bot carry , amount , structure capacity)
-        let carry_component = match carry_table.get_by_id_mut(&intent.bot) {
-            Some(x) => x,
+        let carried = match carry_table.get_by_id(&intent.bot) {
+            Some(x) => x.get(intent.ty),
             None => {
                 warn!(logger, "Bot has no carry");
+                record_outcome(&mut outcomes, intent.bot, |o| {
+                    o.dropoff_intent = Some(OperationResult::InvalidInput)
+                });
                 continue;
             }
         };
-        let store_component = match energy_table.get_by_id_mut(&intent.structure) {
-            Some(x) => x,
-            None => {
-                warn!(logger, "Structure has no energy");
-                continue;
+        let amount = intent.amount.min(carried);
+
+        // energy delivered to a room controller upgrades it
+        // otherwise energy goes into the energy store of the structure, if it has one
+        let (controller, energy_store) = if intent.ty == Resource::Energy {
+            (
+                controllers.get_by_id_mut(&intent.structure),
+                energy_table.get_by_id_mut(&intent.structure),
+            )
+        } else {
+            (None, None)
+        };
+        let dropoff = match (controller, energy_store) {
+            (Some(controller), _) => {
+                let dropoff =
+                    (amount as u32).min(controller.progress_max - controller.progress) as u16;
+                controller.progress += dropoff as u32;
+                dropoff
+            }
+            (None, Some(store_component)) => {
+                let dropoff = amount.min(store_component.energy_max - store_component.energy);
+                store_component.energy += dropoff;
+                dropoff
+            }
+            (None, None) => match carry_table.get_by_id_mut(&intent.structure) {
+                Some(store_component) if structures.contains_id(&intent.structure) => {
+                    store_component.add(intent.ty, amount)
+                }
+                _ => {
+                    warn!(logger, "Structure can not store {:?}", intent.ty);
+                    record_outcome(&mut outcomes, intent.bot, |o| {
+                        o.dropoff_intent = Some(OperationResult::InvalidTarget)
+                    });
+                    continue;
+                }
+            },
+        };
+        let carry_component = carry_table
+            .get_by_id_mut(&intent.bot)
+            .expect("bot carry to exist");
+        carry_component.take(intent.ty, dropoff);
+
+        let res = if dropoff == 0 && intent.amount > 0 {
+            if carried == 0 {
+                OperationResult::Empty
+            } else {
+                OperationResult::Full
             }
+        } else {
+            OperationResult::Ok
         };
-        let dropoff = intent
-            .amount
-            .min(carry_component.carry)
-            .min(store_component.energy_max - store_component.energy);
+        record_outcome(&mut outcomes, intent.bot, |o| o.dropoff_intent = Some(res));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Bot, OwnedEntity, PositionComponent};
+    use crate::storage::views::{FromWorld, FromWorldMut};
+    use crate::utils::{setup_testing, test_logger};
+    use crate::world::init_inmemory_storage;
+
+    #[test]
+    fn dropoff_into_storage_keeps_resource_type() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+
+        let bot_id = store.insert_entity();
+        let storage_id = store.insert_entity();
+
+        store.unsafe_view::<EntityId, Bot>().insert(bot_id);
+        store
+            .unsafe_view::<EntityId, Structure>()
+            .insert(storage_id);
+        let mut carry = CarryComponent::new(50);
+        carry.add(Resource::Energy, 10);
+        carry.add(Resource::Iron, 20);
+        store
+            .unsafe_view::<EntityId, CarryComponent>()
+            .insert_or_update(bot_id, carry);
+        store
+            .unsafe_view::<EntityId, CarryComponent>()
+            .insert_or_update(storage_id, CarryComponent::new(100));
+        for id in [bot_id, storage_id].iter() {
+            store
+                .unsafe_view::<EntityId, PositionComponent>()
+                .insert_or_update(*id, PositionComponent::default());
+            store
+                .unsafe_view::<EntityId, OwnedEntity>()
+                .insert_or_update(*id, OwnedEntity::default());
+        }
+
+        move_into_storage(
+            &mut *store,
+            vec![BotIntents {
+                dropoff_intent: Some(DropoffIntent {
+                    bot: bot_id,
+                    structure: storage_id,
+                    amount: 15,
+                    ty: Resource::Iron,
+                }),
+                ..Default::default()
+            }],
+        );
+        update(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
 
-        store_component.energy += dropoff;
-        carry_component.carry -= dropoff;
+        let carry_table = store.view::<EntityId, CarryComponent>();
+        let bot_carry = carry_table.get_by_id(&bot_id).unwrap();
+        assert_eq!(bot_carry.get(Resource::Iron), 5);
+        assert_eq!(bot_carry.get(Resource::Energy), 10);
+        let stored = carry_table.get_by_id(&storage_id).unwrap();
+        assert_eq!(stored.get(Resource::Iron), 15);
+        assert_eq!(stored.total(), 15);
+        assert_eq!(
+            store
+                .view::<EntityId, IntentOutcomes>()
+                .get_by_id(&bot_id)
+                .and_then(|o| o.dropoff_intent),
+            Some(OperationResult::Ok)
+        );
     }
 }
@@ -0,0 +1,21 @@
+//! Bots recover from their fatigue every tick.
+//!
+use crate::components::FatigueComponent;
+use crate::indices::EntityId;
+use crate::profile;
+use crate::storage::views::{UnsafeView, WorldLogger};
+use slog::debug;
+
+pub fn update(
+    mut fatigue: UnsafeView<EntityId, FatigueComponent>,
+    WorldLogger(logger): WorldLogger,
+) {
+    profile!("FatigueSystem update");
+    debug!(logger, "update fatigue system called");
+
+    for (_, f) in fatigue.iter_mut() {
+        f.fatigue = f.fatigue.saturating_sub(f.recovery);
+    }
+
+    debug!(logger, "update fatigue system done");
+}
@@ -0,0 +1,308 @@
+//! Heals restore the hp of bots, repairs restore the hp of structures by spending carried energy.
+//!
+//! Runs after the attack system, so a bot struck down may still be saved in the same tick.
+//!
+use super::record_outcome;
+use crate::components::{CarryComponent, HealComponent, HpComponent, IntentOutcomes, Resource};
+use crate::indices::*;
+use crate::intents::*;
+use crate::profile;
+use crate::scripting_api::OperationResult;
+use crate::storage::views::{UnsafeView, UnwrapView, View, WorldLogger};
+use slog::{o, trace, warn};
+
+type Mut = (
+    UnsafeView<EntityId, HpComponent>,
+    UnsafeView<EntityId, CarryComponent>,
+    UnsafeView<EntityId, IntentOutcomes>,
+);
+type Const<'a> = (
+    UnwrapView<'a, EmptyKey, Intents<HealIntent>>,
+    UnwrapView<'a, EmptyKey, Intents<RepairIntent>>,
+    View<'a, EntityId, HealComponent>,
+    WorldLogger,
+);
+
+pub fn update(
+    (mut hps, mut carry_table, mut outcomes): Mut,
+    (heal_intents, repair_intents, heals, WorldLogger(logger)): Const,
+) {
+    profile!("HealSystem update");
+
+    for intent in heal_intents.iter() {
+        let logger = logger.new(o!("entity"=>intent.healer.0));
+        trace!(logger, "Executing heal intent {:?}", intent);
+
+        let res = match (
+            heals.get_by_id(&intent.healer),
+            hps.get_by_id_mut(&intent.target),
+        ) {
+            (Some(heal), Some(hp)) => {
+                hp.hp = hp.hp.saturating_add(heal.strength).min(hp.hp_max);
+                OperationResult::Ok
+            }
+            (None, _) => {
+                warn!(logger, "Healer has no heal component");
+                OperationResult::InvalidInput
+            }
+            (_, None) => {
+                warn!(logger, "Target has no hp");
+                OperationResult::InvalidTarget
+            }
+        };
+        record_outcome(&mut outcomes, intent.healer, |o| o.heal_intent = Some(res));
+    }
+
+    for intent in repair_intents.iter() {
+        let logger = logger.new(o!("entity"=>intent.bot.0));
+        trace!(logger, "Executing repair intent {:?}", intent);
+
+        let res = match (
+            carry_table.get_by_id_mut(&intent.bot),
+            hps.get_by_id_mut(&intent.structure),
+        ) {
+            (Some(carry), Some(hp)) => {
+                let missing = hp.hp_max.saturating_sub(hp.hp).min(REPAIR_POWER);
+                // round up, so repairing a single hp still costs energy
+                let cost = (missing + REPAIR_HP_PER_ENERGY - 1) / REPAIR_HP_PER_ENERGY;
+                let spent = carry.take(Resource::Energy, cost);
+                if spent == 0 {
+                    OperationResult::Empty
+                } else {
+                    hp.hp = hp
+                        .hp
+                        .saturating_add(spent * REPAIR_HP_PER_ENERGY)
+                        .min(hp.hp_max);
+                    OperationResult::Ok
+                }
+            }
+            (None, _) => {
+                warn!(logger, "Bot has no carry");
+                OperationResult::InvalidInput
+            }
+            (_, None) => {
+                warn!(logger, "Structure has no hp");
+                OperationResult::InvalidTarget
+            }
+        };
+        record_outcome(&mut outcomes, intent.bot, |o| o.repair_intent = Some(res));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::{build_world, Fixture};
+    use crate::storage::views::{FromWorld, FromWorldMut};
+    use crate::utils::{setup_testing, test_logger};
+    use crate::world::init_inmemory_storage;
+    use uuid::Uuid;
+
+    /// Entities in order:
+    /// 0. healer bot of the default user, carrying energy
+    /// 1. bot at full hp
+    /// 2. damaged bot
+    /// 3. damaged bot out of heal range
+    /// 4. damaged structure
+    /// 5. damaged structure of another user
+    /// 6. structure at full hp
+    /// 7. damaged structure out of repair range
+    fn heal_fixture() -> Fixture {
+        serde_json::from_str(
+            r#"{
+                "rooms": [ { "room": { "q": 0, "r": 0 }, "defaultTerrain": "plain" } ],
+                "entities": [
+                    {
+                        "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 8, "r": 8 } },
+                        "owner": "00000000-0000-0000-0000-000000000000",
+                        "bot": true,
+                        "heal": { "strength": 30 },
+                        "carry": { "contents": { "energy": 50 }, "carryMax": 50 }
+                    },
+                    {
+                        "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 8, "r": 9 } },
+                        "owner": "00000000-0000-0000-0000-000000000000",
+                        "bot": true,
+                        "hp": { "hp": 100, "hpMax": 100 }
+                    },
+                    {
+                        "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 9, "r": 8 } },
+                        "owner": "00000000-0000-0000-0000-000000000000",
+                        "bot": true,
+                        "hp": { "hp": 80, "hpMax": 100 }
+                    },
+                    {
+                        "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 8, "r": 12 } },
+                        "owner": "00000000-0000-0000-0000-000000000000",
+                        "bot": true,
+                        "hp": { "hp": 50, "hpMax": 100 }
+                    },
+                    {
+                        "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 8, "r": 10 } },
+                        "owner": "00000000-0000-0000-0000-000000000000",
+                        "structure": true,
+                        "hp": { "hp": 50, "hpMax": 100 }
+                    },
+                    {
+                        "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 7, "r": 8 } },
+                        "owner": "00000000-0000-0000-0000-000000000001",
+                        "structure": true,
+                        "hp": { "hp": 50, "hpMax": 100 }
+                    },
+                    {
+                        "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 9, "r": 7 } },
+                        "owner": "00000000-0000-0000-0000-000000000000",
+                        "structure": true,
+                        "hp": { "hp": 100, "hpMax": 100 }
+                    },
+                    {
+                        "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 8, "r": 13 } },
+                        "owner": "00000000-0000-0000-0000-000000000000",
+                        "structure": true,
+                        "hp": { "hp": 50, "hpMax": 100 }
+                    }
+                ]
+            }"#,
+        )
+        .expect("Failed to parse fixture")
+    }
+
+    #[test]
+    fn heal_intents_are_checked() {
+        setup_testing();
+        let world = build_world(test_logger(), &heal_fixture()).expect("Failed to build the world");
+        let logger = test_logger();
+        let check = |user_id: UserId, target: u32| {
+            let intent = HealIntent {
+                healer: EntityId(0),
+                target: EntityId(target),
+            };
+            check_heal_intent(&logger, &intent, user_id, FromWorld::new(&*world))
+        };
+        let user_id = UserId::default();
+
+        assert_eq!(check(user_id, 2), OperationResult::Ok);
+        assert_eq!(check(user_id, 1), OperationResult::Full);
+        assert_eq!(check(user_id, 3), OperationResult::NotInRange);
+        // structures are repaired, not healed
+        assert_eq!(check(user_id, 4), OperationResult::InvalidTarget);
+        assert_eq!(
+            check(UserId(Uuid::from_u128(1)), 2),
+            OperationResult::NotOwner
+        );
+    }
+
+    #[test]
+    fn repair_intents_are_checked() {
+        setup_testing();
+        let world = build_world(test_logger(), &heal_fixture()).expect("Failed to build the world");
+        let logger = test_logger();
+        let check = |structure: u32| {
+            let intent = RepairIntent {
+                bot: EntityId(0),
+                structure: EntityId(structure),
+            };
+            check_repair_intent(&logger, &intent, UserId::default(), FromWorld::new(&*world))
+        };
+
+        assert_eq!(check(4), OperationResult::Ok);
+        assert_eq!(check(5), OperationResult::NotOwner);
+        assert_eq!(check(6), OperationResult::Full);
+        assert_eq!(check(7), OperationResult::NotInRange);
+        // bots are healed, not repaired
+        assert_eq!(check(2), OperationResult::InvalidTarget);
+    }
+
+    #[test]
+    fn heal_does_not_exceed_max_hp() {
+        setup_testing();
+        let mut world =
+            build_world(test_logger(), &heal_fixture()).expect("Failed to build the world");
+
+        move_into_storage(
+            &mut *world,
+            vec![BotIntents {
+                heal_intent: Some(HealIntent {
+                    healer: EntityId(0),
+                    target: EntityId(2),
+                }),
+                ..Default::default()
+            }],
+        );
+        update(FromWorldMut::new(&mut *world), FromWorld::new(&*world));
+
+        let hp = world
+            .view::<EntityId, HpComponent>()
+            .get_by_id(&EntityId(2))
+            .copied()
+            .unwrap();
+        assert_eq!(hp.hp, hp.hp_max);
+        assert_eq!(
+            world
+                .view::<EntityId, IntentOutcomes>()
+                .get_by_id(&EntityId(0))
+                .and_then(|o| o.heal_intent),
+            Some(OperationResult::Ok)
+        );
+    }
+
+    #[test]
+    fn repair_spends_energy() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+
+        let bot_id = store.insert_entity();
+        let wall_id = store.insert_entity();
+
+        let mut carry = CarryComponent::new(50);
+        carry.add(Resource::Energy, 50);
+        store
+            .unsafe_view::<EntityId, CarryComponent>()
+            .insert_or_update(bot_id, carry);
+        store
+            .unsafe_view::<EntityId, HpComponent>()
+            .insert_or_update(
+                wall_id,
+                HpComponent {
+                    hp: 80,
+                    hp_max: 100,
+                },
+            );
+
+        move_into_storage(
+            &mut *store,
+            vec![BotIntents {
+                repair_intent: Some(RepairIntent {
+                    bot: bot_id,
+                    structure: wall_id,
+                }),
+                ..Default::default()
+            }],
+        );
+        update(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
+
+        assert_eq!(
+            store
+                .view::<EntityId, HpComponent>()
+                .get_by_id(&wall_id)
+                .unwrap()
+                .hp,
+            100
+        );
+        assert_eq!(
+            store
+                .view::<EntityId, CarryComponent>()
+                .get_by_id(&bot_id)
+                .unwrap()
+                .get(Resource::Energy),
+            50 - 20 / REPAIR_HP_PER_ENERGY
+        );
+        assert_eq!(
+            store
+                .view::<EntityId, IntentOutcomes>()
+                .get_by_id(&bot_id)
+                .and_then(|o| o.repair_intent),
+            Some(OperationResult::Ok)
+        );
+    }
+}
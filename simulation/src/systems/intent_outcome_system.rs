@@ -0,0 +1,11 @@
+use crate::components::IntentOutcomes;
+use crate::indices::EntityId;
+use crate::profile;
+use crate::storage::views::UnsafeView;
+
+/// Forget the outcomes of the previous tick's intents
+pub fn update(mut outcomes: UnsafeView<EntityId, IntentOutcomes>, _: ()) {
+    profile!("IntentOutcomeSystem update");
+
+    outcomes.clear();
+}
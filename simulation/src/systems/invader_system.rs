@@ -0,0 +1,268 @@
+//! Spawns waves of NPC invaders in wealthy rooms, configured by `GameConfig::invaders`.
+//!
+//! The wealth of a room is the energy in the spawns and towers, plus the resources in the
+//! storages of the room owner.
+//!
+//! Invaders enter at the edge of the room, as far from its center as possible.
+//!
+use super::spawn_system::{insert_bot_body, BotBodyMut};
+use crate::components::{
+    game_config::GameConfig, CarryComponent, EnergyComponent, EntityComponent, EntityScript,
+    NpcComponent, OwnedEntity, PositionComponent, RoomProperties, StructureKind, TerrainComponent,
+};
+use crate::indices::{ConfigKey, EntityId, Room, WorldPosition};
+use crate::npc::npc_user_id;
+use crate::profile;
+use crate::storage::views::{InsertEntityView, UnsafeView, UnwrapView, View, WorldLogger};
+use crate::Time;
+use slog::{debug, info, warn};
+use std::collections::BTreeMap;
+
+type Mut = (
+    BotBodyMut,
+    UnsafeView<EntityId, PositionComponent>,
+    UnsafeView<EntityId, OwnedEntity>,
+    UnsafeView<EntityId, EntityScript>,
+    UnsafeView<EntityId, NpcComponent>,
+    InsertEntityView,
+);
+type Const<'a> = (
+    View<'a, Room, OwnedEntity>,
+    View<'a, EntityId, StructureKind>,
+    View<'a, EntityId, EnergyComponent>,
+    View<'a, EntityId, CarryComponent>,
+    View<'a, WorldPosition, TerrainComponent>,
+    View<'a, WorldPosition, EntityComponent>,
+    View<'a, ConfigKey, RoomProperties>,
+    UnwrapView<'a, ConfigKey, GameConfig>,
+    Time,
+    WorldLogger,
+);
+
+pub fn update(
+    (bodies, mut positions, mut owners, mut scripts, mut npcs, mut insert_entity): Mut,
+    (
+        room_owners,
+        kinds,
+        energy,
+        carry,
+        terrain,
+        entities_by_pos,
+        room_props,
+        config,
+        Time(time),
+        WorldLogger(logger),
+    ): Const,
+) {
+    profile!("InvaderSystem update");
+
+    let rules = &config.invaders;
+    if rules.interval == 0 || time == 0 || time % rules.interval != 0 {
+        return;
+    }
+    let template = match config.bot_templates.get(&rules.template) {
+        Some(template) => template,
+        None => {
+            warn!(
+                logger,
+                "Invader template {:?} does not exist, skipping the wave", rules.template
+            );
+            return;
+        }
+    };
+
+    let room_of = |id: &EntityId| {
+        positions
+            .get_by_id(id)
+            .map(|PositionComponent(pos)| Room(pos.room))
+    };
+    let mut wealth = BTreeMap::<Room, u32>::new();
+    for (id, kind) in kinds.iter() {
+        let room = match room_of(&id) {
+            Some(room) => room,
+            None => continue,
+        };
+        // only the structures of the room owner count
+        let room_owner = room_owners.get_by_id(&room).map(|owner| owner.owner_id);
+        let owner = owners.get_by_id(&id).map(|owner| owner.owner_id);
+        if room_owner.is_none() || owner != room_owner {
+            continue;
+        }
+        let amount = match kind {
+            StructureKind::Spawn | StructureKind::Tower => energy
+                .get_by_id(&id)
+                .map(|EnergyComponent { energy, .. }| u32::from(*energy)),
+            StructureKind::Storage => carry.get_by_id(&id).map(|carry| u32::from(carry.total())),
+            _ => None,
+        };
+        *wealth.entry(room).or_insert(0) += amount.unwrap_or(0);
+    }
+    let mut invaders = BTreeMap::<Room, u32>::new();
+    for (id, _) in npcs.iter() {
+        if let Some(room) = room_of(&id) {
+            *invaders.entry(room).or_insert(0) += 1;
+        }
+    }
+
+    let center = room_props
+        .value
+        .as_ref()
+        .map(|props| props.center)
+        .unwrap_or_default();
+    let targets: Vec<(Room, u32)> = room_owners
+        .iter()
+        .filter(|(room, _)| wealth.get(room).copied().unwrap_or(0) >= rules.wealth_threshold)
+        .filter_map(|(room, _)| {
+            let count = invaders.get(&room).copied().unwrap_or(0);
+            let n = rules
+                .wave_size
+                .min(rules.max_per_room.saturating_sub(count));
+            if n > 0 {
+                Some((room, n))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    for (room, n) in targets {
+        let mut entries: Vec<WorldPosition> = terrain
+            .table
+            .get_by_id(&room.0)
+            .map(|tiles| {
+                tiles
+                    .iter()
+                    .filter(|(_, TerrainComponent(tile))| tile.is_walkable())
+                    .map(|(pos, _)| WorldPosition { room: room.0, pos })
+                    .filter(|pos| !entities_by_pos.contains_key(pos))
+                    .collect()
+            })
+            .unwrap_or_default();
+        // farthest from the center first, ties broken by position for determinism
+        entries.sort_by_key(|pos| (std::cmp::Reverse(pos.pos.hex_distance(center)), *pos));
+
+        info!(
+            logger,
+            "Spawning {} invaders in room {:?}",
+            n.min(entries.len() as u32),
+            room
+        );
+        for pos in entries.into_iter().take(n as usize) {
+            let entity_id = unsafe { insert_entity.insert_entity() };
+            insert_bot_body(entity_id, template, bodies);
+            positions.insert_or_update(entity_id, PositionComponent(pos));
+            owners.insert_or_update(
+                entity_id,
+                OwnedEntity {
+                    owner_id: npc_user_id(),
+                },
+            );
+            npcs.insert(entity_id);
+            if let Some(script_id) = rules.script {
+                scripts.insert_or_update(entity_id, EntityScript(script_id));
+            }
+            debug!(logger, "Invader {:?} spawned at {:?}", entity_id, pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Axial;
+    use crate::harness::{build_world, Fixture};
+    use crate::storage::views::{FromWorld, FromWorldMut};
+    use crate::systems::positions_system;
+    use crate::utils::{setup_testing, test_logger};
+
+    #[test]
+    fn wealthy_rooms_are_invaded() {
+        setup_testing();
+        // both rooms are owned, but only the first one is wealthy
+        // the resource node of the second room does not count towards its wealth
+        let fixture: Fixture = serde_json::from_str(
+            r#"{
+                "roomRadius": 5,
+                "rooms": [
+                    {
+                        "room": { "q": 0, "r": 0 },
+                        "owner": "00000000-0000-0000-0000-000000000000",
+                        "defaultTerrain": "plain"
+                    },
+                    {
+                        "room": { "q": 1, "r": 0 },
+                        "owner": "00000000-0000-0000-0000-000000000000",
+                        "defaultTerrain": "plain"
+                    }
+                ],
+                "entities": [
+                    {
+                        "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 5, "r": 5 } },
+                        "owner": "00000000-0000-0000-0000-000000000000",
+                        "structure": true,
+                        "kind": "spawn",
+                        "energy": { "energy": 500, "energyMax": 500 }
+                    },
+                    {
+                        "pos": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 5, "r": 6 } },
+                        "owner": "00000000-0000-0000-0000-000000000000",
+                        "structure": true,
+                        "kind": "storage",
+                        "carry": { "contents": { "energy": 1000, "iron": 500 }, "carryMax": 2000 }
+                    },
+                    {
+                        "pos": { "room": { "q": 1, "r": 0 }, "roomPos": { "q": 5, "r": 5 } },
+                        "owner": "00000000-0000-0000-0000-000000000000",
+                        "structure": true,
+                        "kind": "spawn",
+                        "energy": { "energy": 100, "energyMax": 500 }
+                    },
+                    {
+                        "pos": { "room": { "q": 1, "r": 0 }, "roomPos": { "q": 5, "r": 6 } },
+                        "resource": "energy",
+                        "resourceAmount": { "amount": 5000, "amountMax": 5000 }
+                    }
+                ]
+            }"#,
+        )
+        .expect("Failed to parse fixture");
+        let mut world = build_world(test_logger(), &fixture).expect("Failed to build the world");
+        let rules = world
+            .view::<ConfigKey, GameConfig>()
+            .unwrap_value()
+            .invaders
+            .clone();
+        let count_invaders = |world: &crate::world::World| {
+            world.view::<EntityId, NpcComponent>().iter().count() as u32
+        };
+
+        // waves only come every `interval` ticks
+        world.resources.time.value = Some(Time(rules.interval + 1));
+        update(FromWorldMut::new(&mut *world), FromWorld::new(&*world));
+        assert_eq!(count_invaders(&*world), 0);
+
+        world.resources.time.value = Some(Time(rules.interval));
+        for _ in 0..3 {
+            update(FromWorldMut::new(&mut *world), FromWorld::new(&*world));
+            positions_system::update(FromWorldMut::new(&mut *world), FromWorld::new(&*world));
+        }
+        assert_eq!(count_invaders(&*world), rules.max_per_room);
+
+        for (id, _) in world.view::<EntityId, NpcComponent>().iter() {
+            let PositionComponent(pos) = world
+                .view::<EntityId, PositionComponent>()
+                .get_by_id(&id)
+                .copied()
+                .unwrap();
+            assert_eq!(pos.room, Axial::new(0, 0));
+            assert_eq!(
+                world
+                    .view::<EntityId, OwnedEntity>()
+                    .get_by_id(&id)
+                    .unwrap()
+                    .owner_id,
+                npc_user_id()
+            );
+        }
+    }
+}
@@ -1,15 +1,18 @@
-use crate::components::{CarryComponent, EnergyComponent, Resource, ResourceComponent};
+use super::record_outcome;
+use crate::components::{
+    CarryComponent, IntentOutcomes, Resource, ResourceAmountComponent, ResourceComponent,
+};
 use crate::indices::*;
 use crate::intents::{Intents, MineIntent};
 use crate::profile;
+use crate::scripting_api::OperationResult;
 use crate::storage::views::{UnsafeView, UnwrapView, View, WorldLogger};
-use slog::{o, trace, warn};
-
-pub const MINE_AMOUNT: u16 = 10; // TODO: get from bot body
+use slog::{o, trace, warn, Logger};
 
 type Mut = (
-    UnsafeView<EntityId, EnergyComponent>,
+    UnsafeView<EntityId, ResourceAmountComponent>,
     UnsafeView<EntityId, CarryComponent>,
+    UnsafeView<EntityId, IntentOutcomes>,
 );
 type Const<'a> = (
     View<'a, EntityId, ResourceComponent>,
@@ -18,7 +21,7 @@ type Const<'a> = (
 );
 
 pub fn update(
-    (mut energy_table, mut carry_table): Mut,
+    (mut amount_table, mut carry_table, mut outcomes): Mut,
     (resource_table, intents, WorldLogger(logger)): Const,
 ) {
     profile!("MineSystem update");
@@ -26,43 +29,130 @@ pub fn update(
     for intent in intents.iter() {
         let logger = logger.new(o!("bot" => intent.bot.0));
         trace!(logger, "Bot is mining [{:?}]", intent.resource);
-        match resource_table.get_by_id(&intent.resource) {
-            Some(ResourceComponent(Resource::Energy)) => {
-                let resource_energy = match energy_table.get_by_id_mut(&intent.resource) {
-                    Some(resource_energy) => {
-                        if resource_energy.energy == 0 {
-                            trace!(logger, "Mineral is empty!");
-                            continue;
-                        }
-                        resource_energy
-                    }
-                    None => {
-                        warn!(logger, "MineIntent resource has no energy component!");
-                        continue;
-                    }
-                };
-                let carry = match carry_table.get_by_id_mut(&intent.bot) {
-                    Some(x) => x,
-                    None => {
-                        warn!(logger, "MineIntent bot has no carry component");
-                        continue;
-                    }
-                };
+        let res = mine(
+            &logger,
+            intent,
+            &resource_table,
+            &mut amount_table,
+            &mut carry_table,
+        );
+        record_outcome(&mut outcomes, intent.bot, |o| o.mine_intent = Some(res));
+    }
+}
 
-                let mined = resource_energy.energy.min(MINE_AMOUNT); // Max amount that can be mined
-                let mined = (carry.carry_max - carry.carry).min(mined); // Max amount the bot can carry
+fn mine(
+    logger: &Logger,
+    intent: &MineIntent,
+    resource_table: &View<EntityId, ResourceComponent>,
+    amount_table: &mut UnsafeView<EntityId, ResourceAmountComponent>,
+    carry_table: &mut UnsafeView<EntityId, CarryComponent>,
+) -> OperationResult {
+    match resource_table.get_by_id(&intent.resource) {
+        Some(ResourceComponent(Resource::Empty)) | None => {
+            warn!(logger, "Resource not found");
+            OperationResult::InvalidTarget
+        }
+        Some(ResourceComponent(ty)) => {
+            let ty = *ty;
+            let resource_amount = match amount_table.get_by_id_mut(&intent.resource) {
+                Some(resource_amount) => {
+                    if resource_amount.amount == 0 {
+                        trace!(logger, "Mineral is empty!");
+                        return OperationResult::Empty;
+                    }
+                    resource_amount
+                }
+                None => {
+                    warn!(logger, "MineIntent resource has no amount component!");
+                    return OperationResult::InvalidTarget;
+                }
+            };
+            let carry = match carry_table.get_by_id_mut(&intent.bot) {
+                Some(x) => x,
+                None => {
+                    warn!(logger, "MineIntent bot has no carry component");
+                    return OperationResult::InvalidInput;
+                }
+            };
+            if carry.is_full() {
+                trace!(logger, "Bot is full");
+                return OperationResult::Full;
+            }
 
-                carry.carry += mined;
-                resource_energy.energy -= mined;
+            let mined = resource_amount.amount.min(ty.mining_rate()); // Max amount that can be mined
+            let mined = carry.add(ty, mined); // Max amount the bot can carry
+            resource_amount.amount -= mined;
 
-                trace!(
-                    logger,
-                    "Mine succeeded new bot carry {:?} new resource energy {:?}",
-                    carry,
-                    resource_energy
-                );
-            }
-            Some(ResourceComponent(_)) | None => warn!(logger, "Resource not found"),
+            trace!(
+                logger,
+                "Mine succeeded new bot carry {:?} new resource amount {:?}",
+                carry,
+                resource_amount
+            );
+            OperationResult::Ok
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intents::{move_into_storage, BotIntents};
+    use crate::storage::views::{FromWorld, FromWorldMut};
+    use crate::utils::{setup_testing, test_logger};
+    use crate::world::init_inmemory_storage;
+
+    #[test]
+    fn mining_takes_from_the_resource_amount() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+
+        let bot = store.insert_entity();
+        let node = store.insert_entity();
+        store
+            .unsafe_view::<EntityId, CarryComponent>()
+            .insert_or_update(bot, CarryComponent::new(50));
+        store
+            .unsafe_view::<EntityId, ResourceComponent>()
+            .insert_or_update(node, ResourceComponent(Resource::Iron));
+        store
+            .unsafe_view::<EntityId, ResourceAmountComponent>()
+            .insert_or_update(
+                node,
+                ResourceAmountComponent {
+                    amount: 100,
+                    amount_max: 100,
+                },
+            );
+
+        move_into_storage(
+            &mut *store,
+            vec![BotIntents {
+                mine_intent: Some(MineIntent {
+                    bot,
+                    resource: node,
+                }),
+                ..Default::default()
+            }],
+        );
+        update(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
+
+        let mined = Resource::Iron.mining_rate();
+        assert_eq!(
+            store
+                .view::<EntityId, CarryComponent>()
+                .get_by_id(&bot)
+                .unwrap()
+                .get(Resource::Iron),
+            mined
+        );
+        assert_eq!(
+            store
+                .view::<EntityId, ResourceAmountComponent>()
+                .get_by_id(&node)
+                .unwrap()
+                .amount,
+            100 - mined
+        );
+    }
+}
@@ -9,7 +9,7 @@ use slog::{debug, error, trace, Logger};
 
 type Mut = (
     UnsafeView<EntityId, comp::PositionComponent>,
-    UnsafeView<EntityId, comp::EnergyComponent>,
+    UnsafeView<EntityId, comp::ResourceAmountComponent>,
     DeferredDeleteEntityView,
 );
 type Const<'a> = (
@@ -20,7 +20,7 @@ type Const<'a> = (
 );
 
 pub fn update(
-    (mut entity_positions, mut energy, mut delete_entity_deferred): Mut,
+    (mut entity_positions, mut amounts, mut delete_entity_deferred): Mut,
     (position_entities, terrain_table, resources, WorldLogger(logger)): Const,
 ) {
     profile!("Mineral System update");
@@ -30,24 +30,24 @@ pub fn update(
 
     let minerals_it = resources
         .iter()
-        .filter(|(_, r)| matches!(r.0, comp::Resource::Energy));
+        .filter(|(_, r)| !matches!(r.0, comp::Resource::Empty));
     let entity_positions_it = entity_positions.iter_mut();
-    let energy_iter = energy.iter_mut();
+    let amount_iter = amounts.iter_mut();
 
     // in case of an error we need to clean up the mineral
     // however best not to clean it inside the iterator, hmmm???
-    join!([minerals_it, entity_positions_it, energy_iter]).for_each(
-        |(id, (_resource, position, energy))| {
+    join!([minerals_it, entity_positions_it, amount_iter]).for_each(
+        |(id, (_resource, position, amount))| {
             trace!(
                 logger,
                 "updating {:?} {:?} {:?} {:?}",
                 id,
                 _resource,
                 position,
-                energy
+                amount
             );
 
-            if energy.energy > 0 {
+            if amount.amount > 0 {
                 return;
             }
             trace!(logger, "Respawning mineral {:?}", id);
@@ -82,7 +82,7 @@ pub fn update(
             );
             match pos {
                 Some(pos) => {
-                    energy.energy = energy.energy_max;
+                    amount.amount = amount.amount_max;
                     position.0.pos = pos;
                 }
                 None => {
@@ -1,75 +1,164 @@
-use crate::components::{Bot, EntityComponent, PositionComponent};
+//! Moves are resolved for the whole tick at once: bots may swap places, or follow each other in a
+//! line, as long as every bot in the way moves as well.
+//!
+//! When multiple bots try to move onto the same tile the one with the lowest id wins, the others
+//! get a `Conflict` outcome. Bots blocked by entities that stay in place get `Occupied`.
+//!
+use super::record_outcome;
+use crate::components::{
+    Bot, EntityComponent, FatigueComponent, IntentOutcomes, PositionComponent, RoadComponent,
+    TerrainComponent,
+};
 use crate::indices::{EmptyKey, EntityId, WorldPosition};
 use crate::intents::{Intents, MoveIntent};
 use crate::profile;
+use crate::scripting_api::OperationResult;
 use crate::storage::views::{UnsafeView, UnwrapViewMut, View, WorldLogger};
 use crate::tables::traits::Table;
+use crate::terrain;
 use rayon::prelude::*;
 use slog::{debug, trace, Logger};
+use std::collections::BTreeMap;
 
 type Mut = (
     UnsafeView<EntityId, PositionComponent>,
     UnwrapViewMut<EmptyKey, Intents<MoveIntent>>,
+    UnsafeView<EntityId, IntentOutcomes>,
+    UnsafeView<EntityId, FatigueComponent>,
 );
 type Const<'a> = (
     View<'a, EntityId, Bot>,
     View<'a, WorldPosition, EntityComponent>,
+    View<'a, WorldPosition, TerrainComponent>,
+    View<'a, WorldPosition, RoadComponent>,
     WorldLogger,
 );
 
-pub fn update((mut positions, mut intents): Mut, (bots, pos_entities, WorldLogger(logger)): Const) {
+/// Moving bots gain fatigue by the cost of the tile they move onto, fatigued bots can not move
+pub fn update(
+    (mut positions, mut intents, mut outcomes, mut fatigue): Mut,
+    (bots, pos_entities, terrain, roads, WorldLogger(logger)): Const,
+) {
     profile!(" MoveSystem update");
 
-    pre_process_move_intents(&logger, &mut intents.0);
+    // intents removed by pre_process keep this outcome
     for intent in intents.iter() {
-        trace!(
-            logger,
-            "Moving bot[{:?}] to {:?}",
-            intent.bot,
-            intent.position
-        );
+        record_outcome(&mut outcomes, intent.bot, |o| {
+            o.move_intent = Some(OperationResult::Conflict)
+        });
+    }
 
-        if bots.get_by_id(&intent.bot).is_none() {
+    // invalid intents should not take part in the contest for tiles
+    intents.0.retain(|intent| {
+        let res = if bots.get_by_id(&intent.bot).is_none() {
             trace!(logger, "Bot by id {:?} does not exist", intent.bot);
-            continue;
-        }
+            OperationResult::InvalidInput
+        } else if fatigue
+            .get_by_id(&intent.bot)
+            .map(|f| f.fatigue > 0)
+            .unwrap_or(false)
+        {
+            trace!(logger, "Bot {:?} is fatigued", intent.bot);
+            OperationResult::Fatigued
+        } else {
+            return true;
+        };
+        record_outcome(&mut outcomes, intent.bot, |o| o.move_intent = Some(res));
+        false
+    });
 
-        if pos_entities.get_by_id(&intent.position).is_some() {
-            trace!(logger, "Occupied {:?} ", intent.position);
-            continue;
-        }
+    pre_process_move_intents(&logger, &mut intents.0);
 
-        positions.insert_or_update(intent.bot, PositionComponent(intent.position));
+    let moves = intents
+        .iter()
+        .map(|intent| (intent.bot, intent.position))
+        .collect::<BTreeMap<_, _>>();
+    let can_move = resolve_moves(&moves, |pos| {
+        pos_entities.get_by_id(pos).map(|EntityComponent(id)| *id)
+    });
 
-        trace!(logger, "Move successful");
+    for (bot, position) in moves {
+        let res = if can_move.get(&bot).copied().unwrap_or(false) {
+            trace!(logger, "Moving bot[{:?}] to {:?}", bot, position);
+            positions.insert_or_update(bot, PositionComponent(position));
+            if let Some(f) = fatigue.get_by_id_mut(&bot) {
+                let tile = terrain
+                    .get_by_id(&position)
+                    .map(|TerrainComponent(t)| *t)
+                    .unwrap_or_default();
+                let cost = terrain::move_cost(tile, roads.contains_key(&position));
+                f.fatigue = f.fatigue.saturating_add(cost);
+            }
+            OperationResult::Ok
+        } else {
+            trace!(logger, "Bot[{:?}] is blocked at {:?}", bot, position);
+            OperationResult::Occupied
+        };
+        record_outcome(&mut outcomes, bot, |o| o.move_intent = Some(res));
     }
 }
 
-/// Remove duplicate positions.
+/// Decide which moves may be executed. Every target position must be unique.
+///
+/// A move succeeds if its target is empty, or the entity standing there moves away successfully.
+/// Moves forming a cycle, like two bots swapping places, succeed together.
+fn resolve_moves(
+    moves: &BTreeMap<EntityId, WorldPosition>,
+    occupant: impl Fn(&WorldPosition) -> Option<EntityId>,
+) -> BTreeMap<EntityId, bool> {
+    let mut result = BTreeMap::new();
+    for start in moves.keys() {
+        if result.contains_key(start) {
+            continue;
+        }
+        // follow the line of entities in the way, until it ends or loops back
+        let mut chain = vec![*start];
+        let ok = loop {
+            let current = chain[chain.len() - 1];
+            let next = match occupant(&moves[&current]) {
+                Some(next) => next,
+                None => break true,
+            };
+            if let Some(ok) = result.get(&next) {
+                break *ok;
+            }
+            if !moves.contains_key(&next) {
+                // the occupant stays in place
+                break false;
+            }
+            if chain.contains(&next) {
+                break true;
+            }
+            chain.push(next);
+        };
+        for id in chain {
+            result.insert(id, ok);
+        }
+    }
+    result
+}
+
+/// Remove intents targeting the same position, keeping the one of the bot with the lowest id.
 /// We assume that there are no duplicated entities
 fn pre_process_move_intents(logger: &Logger, move_intents: &mut Vec<MoveIntent>) {
     profile!("pre_process_move_intents");
 
-    let len = move_intents.len();
-    if len < 2 {
+    if move_intents.len() < 2 {
         // 0 and 1 long vectors do not have duplicates
         return;
     }
-    move_intents.par_sort_unstable_by_key(|intent| intent.position);
-    // move in reverse order because we want to remove invalid intents as we move,
-    // swap_remove would change the last position, screwing with the ordering
-    for current in (0..=len - 2).rev() {
-        let last = current + 1;
-        let a = &move_intents[last];
-        let b = &move_intents[current];
-        if a.position == b.position {
+    move_intents.par_sort_unstable_by_key(|intent| (intent.position, intent.bot));
+    move_intents.dedup_by(|a, b| {
+        // `b` precedes `a`
+        let dupe = a.position == b.position;
+        if dupe {
             debug!(
                 logger,
                 "Duplicated position in move intents, removing {:?}", a
             );
-            move_intents.swap_remove(last);
         }
-    }
+        dupe
+    });
 }
 
 #[cfg(test)]
@@ -78,6 +167,7 @@ mod tests {
     use crate::geometry::Axial;
     use crate::indices::EntityId;
     use crate::indices::WorldPosition;
+    use crate::terrain::TileTerrainType;
     use crate::utils::*;
 
     #[test]
@@ -120,4 +210,190 @@ mod tests {
         assert_eq!(intents.len(), 2);
         assert_ne!(intents[0].position, intents[1].position);
     }
+
+    #[test]
+    fn losing_duplicate_move_records_conflict() {
+        setup_testing();
+        let mut store = crate::world::init_inmemory_storage(test_logger());
+
+        let a = store.insert_entity();
+        let b = store.insert_entity();
+        store.unsafe_view::<EntityId, Bot>().insert(a);
+        store.unsafe_view::<EntityId, Bot>().insert(b);
+
+        let position = WorldPosition {
+            room: Default::default(),
+            pos: Axial::new(42, 69),
+        };
+        crate::intents::move_into_storage(
+            &mut store,
+            vec![
+                crate::intents::BotIntents {
+                    entity_id: a,
+                    move_intent: Some(MoveIntent { bot: a, position }),
+                    ..Default::default()
+                },
+                crate::intents::BotIntents {
+                    entity_id: b,
+                    move_intent: Some(MoveIntent { bot: b, position }),
+                    ..Default::default()
+                },
+            ],
+        );
+
+        update(
+            crate::storage::views::FromWorldMut::new(&mut *store),
+            crate::storage::views::FromWorld::new(&*store),
+        );
+
+        let outcomes = store.view::<EntityId, IntentOutcomes>();
+        let mut results = [a, b]
+            .iter()
+            .map(|id| outcomes.get_by_id(id).and_then(|o| o.move_intent))
+            .collect::<Vec<_>>();
+        results.sort_by_key(|r| r.map(|r| r as i32));
+        assert_eq!(
+            results,
+            vec![Some(OperationResult::Ok), Some(OperationResult::Conflict)]
+        );
+    }
+
+    #[test]
+    fn moving_makes_bots_fatigued() {
+        setup_testing();
+        let mut store = crate::world::init_inmemory_storage(test_logger());
+
+        let bot = store.insert_entity();
+        store.unsafe_view::<EntityId, Bot>().insert(bot);
+        store
+            .unsafe_view::<EntityId, FatigueComponent>()
+            .insert_or_update(
+                bot,
+                FatigueComponent {
+                    fatigue: 0,
+                    recovery: 2,
+                },
+            );
+
+        let mut move_to = |q| {
+            let position = WorldPosition {
+                room: Default::default(),
+                pos: Axial::new(q, 0),
+            };
+            crate::intents::move_into_storage(
+                &mut store,
+                vec![crate::intents::BotIntents {
+                    entity_id: bot,
+                    move_intent: Some(MoveIntent { bot, position }),
+                    ..Default::default()
+                }],
+            );
+            update(
+                crate::storage::views::FromWorldMut::new(&mut *store),
+                crate::storage::views::FromWorld::new(&*store),
+            );
+            store
+                .view::<EntityId, IntentOutcomes>()
+                .get_by_id(&bot)
+                .and_then(|o| o.move_intent)
+        };
+
+        assert_eq!(move_to(1), Some(OperationResult::Ok));
+        assert_eq!(move_to(2), Some(OperationResult::Fatigued));
+
+        let fatigue = store.view::<EntityId, FatigueComponent>();
+        assert_eq!(
+            fatigue.get_by_id(&bot).map(|f| f.fatigue),
+            Some(TileTerrainType::Plain.move_cost())
+        );
+    }
+
+    /// Place bots at `(q, 0)` and let each move to `(to, 0)`, returning the outcomes and the new
+    /// positions
+    fn run_moves(
+        stationary: &[i32],
+        moves: &[(i32, i32)],
+    ) -> Vec<(Option<OperationResult>, WorldPosition)> {
+        setup_testing();
+        let mut store = crate::world::init_inmemory_storage(test_logger());
+
+        let at = |q| WorldPosition {
+            room: Default::default(),
+            pos: Axial::new(q, 0),
+        };
+
+        for q in stationary.iter() {
+            let id = store.insert_entity();
+            store
+                .unsafe_view::<WorldPosition, EntityComponent>()
+                .insert(at(*q), EntityComponent(id))
+                .unwrap();
+        }
+        let mut intents = vec![];
+        let mut ids = vec![];
+        for (from, to) in moves.iter() {
+            let bot = store.insert_entity();
+            store.unsafe_view::<EntityId, Bot>().insert(bot);
+            store
+                .unsafe_view::<EntityId, PositionComponent>()
+                .insert_or_update(bot, PositionComponent(at(*from)));
+            store
+                .unsafe_view::<WorldPosition, EntityComponent>()
+                .insert(at(*from), EntityComponent(bot))
+                .unwrap();
+            intents.push(crate::intents::BotIntents {
+                entity_id: bot,
+                move_intent: Some(MoveIntent {
+                    bot,
+                    position: at(*to),
+                }),
+                ..Default::default()
+            });
+            ids.push(bot);
+        }
+        crate::intents::move_into_storage(&mut store, intents);
+
+        update(
+            crate::storage::views::FromWorldMut::new(&mut *store),
+            crate::storage::views::FromWorld::new(&*store),
+        );
+
+        let outcomes = store.view::<EntityId, IntentOutcomes>();
+        let positions = store.view::<EntityId, PositionComponent>();
+        ids.iter()
+            .map(|id| {
+                (
+                    outcomes.get_by_id(id).and_then(|o| o.move_intent),
+                    positions.get_by_id(id).unwrap().0,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bots_can_swap_places() {
+        let res = run_moves(&[], &[(0, 1), (1, 0)]);
+        assert_eq!(res[0].0, Some(OperationResult::Ok));
+        assert_eq!(res[1].0, Some(OperationResult::Ok));
+        assert_eq!(res[0].1.pos, Axial::new(1, 0));
+        assert_eq!(res[1].1.pos, Axial::new(0, 0));
+    }
+
+    #[test]
+    fn bots_can_follow_each_other() {
+        let res = run_moves(&[], &[(0, 1), (1, 2), (2, 3)]);
+        for (i, (outcome, pos)) in res.iter().enumerate() {
+            assert_eq!(*outcome, Some(OperationResult::Ok));
+            assert_eq!(pos.pos, Axial::new(i as i32 + 1, 0));
+        }
+    }
+
+    #[test]
+    fn blocked_line_does_not_move() {
+        let res = run_moves(&[3], &[(0, 1), (1, 2), (2, 3)]);
+        for (i, (outcome, pos)) in res.iter().enumerate() {
+            assert_eq!(*outcome, Some(OperationResult::Occupied));
+            assert_eq!(pos.pos, Axial::new(i as i32, 0));
+        }
+    }
 }
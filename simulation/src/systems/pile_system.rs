@@ -0,0 +1,178 @@
+//! Resource piles are created by bots dropping resources, or dying while carrying them.
+//! Piles lose `PILE_DECAY_AMOUNT` of every resource every `PILE_DECAY_INTERVAL` ticks, and are
+//! removed once empty.
+//!
+use crate::components::{
+    CarryComponent, PileComponent, PositionComponent, Resource, ResourcePileComponent,
+};
+use crate::indices::{EntityId, WorldPosition};
+use crate::profile;
+use crate::storage::views::{
+    DeferredDeleteEntityView, FromWorldMut, InsertEntityView, UnsafeView, WorldLogger,
+};
+use crate::world::World;
+use slog::{debug, trace};
+
+pub const PILE_DECAY_INTERVAL: u8 = 10;
+pub const PILE_DECAY_AMOUNT: u16 = 10;
+
+type Mut = (
+    UnsafeView<EntityId, ResourcePileComponent>,
+    UnsafeView<EntityId, CarryComponent>,
+    DeferredDeleteEntityView,
+);
+
+pub fn update((mut piles, mut carry_table, mut delete): Mut, WorldLogger(logger): WorldLogger) {
+    profile!("PileSystem update");
+    debug!(logger, "update pile system called");
+
+    for (id, pile) in piles.iter_mut() {
+        let contents = match carry_table.get_by_id_mut(&id) {
+            Some(c) => c,
+            None => {
+                unsafe { delete.delete_entity(id) };
+                continue;
+            }
+        };
+        if pile.time_remaining > 0 {
+            pile.time_remaining -= 1;
+        } else {
+            pile.time_remaining = PILE_DECAY_INTERVAL;
+            let resources = contents.contents.keys().copied().collect::<Vec<_>>();
+            for ty in resources {
+                contents.take(ty, PILE_DECAY_AMOUNT);
+            }
+        }
+        if contents.total() == 0 {
+            trace!(logger, "Pile {:?} is empty, deleting", id);
+            unsafe { delete.delete_entity(id) };
+        }
+    }
+
+    debug!(logger, "update pile system done");
+}
+
+/// Views used to drop resources on the ground
+#[derive(Clone, Copy)]
+pub struct DropResourcesMut {
+    pub piles: UnsafeView<EntityId, ResourcePileComponent>,
+    pub carry_table: UnsafeView<EntityId, CarryComponent>,
+    pub positions: UnsafeView<EntityId, PositionComponent>,
+    pub piles_by_pos: UnsafeView<WorldPosition, PileComponent>,
+    pub insert_entity: InsertEntityView,
+}
+
+impl FromWorldMut for DropResourcesMut {
+    fn new(w: &mut World) -> Self {
+        Self {
+            piles: FromWorldMut::new(w),
+            carry_table: FromWorldMut::new(w),
+            positions: FromWorldMut::new(w),
+            piles_by_pos: FromWorldMut::new(w),
+            insert_entity: FromWorldMut::new(w),
+        }
+    }
+}
+
+/// Add the resources to the pile at the given position, creating a new pile if there is none.
+/// Returns the id of the pile.
+pub fn drop_resources(
+    pos: WorldPosition,
+    resources: impl IntoIterator<Item = (Resource, u16)>,
+    DropResourcesMut {
+        mut piles,
+        mut carry_table,
+        mut positions,
+        mut piles_by_pos,
+        mut insert_entity,
+    }: DropResourcesMut,
+) -> EntityId {
+    // the index is rebuilt once per tick, the pile may have been deleted since
+    let existing = piles_by_pos
+        .get_by_id(&pos)
+        .map(|PileComponent(id)| *id)
+        .filter(|id| piles.contains(id));
+    let pile_id = match existing {
+        Some(id) => id,
+        None => {
+            let id = unsafe { insert_entity.insert_entity() };
+            piles.insert_or_update(
+                id,
+                ResourcePileComponent {
+                    time_remaining: PILE_DECAY_INTERVAL,
+                },
+            );
+            carry_table.insert_or_update(id, CarryComponent::new(u16::MAX));
+            positions.insert_or_update(id, PositionComponent(pos));
+            match piles_by_pos.get_by_id_mut(&pos) {
+                Some(pile) => *pile = PileComponent(id),
+                None => piles_by_pos
+                    .insert(pos, PileComponent(id))
+                    .expect("Failed to insert pile position"),
+            }
+            id
+        }
+    };
+    if let Some(pile) = carry_table.get_by_id_mut(&pile_id) {
+        for (ty, amount) in resources {
+            pile.add(ty, amount);
+        }
+    }
+    pile_id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Axial;
+    use crate::storage::views::FromWorld;
+    use crate::systems::positions_system;
+    use crate::utils::{setup_testing, test_logger};
+    use crate::world::init_inmemory_storage;
+
+    #[test]
+    fn drops_at_the_same_position_share_a_pile() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+        let pos = |q| WorldPosition {
+            room: Axial::new(0, 0),
+            pos: Axial::new(q, 5),
+        };
+
+        let pile = drop_resources(
+            pos(5),
+            Some((Resource::Energy, 10)),
+            FromWorldMut::new(&mut *store),
+        );
+        let other = drop_resources(
+            pos(6),
+            Some((Resource::Energy, 10)),
+            FromWorldMut::new(&mut *store),
+        );
+        assert_ne!(pile, other);
+
+        // the pile is found both before and after the index is rebuilt
+        assert_eq!(
+            drop_resources(
+                pos(5),
+                Some((Resource::Iron, 5)),
+                FromWorldMut::new(&mut *store)
+            ),
+            pile
+        );
+        positions_system::update(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
+        assert_eq!(
+            drop_resources(
+                pos(5),
+                Some((Resource::Iron, 5)),
+                FromWorldMut::new(&mut *store)
+            ),
+            pile
+        );
+
+        let carry = store.view::<EntityId, CarryComponent>();
+        let carry = carry.get_by_id(&pile).unwrap();
+        assert_eq!(carry.get(Resource::Energy), 10);
+        assert_eq!(carry.get(Resource::Iron), 10);
+    }
+}
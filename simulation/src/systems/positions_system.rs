@@ -1,19 +1,79 @@
-use crate::components::{EntityComponent, PositionComponent};
+use crate::components::{
+    ConstructionSite, EntityComponent, PileComponent, PositionComponent, RemainsComponent,
+    ResourcePileComponent, RoadComponent, SiteComponent, StructureKind,
+};
 use crate::indices::{EntityId, WorldPosition};
 use crate::profile;
 use crate::storage::views::{UnsafeView, View, WorldLogger};
 use slog::{debug, error};
 
-type Mut = UnsafeView<WorldPosition, EntityComponent>;
-type Const<'a> = (View<'a, EntityId, PositionComponent>, WorldLogger);
+type Mut = (
+    UnsafeView<WorldPosition, EntityComponent>,
+    UnsafeView<WorldPosition, RoadComponent>,
+    UnsafeView<WorldPosition, SiteComponent>,
+    UnsafeView<WorldPosition, PileComponent>,
+);
+type Const<'a> = (
+    View<'a, EntityId, PositionComponent>,
+    View<'a, EntityId, StructureKind>,
+    View<'a, EntityId, ConstructionSite>,
+    View<'a, EntityId, ResourcePileComponent>,
+    View<'a, EntityId, RemainsComponent>,
+    WorldLogger,
+);
 
-/// Reset the entity positions table
-pub fn update(mut position_entities: Mut, (positions, WorldLogger(logger)): Const) {
+/// Reset the entity positions table.
+/// Walkable structures, their construction sites, resource piles and remains are not registered
+/// there, roads, construction sites and resource piles are collected into their own tables.
+pub fn update(
+    (mut position_entities, mut roads, mut site_positions, mut pile_positions): Mut,
+    (positions, kinds, sites, piles, remains, WorldLogger(logger)): Const,
+) {
     profile!("PositionSystem update");
     debug!(logger, "update positions system called");
 
+    roads.clear();
+    site_positions.clear();
+    for (id, _) in sites.iter() {
+        if let Some(PositionComponent(pos)) = positions.get_by_id(&id) {
+            site_positions
+                .insert(*pos, SiteComponent(id))
+                .map_err(|e| {
+                    error!(logger, "Failed to insert construction site {:?}", e);
+                })
+                .ok();
+        }
+    }
+    pile_positions.clear();
+    for (id, _) in piles.iter() {
+        if let Some(PositionComponent(pos)) = positions.get_by_id(&id) {
+            pile_positions
+                .insert(*pos, PileComponent(id))
+                .map_err(|e| {
+                    error!(logger, "Failed to insert resource pile {:?}", e);
+                })
+                .ok();
+        }
+    }
     let mut positions = positions
         .iter()
+        .filter(|(id, _)| !piles.contains(id) && !remains.contains(id))
+        .filter(|(id, PositionComponent(pos))| match kinds.get_by_id(id) {
+            Some(StructureKind::Road) => {
+                roads
+                    .insert(*pos, RoadComponent(*id))
+                    .map_err(|e| {
+                        error!(logger, "Failed to insert road {:?}", e);
+                    })
+                    .ok();
+                false
+            }
+            Some(kind) => !kind.is_walkable(),
+            None => sites
+                .get_by_id(id)
+                .map(|site| !site.kind.is_walkable())
+                .unwrap_or(true),
+        })
         .map(|(id, PositionComponent(pos))| (*pos, EntityComponent(id)))
         .collect::<Vec<_>>();
 
@@ -0,0 +1,29 @@
+//! Removes the remains of dead entities once their time is up.
+//!
+use crate::components::RemainsComponent;
+use crate::indices::EntityId;
+use crate::profile;
+use crate::storage::views::{DeferredDeleteEntityView, UnsafeView, WorldLogger};
+use slog::{debug, trace};
+
+pub fn update(
+    (mut remains, mut delete): (
+        UnsafeView<EntityId, RemainsComponent>,
+        DeferredDeleteEntityView,
+    ),
+    WorldLogger(logger): WorldLogger,
+) {
+    profile!("RemainsSystem update");
+    debug!(logger, "update remains system called");
+
+    for (id, remains) in remains.iter_mut() {
+        if remains.time_remaining > 0 {
+            remains.time_remaining -= 1;
+        } else {
+            trace!(logger, "Remains {:?} expired, deleting", id);
+            unsafe { delete.delete_entity(id) };
+        }
+    }
+
+    debug!(logger, "update remains system done");
+}
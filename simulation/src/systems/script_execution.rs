@@ -1,7 +1,8 @@
 use crate::storage::views::FromWorld;
 use crate::{
     components::{
-        game_config::GameConfig, EntityScript, OwnedEntity, ScriptComponent, ScriptHistoryEntry,
+        game_config::GameConfig, CpuUsage, EntityScript, OwnedEntity, ScriptComponent,
+        ScriptErrorStats, ScriptHistoryEntry, ScriptRunStats,
     },
     prelude::World,
 };
@@ -11,8 +12,10 @@ use crate::{
 };
 use crate::{intents::*, profile};
 use cao_lang::prelude::*;
+use rand::{rngs::SmallRng, SeedableRng};
 use rayon::prelude::*;
 use slog::{debug, o, trace, warn};
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::fmt::{self, Display, Formatter};
 use std::mem::{replace, take};
@@ -32,6 +35,14 @@ pub enum ExecutionError {
     },
 }
 
+/// Execute the scripts in `workload`.
+///
+/// Scripts are grouped by their owners, each group is executed on a single thread, so the
+/// users' CPU budgets (`GameConfig::user_cpu_budget`) can be enforced.
+/// CPU usage is the number of executed cards, see `cards_executed`.
+/// Scripts are started while their owner has budget left, the rest are skipped and deferred to
+/// the next tick. A script may not use more than the remaining budget, scripts running out of it
+/// are aborted and use up the budget of the tick.
 pub fn execute_scripts(
     workload: &[(EntityId, EntityScript)],
     storage: &mut World,
@@ -40,68 +51,212 @@ pub fn execute_scripts(
 
     let logger = storage.logger.new(o!("tick" => storage.time()));
     let owners_table = storage.view::<EntityId, OwnedEntity>().reborrow();
+    let usage_table = storage.view::<UserId, CpuUsage>().reborrow();
 
-    let n_scripts = workload.len();
+    let mut by_owner = BTreeMap::<Option<UserId>, Vec<(EntityId, EntityScript)>>::new();
+    for (entity_id, script) in workload {
+        let owner_id = owners_table
+            .get_by_id(&entity_id)
+            .map(|OwnedEntity { owner_id }| *owner_id);
+        by_owner
+            .entry(owner_id)
+            .or_insert_with(Vec::new)
+            .push((*entity_id, *script));
+    }
+    let groups = by_owner
+        .into_iter()
+        .map(|(owner_id, mut scripts)| {
+            // start with the scripts that were deferred last tick
+            let resume_from = owner_id
+                .and_then(|owner_id| usage_table.get_by_id(&owner_id))
+                .and_then(|usage| usage.resume_from);
+            if let Some(resume_from) = resume_from {
+                let start = scripts
+                    .iter()
+                    .position(|(id, _)| *id >= resume_from)
+                    .unwrap_or(0);
+                scripts.rotate_left(start);
+            }
+            (owner_id, scripts)
+        })
+        .collect::<Vec<_>>();
+
+    let n_groups = groups.len();
     let n_threads = rayon::current_num_threads();
-    // the +1 handles edge cases, where n_script < n_threads
+    // the +1 handles edge cases, where n_groups < n_threads
     // this way in practice 1 thread will have a bit less work to perform than the others,
     // but it should be fine.
     // Also if the programs call engine functions that have internal parallelisation, then
     // load balancing should be even less of a problem...
-    let chunk_size = (n_scripts / n_threads) + 1;
+    let chunk_size = (n_groups / n_threads) + 1;
 
     debug!(
         logger,
-        "Executing {} scripts on {} threads in chunks of {}", n_scripts, n_threads, chunk_size
+        "Executing {} scripts of {} users on {} threads in chunks of {}",
+        workload.len(),
+        n_groups,
+        n_threads,
+        chunk_size
     );
 
-    let intents: Option<Vec<BotIntents>> = workload
+    type ChunkResult = (
+        Vec<BotIntents>,
+        Vec<(UserId, CpuUsage)>,
+        // script and whether its execution failed
+        Vec<(ScriptId, bool)>,
+    );
+    let result: Option<ChunkResult> = groups
         .par_chunks(chunk_size)
         .fold(
-            || Vec::with_capacity(chunk_size),
-            |mut intents, entity_scripts| {
+            || {
+                (
+                    Vec::with_capacity(chunk_size),
+                    Vec::with_capacity(chunk_size),
+                    Vec::with_capacity(chunk_size),
+                )
+            },
+            |(mut intents, mut usages, mut runs), groups| {
                 let data = ScriptExecutionData::unsafe_default(logger.clone());
 
                 let conf = UnwrapView::<ConfigKey, GameConfig>::new(storage);
                 let mut vm = VM::new(logger.clone(), data);
                 vm.history.reserve(conf.execution_limit as usize);
-                vm.max_iter = i32::try_from(conf.execution_limit)
-                    .expect("Expected execution_limit to fit into 31 bits");
                 crate::scripting_api::make_import().execute_imports(&mut vm);
 
-                for (entity_id, script) in entity_scripts {
-                    let owner_id = owners_table
-                        .get_by_id(&entity_id)
-                        .map(|OwnedEntity { owner_id }| *owner_id);
-
-                    vm.clear();
-                    match execute_single_script(
-                        &logger, *entity_id, script.0, owner_id, storage, &mut vm,
-                    ) {
-                        Ok(ints) => intents.push(ints),
-                        Err(err) => {
-                            warn!(
+                for (owner_id, scripts) in groups {
+                    let budget = match owner_id {
+                        Some(_) => conf.user_cpu_budget,
+                        None => std::u32::MAX,
+                    };
+                    let mut usage = CpuUsage {
+                        budget,
+                        ..Default::default()
+                    };
+                    for (i, (entity_id, script)) in scripts.iter().enumerate() {
+                        let remaining = budget.saturating_sub(usage.used);
+                        if remaining == 0 {
+                            debug!(
                                 logger,
-                                "Execution failure in {:?} of {:?}:\n{:?}", script, entity_id, err
+                                "User {:?} ran out of CPU budget, deferring {} scripts",
+                                owner_id,
+                                scripts.len() - i
                             );
+                            usage.skipped = (scripts.len() - i) as u32;
+                            usage.resume_from = Some(*entity_id);
+                            break;
                         }
+
+                        vm.clear();
+                        let max_iter = conf.execution_limit.min(remaining);
+                        vm.max_iter = i32::try_from(max_iter)
+                            .expect("Expected execution_limit to fit into 31 bits");
+                        let used = match execute_single_script(
+                            &logger, *entity_id, script.0, *owner_id, remaining, storage, &mut vm,
+                        ) {
+                            Ok(ints) => {
+                                runs.push((script.0, false));
+                                let used = ints
+                                    .script_history_intent
+                                    .as_ref()
+                                    .map(|entry| entry.payload.len() as u32)
+                                    .unwrap_or(0);
+                                intents.push(ints);
+                                used
+                            }
+                            Err(ExecutionError::RuntimeError {
+                                error: cao_lang::prelude::ExecutionError::Timeout,
+                                ..
+                            }) if max_iter < conf.execution_limit => {
+                                // cut short by the budget of the user, not a failure of the script
+                                debug!(
+                                    logger,
+                                    "{:?} of {:?} ran out of CPU budget", script, entity_id
+                                );
+                                vm.history.clear();
+                                remaining
+                            }
+                            Err(err) => {
+                                runs.push((script.0, true));
+                                warn!(
+                                    logger,
+                                    "Execution failure in {:?} of {:?}:\n{:?}",
+                                    script,
+                                    entity_id,
+                                    err
+                                );
+                                let used = cards_executed(&vm);
+                                vm.history.clear();
+                                used
+                            }
+                        };
+                        usage.used = usage.used.saturating_add(used);
+                    }
+                    if let Some(owner_id) = owner_id {
+                        usages.push((*owner_id, usage));
                     }
                 }
-                intents
+                (intents, usages, runs)
             },
         )
-        .reduce_with(|mut res, intermediate| {
-            res.extend(intermediate);
-            res
+        .reduce_with(|(mut intents, mut usages, mut runs), (int, us, r)| {
+            intents.extend(int);
+            usages.extend(us);
+            runs.extend(r);
+            (intents, usages, runs)
         });
 
+    let (intents, usages, runs) = result.unwrap_or_default();
+
+    let mut usage_table = storage.unsafe_view::<UserId, CpuUsage>();
+    *usage_table = Default::default();
+    for (user_id, usage) in usages {
+        usage_table.insert_or_update(user_id, usage);
+    }
+
+    update_error_stats(storage, runs);
+
     debug!(
         logger,
         "Executing scripts done. Returning {:?} intents",
-        intents.as_ref().map(|i| i.len())
+        intents.len()
     );
     trace!(logger, "Intents {:#?}", intents);
-    intents.unwrap_or_else(Vec::default)
+    intents
+}
+
+/// Record the number of runs and runtime errors of the scripts in this tick
+fn update_error_stats(storage: &mut World, runs: Vec<(ScriptId, bool)>) {
+    let time = storage.time();
+    let window = UnwrapView::<ConfigKey, GameConfig>::new(storage).script_error_window as usize;
+
+    let mut by_script = BTreeMap::<ScriptId, ScriptRunStats>::new();
+    for (script_id, failed) in runs {
+        let stats = by_script.entry(script_id).or_insert(ScriptRunStats {
+            time,
+            ..Default::default()
+        });
+        stats.runs += 1;
+        stats.errors += failed as u32;
+    }
+
+    let mut stats_table = storage.unsafe_view::<ScriptId, ScriptErrorStats>();
+    for (script_id, stats) in by_script {
+        match stats_table.get_by_id_mut(&script_id) {
+            Some(s) => s.push(stats, window),
+            None => {
+                let mut s = ScriptErrorStats::default();
+                s.push(stats, window);
+                stats_table.insert_or_update(script_id, s);
+            }
+        }
+    }
+}
+
+/// Number of cards the current script executed so far.
+/// The VM records an entry per executed card in its history, scripts must be compiled with
+/// breadcrumbs for their CPU usage to be metered.
+pub fn cards_executed(vm: &VM<ScriptExecutionData>) -> u32 {
+    vm.history.len() as u32
 }
 
 fn prepare_script_data(
@@ -117,11 +272,14 @@ fn prepare_script_data(
     ScriptExecutionData::new(logger.clone(), storage, intents, entity_id, user_id)
 }
 
+/// `cpu_budget` is the remaining CPU budget of the owner of the script, reported to the script
+/// via the `cpu_remaining` import. The actual iteration limit is given by `vm.max_iter`.
 pub fn execute_single_script<'a>(
     logger: &slog::Logger,
     entity_id: EntityId,
     script_id: ScriptId,
     user_id: Option<UserId>,
+    cpu_budget: u32,
     storage: &'a World,
     vm: &mut VM<'a, ScriptExecutionData>,
 ) -> ExecutionResult {
@@ -136,7 +294,8 @@ pub fn execute_single_script<'a>(
         })?;
 
     vm.logger = logger.clone();
-    let data = prepare_script_data(&logger, entity_id, user_id, storage);
+    let mut data = prepare_script_data(&logger, entity_id, user_id, storage);
+    data.cpu_budget = cpu_budget;
     vm.auxiliary_data = data;
 
     trace!(logger, "Starting script execution");
@@ -179,6 +338,11 @@ pub struct ScriptExecutionData {
     pub entity_id: EntityId,
     pub user_id: Option<UserId>,
     pub intents: BotIntents,
+    /// Remaining CPU budget of the user at the start of the execution
+    pub cpu_budget: u32,
+    /// Seeded by the world seed, the current tick and the entity, so replays of a tick reproduce
+    /// the same numbers
+    pub rng: SmallRng,
     storage: *const World,
     pub logger: slog::Logger,
 }
@@ -193,6 +357,13 @@ impl Display for ScriptExecutionData {
     }
 }
 
+/// Mix the inputs, so neighbouring entities and ticks get unrelated sequences
+fn script_rng_seed(world_seed: u64, time: u64, EntityId(entity_id): EntityId) -> u64 {
+    world_seed
+        ^ time.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (entity_id as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+}
+
 impl ScriptExecutionData {
     /// To be used as a placeholder, do not consume
     pub fn unsafe_default(logger: slog::Logger) -> Self {
@@ -200,6 +371,8 @@ impl ScriptExecutionData {
             entity_id: Default::default(),
             user_id: None,
             intents: Default::default(),
+            cpu_budget: 0,
+            rng: SmallRng::seed_from_u64(0),
             storage: std::ptr::null(),
             logger,
         }
@@ -212,11 +385,20 @@ impl ScriptExecutionData {
         entity_id: EntityId,
         user_id: Option<UserId>,
     ) -> Self {
+        let world_seed = storage
+            .view::<ConfigKey, GameConfig>()
+            .value
+            .as_ref()
+            .map(|conf| conf.world_seed)
+            .unwrap_or(0);
+        let seed = script_rng_seed(world_seed, storage.time(), entity_id);
         Self {
             storage: storage as *const _,
             intents,
             entity_id,
             user_id,
+            cpu_budget: std::u32::MAX,
+            rng: SmallRng::seed_from_u64(seed),
             logger,
         }
     }
@@ -225,3 +407,160 @@ impl ScriptExecutionData {
         unsafe { &*self.storage }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{setup_testing, test_logger};
+    use crate::world::init_inmemory_storage;
+
+    fn insert_script(store: &mut World, cards: &str) -> ScriptId {
+        let cu = format!(
+            r#"{{ "lanes": [ {{ "name": "main", "cards": {} }} ] }}"#,
+            cards
+        );
+        let cu: CompilationUnit = serde_json::from_str(&cu).expect("Failed to parse program");
+        let program = compile(None, cu, CompileOptions::new().with_breadcrumbs(true))
+            .expect("Failed to compile program");
+        let script_id = ScriptId(uuid::Uuid::new_v4());
+        store
+            .unsafe_view::<ScriptId, ScriptComponent>()
+            .insert_or_update(script_id, ScriptComponent(program));
+        script_id
+    }
+
+    fn insert_bot(
+        store: &mut World,
+        owner_id: UserId,
+        script_id: ScriptId,
+    ) -> (EntityId, EntityScript) {
+        let entity_id = store.insert_entity();
+        store
+            .unsafe_view::<EntityId, OwnedEntity>()
+            .insert_or_update(entity_id, OwnedEntity { owner_id });
+        (entity_id, EntityScript(script_id))
+    }
+
+    /// Value logged by `Log Scalar`
+    fn logged_integer(intents: &[BotIntents], entity_id: EntityId) -> u32 {
+        let log = intents
+            .iter()
+            .find(|intent| intent.entity_id == entity_id)
+            .and_then(|intent| intent.log_intent.as_ref())
+            .expect("Expected the script to log");
+        let payload = &log.payload[0];
+        let start = payload.find("Integer(").expect("Expected an integer") + "Integer(".len();
+        let end = start + payload[start..].find(')').unwrap();
+        payload[start..end].parse().unwrap()
+    }
+
+    #[test]
+    fn budget_defers_scripts_to_the_next_tick() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+        store
+            .config
+            .game_config
+            .value
+            .as_mut()
+            .unwrap()
+            .user_cpu_budget = 1;
+
+        let script_id = insert_script(&mut store, r#"[ { "ScalarInt": 1 }, { "Pop": null } ]"#);
+        let user_id = UserId(uuid::Uuid::new_v4());
+        let other_id = UserId(uuid::Uuid::new_v4());
+        let mut workload: Vec<_> = (0..3)
+            .map(|_| insert_bot(&mut store, user_id, script_id))
+            .collect();
+        let other = insert_bot(&mut store, other_id, script_id);
+        workload.push(other);
+
+        // the budget is not enough to finish any script, the first one uses it up
+        execute_scripts(&workload, &mut store);
+        let usage_of = |store: &World, user_id: UserId| {
+            store
+                .view::<UserId, CpuUsage>()
+                .get_by_id(&user_id)
+                .cloned()
+                .unwrap()
+        };
+        let usage = usage_of(&*store, user_id);
+        assert_eq!(usage.used, 1, "{:?}", usage);
+        assert_eq!(usage.skipped, 2);
+        assert_eq!(usage.resume_from, Some(workload[1].0));
+        // budgets are per user
+        let usage = usage_of(&*store, other_id);
+        assert_eq!(usage.used, 1, "{:?}", usage);
+        assert_eq!(usage.skipped, 0);
+
+        execute_scripts(&workload, &mut store);
+        let usage = usage_of(&*store, user_id);
+        assert_eq!(usage.skipped, 2);
+        assert_eq!(usage.resume_from, Some(workload[2].0));
+    }
+
+    #[test]
+    fn scripts_may_not_use_more_than_the_remaining_budget() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+        let conf = store.config.game_config.value.as_mut().unwrap();
+        conf.user_cpu_budget = 10;
+        conf.execution_limit = 1000;
+
+        let cards = vec![r#"{ "ScalarInt": 1 }, { "Pop": null }"#; 30].join(",");
+        let script_id = insert_script(&mut store, &format!("[{}]", cards));
+        let user_id = UserId(uuid::Uuid::new_v4());
+        let workload = vec![insert_bot(&mut store, user_id, script_id)];
+
+        let intents = execute_scripts(&workload, &mut store);
+        assert!(intents.is_empty(), "{:?}", intents);
+
+        let usage = store
+            .view::<UserId, CpuUsage>()
+            .get_by_id(&user_id)
+            .cloned()
+            .unwrap();
+        assert_eq!(usage.used, 10);
+        // running out of budget is not an error of the script
+        assert!(store
+            .view::<ScriptId, ScriptErrorStats>()
+            .get_by_id(&script_id)
+            .is_none());
+    }
+
+    #[test]
+    fn cpu_remaining_counts_executed_cards() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+        let budget = store
+            .config
+            .game_config
+            .value
+            .as_ref()
+            .unwrap()
+            .user_cpu_budget;
+
+        let script_id = insert_script(
+            &mut store,
+            r#"[ { "Call": "CPU Remaining" }, { "Call": "Log Scalar" } ]"#,
+        );
+        let user_id = UserId(uuid::Uuid::new_v4());
+        let workload: Vec<_> = (0..2)
+            .map(|_| insert_bot(&mut store, user_id, script_id))
+            .collect();
+
+        let intents = execute_scripts(&workload, &mut store);
+        let first = logged_integer(&intents, workload[0].0);
+        let second = logged_integer(&intents, workload[1].0);
+        assert!(first < budget, "{} {}", first, budget);
+        // the second script only has what the first one left
+        assert!(second < first, "{} {}", second, first);
+
+        let usage = store
+            .view::<UserId, CpuUsage>()
+            .get_by_id(&user_id)
+            .cloned()
+            .unwrap();
+        assert_eq!(usage.used, 2 * (first - second));
+    }
+}
@@ -1,21 +1,237 @@
+use crate::components::game_config::GameConfig;
 use crate::intents::Intents;
 use crate::prelude::*;
 use crate::profile;
-use crate::storage::views::{UnsafeView, UnwrapViewMut};
+use crate::storage::views::{UnsafeView, UnwrapView, UnwrapViewMut};
+use crate::tables::Table;
+use crate::Time;
 use std::mem;
 
 type Mut = (
     UnwrapViewMut<EmptyKey, Intents<ScriptHistoryEntry>>,
     UnsafeView<EntityId, ScriptHistory>,
+    UnsafeView<UserId, DeadScriptHistories>,
 );
-type Const<'a> = ();
+type Const<'a> = (UnwrapView<'a, ConfigKey, GameConfig>, Time);
 
-pub fn update((mut history_intents, mut history_table): Mut, _: Const) {
+pub fn update(
+    (mut history_intents, mut history_table, mut dead_table): Mut,
+    (config, Time(time)): Const,
+) {
     profile!("ScriptHistorySystem update");
 
+    let capacity = config.script_history_len as usize;
+
     let Intents(intents) = mem::take(&mut *history_intents);
-    history_table.clear();
     for intent in intents {
-        history_table.insert_or_update(intent.entity_id, ScriptHistory(intent.payload));
+        match history_table.get_by_id_mut(&intent.entity_id) {
+            Some(history) => history.push(intent, capacity),
+            None => {
+                let mut history = ScriptHistory::default();
+                let entity_id = intent.entity_id;
+                history.push(intent, capacity);
+                history_table.insert_or_update(entity_id, history);
+            }
+        }
+    }
+
+    // drop the entries that fell out of the window, for entities that did not run this tick
+    let mut expired = Vec::new();
+    for (id, history) in history_table.iter_mut() {
+        match capacity {
+            0 => history.0.clear(),
+            _ => {
+                let oldest = time.saturating_sub(capacity as u64 - 1);
+                while history
+                    .0
+                    .front()
+                    .map(|entry| entry.time < oldest)
+                    .unwrap_or(false)
+                {
+                    history.0.pop_front();
+                }
+            }
+        }
+        if history.0.is_empty() {
+            expired.push(id);
+        }
+    }
+    for id in expired {
+        history_table.delete(&id);
+    }
+
+    // the histories of deleted entities expire as a whole
+    let ttl = config.dead_script_history_ttl as u64;
+    let mut expired = Vec::new();
+    for (user_id, DeadScriptHistories(histories)) in dead_table.iter_mut() {
+        histories.retain(|dead| time < dead.died + ttl);
+        if histories.is_empty() {
+            expired.push(user_id);
+        }
+    }
+    for user_id in expired {
+        dead_table.delete(&user_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::views::{FromWorld, FromWorldMut};
+    use crate::storage::DeferredDeleteById;
+    use crate::world::{init_inmemory_storage, World};
+    use crate::{intents, utils::setup_testing};
+
+    fn set_history_len(store: &mut World, len: u32) {
+        store
+            .config
+            .game_config
+            .value
+            .as_mut()
+            .unwrap()
+            .script_history_len = len;
+    }
+
+    /// Run a tick, with the entities in `running` executing their scripts
+    fn tick(store: &mut World, running: &[EntityId]) {
+        let time = store.time();
+        intents::move_into_storage(
+            store,
+            running
+                .iter()
+                .map(|entity_id| intents::BotIntents {
+                    entity_id: *entity_id,
+                    script_history_intent: Some(ScriptHistoryEntry {
+                        entity_id: *entity_id,
+                        payload: vec![],
+                        time,
+                    }),
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        update(FromWorldMut::new(store), FromWorld::new(&*store));
+        store.post_process();
+    }
+
+    fn history_times(store: &World, entity_id: EntityId) -> Option<Vec<u64>> {
+        store
+            .view::<EntityId, ScriptHistory>()
+            .reborrow()
+            .get_by_id(&entity_id)
+            .map(|history| history.0.iter().map(|e| e.time).collect())
+    }
+
+    #[test]
+    fn history_is_bounded_by_config() {
+        setup_testing();
+        let mut store = init_inmemory_storage(crate::utils::test_logger());
+        store
+            .config
+            .game_config
+            .value
+            .as_mut()
+            .unwrap()
+            .script_history_len = 3;
+
+        let entity_id = store.insert_entity();
+
+        for _ in 0..5 {
+            let time = store.time();
+            intents::move_into_storage(
+                &mut store,
+                vec![intents::BotIntents {
+                    entity_id,
+                    script_history_intent: Some(ScriptHistoryEntry {
+                        entity_id,
+                        payload: vec![],
+                        time,
+                    }),
+                    ..Default::default()
+                }],
+            );
+            update(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
+            store.post_process();
+        }
+
+        let history = store
+            .view::<EntityId, ScriptHistory>()
+            .reborrow()
+            .get_by_id(&entity_id)
+            .cloned()
+            .expect("history to be recorded");
+
+        let times = history.0.iter().map(|e| e.time).collect::<Vec<_>>();
+        assert_eq!(times, vec![2, 3, 4]);
+
+        let times = history.range(3, 10).map(|e| e.time).collect::<Vec<_>>();
+        assert_eq!(times, vec![3, 4]);
+    }
+
+    #[test]
+    fn idle_entity_history_expires() {
+        setup_testing();
+        let mut store = init_inmemory_storage(crate::utils::test_logger());
+        set_history_len(&mut *store, 3);
+
+        let entity_id = store.insert_entity();
+
+        tick(&mut *store, &[entity_id]);
+        tick(&mut *store, &[entity_id]);
+        tick(&mut *store, &[]);
+        assert_eq!(history_times(&*store, entity_id), Some(vec![0, 1]));
+        tick(&mut *store, &[]);
+        assert_eq!(history_times(&*store, entity_id), Some(vec![1]));
+        tick(&mut *store, &[]);
+        assert_eq!(history_times(&*store, entity_id), None);
+    }
+
+    #[test]
+    fn history_is_kept_after_death() {
+        setup_testing();
+        let mut store = init_inmemory_storage(crate::utils::test_logger());
+        store
+            .config
+            .game_config
+            .value
+            .as_mut()
+            .unwrap()
+            .dead_script_history_ttl = 2;
+
+        let owner_id = UserId::default();
+        let entity_id = store.insert_entity();
+        store
+            .unsafe_view::<EntityId, OwnedEntity>()
+            .insert_or_update(entity_id, OwnedEntity { owner_id });
+
+        tick(&mut *store, &[entity_id]);
+        store.deferred_delete(entity_id);
+        tick(&mut *store, &[entity_id]);
+
+        assert_eq!(history_times(&*store, entity_id), None);
+        let times = store
+            .dead_script_history(owner_id, entity_id, 0, 10)
+            .map(|e| e.time)
+            .collect::<Vec<_>>();
+        assert_eq!(times, vec![0, 1]);
+
+        // the entity died at the end of tick 1
+        tick(&mut *store, &[]);
+        assert_eq!(
+            store
+                .dead_script_history(owner_id, entity_id, 0, 10)
+                .count(),
+            2
+        );
+        tick(&mut *store, &[]);
+        assert_eq!(
+            store
+                .dead_script_history(owner_id, entity_id, 0, 10)
+                .count(),
+            0
+        );
+        assert!(!store
+            .view::<UserId, DeadScriptHistories>()
+            .contains(&owner_id));
     }
 }
@@ -0,0 +1,132 @@
+use crate::components::game_config::GameConfig;
+use crate::components::{ScriptComponent, ScriptErrorStats, ScriptVersions};
+use crate::indices::{ConfigKey, ScriptId};
+use crate::profile;
+use crate::storage::views::{UnsafeView, UnwrapView, WorldLogger};
+use crate::tables::Table;
+use slog::{info, trace, warn};
+
+type Mut = (
+    UnsafeView<ScriptId, ScriptComponent>,
+    UnsafeView<ScriptId, ScriptVersions>,
+    UnsafeView<ScriptId, ScriptErrorStats>,
+);
+type Const<'a> = (UnwrapView<'a, ConfigKey, GameConfig>, WorldLogger);
+
+/// Roll back the scripts that fail too often to their last good version
+pub fn update(
+    (mut scripts, mut versions_table, mut stats_table): Mut,
+    (config, WorldLogger(logger)): Const,
+) {
+    profile!("ScriptRollbackSystem update");
+
+    let window = config.script_error_window as usize;
+    let threshold = config.script_rollback_error_percent;
+
+    let failing = stats_table
+        .iter()
+        .filter(|(_, stats)| stats.0.len() >= window)
+        .filter(|(_, stats)| {
+            let (runs, errors) = stats.totals();
+            runs > 0 && errors as u64 * 100 >= threshold as u64 * runs as u64
+        })
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+
+    for script_id in failing {
+        // start observing the script from scratch, whatever happens
+        stats_table.delete(&script_id);
+
+        let versions = match versions_table.get_by_id_mut(&script_id) {
+            Some(v) => v,
+            None => {
+                trace!(logger, "{:?} is failing, but it has no versions", script_id);
+                continue;
+            }
+        };
+        let current = versions.current;
+        if let Some(version) = versions.get_mut(current) {
+            version.failed = true;
+        }
+        let (version, program) = match versions.last_good() {
+            Some(v) => (v.version, v.program.clone()),
+            None => {
+                warn!(
+                    logger,
+                    "{:?} version {} is failing, but there is no version to roll back to",
+                    script_id,
+                    current
+                );
+                continue;
+            }
+        };
+        info!(
+            logger,
+            "{:?} version {} is failing, rolling back to version {}", script_id, current, version
+        );
+        versions.current = version;
+        scripts.insert_or_update(script_id, ScriptComponent(program));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::ScriptRunStats;
+    use crate::indices::UserId;
+    use crate::storage::views::{FromWorld, FromWorldMut};
+    use crate::utils::{setup_testing, test_logger};
+    use crate::world::init_inmemory_storage;
+    use cao_lang::prelude::CompiledProgram;
+
+    #[test]
+    fn failing_script_is_rolled_back() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+        let window = store
+            .config
+            .game_config
+            .value
+            .as_ref()
+            .unwrap()
+            .script_error_window;
+
+        let script_id = ScriptId(uuid::Uuid::new_v4());
+        let mut versions = ScriptVersions::default();
+        versions.push(UserId::default(), 0, CompiledProgram::default(), 8);
+        versions.push(UserId::default(), 1, CompiledProgram::default(), 8);
+        store
+            .unsafe_view::<ScriptId, ScriptVersions>()
+            .insert_or_update(script_id, versions);
+
+        let mut stats = ScriptErrorStats::default();
+        for time in 0..window as u64 {
+            stats.push(
+                ScriptRunStats {
+                    time,
+                    runs: 2,
+                    errors: 2,
+                },
+                window as usize,
+            );
+        }
+        store
+            .unsafe_view::<ScriptId, ScriptErrorStats>()
+            .insert_or_update(script_id, stats);
+
+        update(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
+
+        let versions = store.view::<ScriptId, ScriptVersions>();
+        let versions = versions.get_by_id(&script_id).unwrap();
+        assert_eq!(versions.current, 1);
+        assert!(versions.get(2).unwrap().failed);
+        assert!(store
+            .view::<ScriptId, ScriptComponent>()
+            .get_by_id(&script_id)
+            .is_some());
+        assert!(store
+            .view::<ScriptId, ScriptErrorStats>()
+            .get_by_id(&script_id)
+            .is_none());
+    }
+}
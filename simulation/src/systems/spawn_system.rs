@@ -10,44 +10,77 @@ mod spawn_intent_system;
 pub use continous_spawn_system::update as update_cont_spawns;
 pub use spawn_intent_system::update as update_spawn_intents;
 
-use crate::components::*;
-use crate::indices::{EntityId, UserId};
+use crate::components::{game_config::BotTemplate, game_config::GameConfig, *};
+use crate::indices::{ConfigKey, EntityId, UserId};
 use crate::join;
 use crate::profile;
-use crate::storage::views::{UnsafeView, View, WorldLogger};
+use crate::storage::views::{
+    DeferredDeleteEntityView, FromWorldMut, UnsafeView, UnwrapView, View, WorldLogger,
+};
 use crate::tables::{JoinIterator, Table};
+use crate::world::World;
 use slog::{debug, warn, Logger};
 
 type SpawnSystemMut = (
     UnsafeView<EntityId, SpawnComponent>,
     UnsafeView<EntityId, SpawnQueueComponent>,
     UnsafeView<EntityId, EnergyComponent>,
-    (
-        UnsafeView<EntityId, SpawnBotComponent>,
-        UnsafeView<EntityId, Bot>,
-        UnsafeView<EntityId, HpComponent>,
-        UnsafeView<EntityId, DecayComponent>,
-        UnsafeView<EntityId, CarryComponent>,
-        UnsafeView<EntityId, PositionComponent>,
-        UnsafeView<EntityId, OwnedEntity>,
-        UnsafeView<EntityId, EntityScript>,
-    ),
+    SpawnBotMut,
+);
+
+type SpawnSystemConst<'a> = (
+    WorldLogger,
+    View<'a, UserId, EntityScript>,
+    View<'a, UserId, BotGroups>,
+    UnwrapView<'a, ConfigKey, GameConfig>,
 );
 
 pub fn update_spawns(
     (mut spawns, mut spawn_queue, mut energy, spawn_views): SpawnSystemMut,
-    (WorldLogger(logger), user_default_scripts): (WorldLogger, View<UserId, EntityScript>),
+    (WorldLogger(logger), user_default_scripts, user_groups, conf): SpawnSystemConst,
 ) {
     profile!("SpawnSystem update");
 
+    let SpawnBotMut {
+        spawn_bots,
+        mut delete,
+        ..
+    } = spawn_views;
+
     let ss = spawns.iter_mut().filter(|(_, c)| c.spawning.is_none());
-    let en = energy.iter_mut().filter(|(_, e)| e.energy == e.energy_max);
+    let en = energy.iter_mut();
     let sq = spawn_queue.iter_mut();
-    join!([ss, en, sq]).for_each(|(_spawn_id, (spawn, energy, queue))| {
-        // spawns with 500 energy and no currently spawning bot
-        if let Some(bot) = queue.queue.pop_back() {
-            energy.energy -= 500;
-            spawn.time_to_spawn = 10;
+    join!([ss, en, sq]).for_each(|(spawn_id, (spawn, energy, queue))| {
+        // spawns with no currently spawning bot start the next bot they can afford.
+        // Bots the spawn can not afford yet keep their place, so they do not block cheaper ones.
+        let mut next = None;
+        for i in (0..queue.queue.len()).rev() {
+            let bot = queue.queue[i];
+            let template = spawn_bots
+                .get_by_id(&bot)
+                .and_then(|spawning| conf.bot_templates.get(&spawning.template));
+            match template {
+                Some(template) if template.cost <= energy.energy => {
+                    next = Some((i, bot, template));
+                    break;
+                }
+                Some(_) => {}
+                None => {
+                    warn!(
+                        logger,
+                        "Spawn {:?} dropping bot {:?}, its template does not exist", spawn_id, bot
+                    );
+                    queue.queue.remove(i);
+                    unsafe {
+                        delete.delete_entity(bot);
+                    }
+                }
+            }
+        }
+        if let Some((i, bot, template)) = next {
+            queue.queue.remove(i);
+            energy.energy -= template.cost;
+            spawn.time_to_spawn = template.spawn_time.max(1);
             spawn.spawning = Some(bot);
         }
     });
@@ -71,20 +104,41 @@ pub fn update_spawns(
                 spawn_id,
                 entity_id,
                 spawn_views,
-                user_default_scripts,
+                (user_default_scripts, user_groups, conf),
             )
         });
 }
 
-type SpawnBotMut = (
-    UnsafeView<EntityId, SpawnBotComponent>,
-    UnsafeView<EntityId, Bot>,
-    UnsafeView<EntityId, HpComponent>,
-    UnsafeView<EntityId, DecayComponent>,
-    UnsafeView<EntityId, CarryComponent>,
-    UnsafeView<EntityId, PositionComponent>,
-    UnsafeView<EntityId, OwnedEntity>,
-    UnsafeView<EntityId, EntityScript>,
+/// Views of the bots in spawn queues
+#[derive(Clone, Copy)]
+pub struct SpawnBotMut {
+    spawn_bots: UnsafeView<EntityId, SpawnBotComponent>,
+    body: BotBodyMut,
+    positions: UnsafeView<EntityId, PositionComponent>,
+    owned: UnsafeView<EntityId, OwnedEntity>,
+    scripts: UnsafeView<EntityId, EntityScript>,
+    groups: UnsafeView<EntityId, BotGroup>,
+    delete: DeferredDeleteEntityView,
+}
+
+impl FromWorldMut for SpawnBotMut {
+    fn new(w: &mut World) -> Self {
+        Self {
+            spawn_bots: FromWorldMut::new(w),
+            body: FromWorldMut::new(w),
+            positions: FromWorldMut::new(w),
+            owned: FromWorldMut::new(w),
+            scripts: FromWorldMut::new(w),
+            groups: FromWorldMut::new(w),
+            delete: FromWorldMut::new(w),
+        }
+    }
+}
+
+type SpawnBotConst<'a> = (
+    View<'a, UserId, EntityScript>,
+    View<'a, UserId, BotGroups>,
+    UnwrapView<'a, ConfigKey, GameConfig>,
 );
 
 /// Spawns a bot from a spawn.
@@ -93,72 +147,364 @@ fn spawn_bot(
     logger: &Logger,
     spawn_id: EntityId,
     entity_id: EntityId,
-    (
+    SpawnBotMut {
         mut spawn_bots,
-        mut bots,
-        mut hps,
-        mut decay,
-        mut carry,
+        body,
         mut positions,
         mut owned,
-        mut script_table,
-    ): SpawnBotMut,
-    user_default_scripts: View<UserId, EntityScript>,
+        mut scripts,
+        mut groups,
+        mut delete,
+    }: SpawnBotMut,
+    (user_default_scripts, user_groups, conf): SpawnBotConst,
 ) {
     debug!(
         logger,
         "spawn_bot spawn_id: {:?} entity_id: {:?}", spawn_id, entity_id
     );
 
-    match spawn_bots.delete(&entity_id) {
-        Some(_) => (),
+    let spawning = match spawn_bots.delete(&entity_id) {
+        Some(x) => x,
         None => {
             warn!(logger, "Spawning bot {:?} was not found", entity_id);
             return;
         }
     };
 
+    let template: &BotTemplate = match conf.bot_templates.get(&spawning.template) {
+        Some(x) => x,
+        None => {
+            warn!(
+                logger,
+                "Template {:?} of spawning bot {:?} was not found", spawning.template, entity_id
+            );
+            unsafe {
+                delete.delete_entity(entity_id);
+            }
+            return;
+        }
+    };
+
+    insert_bot_body(entity_id, template, body);
+
+    let pos = positions
+        .get_by_id(&spawn_id)
+        .cloned()
+        .expect("Spawn should have position");
+    positions.insert_or_update(entity_id, pos);
+
+    let owner = owned.get_by_id(&spawn_id).cloned();
+    if let Some(owner) = owner {
+        // group members run the group's script, others the user's default
+        let group_script = spawning.group.as_ref().and_then(|group| {
+            user_groups
+                .get_by_id(&owner.owner_id)
+                .and_then(|BotGroups(groups)| groups.get(group))
+        });
+        let script = group_script
+            .map(|script_id| EntityScript(*script_id))
+            .or_else(|| user_default_scripts.get_by_id(&owner.owner_id).copied());
+        if let Some(script) = script {
+            scripts.insert_or_update(entity_id, script);
+        }
+        if let Some(group) = spawning.group {
+            groups.insert_or_update(entity_id, BotGroup(group));
+        }
+
+        owned.insert_or_update(entity_id, owner);
+    }
+
+    debug!(
+        logger,
+        "spawn_bot spawn_id: {:?} entity_id: {:?} - done", spawn_id, entity_id
+    );
+}
+
+pub(crate) type BotBodyMut = (
+    UnsafeView<EntityId, Bot>,
+    UnsafeView<EntityId, HpComponent>,
+    UnsafeView<EntityId, DecayComponent>,
+    UnsafeView<EntityId, CarryComponent>,
+    UnsafeView<EntityId, MeleeAttackComponent>,
+    UnsafeView<EntityId, RangedAttackComponent>,
+    UnsafeView<EntityId, ArmourComponent>,
+    UnsafeView<EntityId, HealComponent>,
+    UnsafeView<EntityId, FatigueComponent>,
+);
+
+/// Insert the body of a bot built by the template.
+/// Position, owner and script are left to the caller.
+pub(crate) fn insert_bot_body(
+    entity_id: EntityId,
+    template: &BotTemplate,
+    (
+        mut bots,
+        mut hps,
+        mut decay,
+        mut carry,
+        mut melee,
+        mut ranged,
+        mut armour,
+        mut heals,
+        mut fatigue,
+    ): BotBodyMut,
+) {
     bots.insert(entity_id);
     hps.insert_or_update(
         entity_id,
         HpComponent {
-            hp: 100,
-            hp_max: 100,
+            hp: template.hp,
+            hp_max: template.hp,
         },
     );
     decay.insert_or_update(
         entity_id,
         DecayComponent {
-            interval: 20,
-            time_remaining: 20,
-            hp_amount: 100,
+            interval: template.decay_interval,
+            time_remaining: template.decay_interval,
+            hp_amount: template.decay_amount,
         },
     );
-    carry.insert_or_update(
+    carry.insert_or_update(entity_id, CarryComponent::new(template.carry_max));
+    if let Some(strength) = template.melee_strength {
+        melee.insert_or_update(
+            entity_id,
+            MeleeAttackComponent {
+                strength,
+                ..Default::default()
+            },
+        );
+    }
+    if let Some(ranged_attack) = template.ranged_attack {
+        ranged.insert_or_update(entity_id, ranged_attack);
+    }
+    if let Some(ref armour_component) = template.armour {
+        armour.insert_or_update(entity_id, armour_component.clone());
+    }
+    if let Some(strength) = template.heal_strength {
+        heals.insert_or_update(entity_id, HealComponent { strength });
+    }
+    fatigue.insert_or_update(
         entity_id,
-        CarryComponent {
-            carry: 0,
-            carry_max: 50,
+        FatigueComponent {
+            fatigue: 0,
+            recovery: template.fatigue_recovery,
         },
     );
+}
 
-    let pos = positions
-        .get_by_id(&spawn_id)
-        .cloned()
-        .expect("Spawn should have position");
-    positions.insert_or_update(entity_id, pos);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::game_config::DEFAULT_BOT_TEMPLATE;
+    use crate::indices::ScriptId;
+    use crate::storage::views::{FromWorld, FromWorldMut};
+    use crate::utils::{setup_testing, test_logger};
+    use crate::world::{init_inmemory_storage, World};
 
-    let owner = owned.get_by_id(&spawn_id).cloned();
-    if let Some(owner) = owner {
-        if let Some(script) = user_default_scripts.get_by_id(&owner.owner_id) {
-            script_table.insert_or_update(entity_id, *script);
+    fn insert_spawn(store: &mut World, energy: u16) -> EntityId {
+        let spawn_id = store.insert_entity();
+        store
+            .unsafe_view::<EntityId, SpawnComponent>()
+            .insert_or_update(spawn_id, SpawnComponent::default());
+        store
+            .unsafe_view::<EntityId, SpawnQueueComponent>()
+            .insert_or_update(spawn_id, SpawnQueueComponent::default());
+        store
+            .unsafe_view::<EntityId, EnergyComponent>()
+            .insert_or_update(
+                spawn_id,
+                EnergyComponent {
+                    energy,
+                    energy_max: 500,
+                },
+            );
+        store
+            .unsafe_view::<EntityId, PositionComponent>()
+            .insert_or_update(spawn_id, PositionComponent::default());
+        spawn_id
+    }
+
+    fn queue_bot(
+        store: &mut World,
+        spawn_id: EntityId,
+        template: &str,
+        group: Option<&str>,
+    ) -> EntityId {
+        let bot_id = store.insert_entity();
+        store
+            .unsafe_view::<EntityId, SpawnQueueComponent>()
+            .get_by_id_mut(&spawn_id)
+            .unwrap()
+            .queue
+            .push_back(bot_id);
+        store
+            .unsafe_view::<EntityId, SpawnBotComponent>()
+            .insert_or_update(
+                bot_id,
+                SpawnBotComponent {
+                    bot: Bot {},
+                    template: template.to_owned(),
+                    group: group.map(|g| g.to_owned()),
+                },
+            );
+        bot_id
+    }
+
+    fn spawn_time(store: &World, template: &str) -> i16 {
+        store
+            .view::<ConfigKey, GameConfig>()
+            .unwrap_value()
+            .bot_templates[template]
+            .spawn_time
+    }
+
+    #[test]
+    fn spawns_bot_by_template() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+
+        let spawn_id = insert_spawn(&mut *store, 500);
+        let bot_id = queue_bot(&mut *store, spawn_id, "warrior", None);
+
+        for _ in 0..spawn_time(&*store, "warrior") {
+            assert!(!store.view::<EntityId, Bot>().contains_id(&bot_id));
+            update_spawns(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
         }
 
-        owned.insert_or_update(entity_id, owner);
+        assert!(store.view::<EntityId, Bot>().contains_id(&bot_id));
+        let energy = store
+            .view::<EntityId, EnergyComponent>()
+            .get_by_id(&spawn_id)
+            .copied()
+            .unwrap();
+        assert_eq!(energy.energy, 0);
+        let hp = store
+            .view::<EntityId, HpComponent>()
+            .get_by_id(&bot_id)
+            .copied()
+            .unwrap();
+        assert_eq!(hp.hp, 200);
+        let melee = store
+            .view::<EntityId, MeleeAttackComponent>()
+            .get_by_id(&bot_id)
+            .copied()
+            .expect("Expected the warrior to have a melee attack");
+        assert_eq!(melee.strength, 20);
     }
 
-    debug!(
-        logger,
-        "spawn_bot spawn_id: {:?} entity_id: {:?} - done", spawn_id, entity_id
-    );
+    #[test]
+    fn spawned_group_members_run_the_group_script() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+        let user_id = UserId::default();
+        let default_script = ScriptId(uuid::Uuid::new_v4());
+        let group_script = ScriptId(uuid::Uuid::new_v4());
+        store
+            .unsafe_view::<UserId, EntityScript>()
+            .insert_or_update(user_id, EntityScript(default_script));
+        let mut groups = BotGroups::default();
+        groups.0.insert("miners".to_owned(), group_script);
+        store
+            .unsafe_view::<UserId, BotGroups>()
+            .insert_or_update(user_id, groups);
+
+        let spawn_id = insert_spawn(&mut *store, 500);
+        store
+            .unsafe_view::<EntityId, OwnedEntity>()
+            .insert_or_update(spawn_id, OwnedEntity { owner_id: user_id });
+        let bot_id = queue_bot(&mut *store, spawn_id, DEFAULT_BOT_TEMPLATE, Some("miners"));
+
+        for _ in 0..spawn_time(&*store, DEFAULT_BOT_TEMPLATE) {
+            update_spawns(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
+        }
+
+        assert!(store.view::<EntityId, Bot>().contains_id(&bot_id));
+        assert_eq!(
+            store
+                .view::<EntityId, EntityScript>()
+                .get_by_id(&bot_id)
+                .map(|EntityScript(id)| *id),
+            Some(group_script)
+        );
+        assert_eq!(
+            store
+                .view::<EntityId, BotGroup>()
+                .get_by_id(&bot_id)
+                .map(|BotGroup(g)| g.as_str()),
+            Some("miners")
+        );
+    }
+
+    #[test]
+    fn unaffordable_bots_do_not_block_the_queue() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+        let mut expensive = store
+            .view::<ConfigKey, GameConfig>()
+            .unwrap_value()
+            .bot_templates[DEFAULT_BOT_TEMPLATE]
+            .clone();
+        expensive.cost = 1000;
+        store
+            .config
+            .game_config
+            .value
+            .as_mut()
+            .unwrap()
+            .bot_templates
+            .insert("expensive".to_owned(), expensive);
+
+        let spawn_id = insert_spawn(&mut *store, 500);
+        let cheap_id = queue_bot(&mut *store, spawn_id, DEFAULT_BOT_TEMPLATE, None);
+        // the queue is served from the back
+        let expensive_id = queue_bot(&mut *store, spawn_id, "expensive", None);
+
+        update_spawns(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
+
+        let spawn = store
+            .view::<EntityId, SpawnComponent>()
+            .get_by_id(&spawn_id)
+            .cloned()
+            .unwrap();
+        assert_eq!(spawn.spawning, Some(cheap_id));
+        let queue = store
+            .view::<EntityId, SpawnQueueComponent>()
+            .get_by_id(&spawn_id)
+            .cloned()
+            .unwrap();
+        assert_eq!(
+            queue.queue.iter().copied().collect::<Vec<_>>(),
+            vec![expensive_id]
+        );
+        assert!(store
+            .view::<EntityId, SpawnBotComponent>()
+            .contains(&expensive_id));
+    }
+
+    #[test]
+    fn bots_of_unknown_templates_are_deleted() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+
+        let spawn_id = insert_spawn(&mut *store, 500);
+        let bot_id = queue_bot(&mut *store, spawn_id, "nope", None);
+        store
+            .unsafe_view::<EntityId, OwnedEntity>()
+            .insert_or_update(bot_id, OwnedEntity::default());
+
+        update_spawns(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
+        store.post_process();
+
+        let queue = store
+            .view::<EntityId, SpawnQueueComponent>()
+            .get_by_id(&spawn_id)
+            .cloned()
+            .unwrap();
+        assert!(queue.queue.is_empty());
+        assert!(!store
+            .view::<EntityId, SpawnBotComponent>()
+            .contains(&bot_id));
+        assert!(!store.view::<EntityId, OwnedEntity>().contains_id(&bot_id));
+    }
 }
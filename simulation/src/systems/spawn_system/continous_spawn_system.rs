@@ -1,7 +1,7 @@
 //! Keeps adding intents
 //!
 
-use crate::components::*;
+use crate::components::{game_config::DEFAULT_BOT_TEMPLATE, *};
 use crate::indices::{EmptyKey, EntityId};
 use crate::intents::{Intents, SpawnIntent};
 use crate::join;
@@ -35,6 +35,8 @@ pub fn update(
         intents.0.push(SpawnIntent {
             spawn_id,
             owner_id: Some(owner.owner_id),
+            template: DEFAULT_BOT_TEMPLATE.to_owned(),
+            group: None,
         });
     }
 }
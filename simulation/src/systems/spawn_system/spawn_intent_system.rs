@@ -1,6 +1,6 @@
 use crate::components::{Bot, OwnedEntity, SpawnBotComponent, SpawnQueueComponent};
 use crate::indices::*;
-use crate::intents::{Intents, SpawnIntent};
+use crate::intents::{Intents, SpawnIntent, SPAWN_QUEUE_LEN};
 use crate::profile;
 use crate::storage::views::{InsertEntityView, UnsafeView, UnwrapView, WorldLogger};
 use slog::{debug, o, trace};
@@ -31,14 +31,20 @@ pub fn update(
                 continue;
             }
         };
-        if spawn.queue.len() > 20 {
-            // TODO: config
+        if spawn.queue.len() >= SPAWN_QUEUE_LEN {
             debug!(logger, "spawn queue is full");
             continue;
         }
 
         let bot_id = unsafe { insert_entity.insert_entity() };
-        spawn_bot_table.insert_or_update(bot_id, SpawnBotComponent { bot: Bot {} });
+        spawn_bot_table.insert_or_update(
+            bot_id,
+            SpawnBotComponent {
+                bot: Bot {},
+                template: intent.template.clone(),
+                group: intent.group.clone(),
+            },
+        );
         if let Some(owner_id) = intent.owner_id {
             owner_table.insert_or_update(bot_id, OwnedEntity { owner_id });
         }
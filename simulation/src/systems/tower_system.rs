@@ -0,0 +1,192 @@
+//! Towers shoot the closest hostile bot in their range, if they have the energy for it.
+//! Their shots deal kinetic damage.
+//!
+use crate::combat::mitigate_damage;
+use crate::components::{
+    ArmourComponent, Bot, DamageType, DeathCause, EnergyComponent, HpComponent,
+    LastDamageComponent, OwnedEntity, PositionComponent, TowerComponent,
+};
+use crate::indices::EntityId;
+use crate::profile;
+use crate::storage::views::{UnsafeView, View, WorldLogger};
+use crate::tables::Table;
+use slog::trace;
+
+type Mut = (
+    UnsafeView<EntityId, EnergyComponent>,
+    UnsafeView<EntityId, HpComponent>,
+    UnsafeView<EntityId, LastDamageComponent>,
+);
+type Const<'a> = (
+    View<'a, EntityId, TowerComponent>,
+    View<'a, EntityId, Bot>,
+    View<'a, EntityId, PositionComponent>,
+    View<'a, EntityId, OwnedEntity>,
+    View<'a, EntityId, ArmourComponent>,
+    WorldLogger,
+);
+
+pub fn update(
+    (mut energy, mut hps, mut last_damage): Mut,
+    (towers, bots, positions, owners, armour, WorldLogger(logger)): Const,
+) {
+    profile!("TowerSystem update");
+
+    let targets = positions
+        .iter()
+        .filter(|(id, _)| bots.contains_id(id))
+        .map(|(id, PositionComponent(pos))| {
+            let owner = owners.get_by_id(&id).map(|o| o.owner_id);
+            (id, *pos, owner)
+        })
+        .collect::<Vec<_>>();
+
+    for (tower_id, tower) in towers.iter() {
+        let tower_energy = match energy.get_by_id_mut(&tower_id) {
+            Some(e) if e.energy >= tower.energy_per_shot => e,
+            _ => continue,
+        };
+        let PositionComponent(tower_pos) = match positions.get_by_id(&tower_id) {
+            Some(p) => *p,
+            None => continue,
+        };
+        let tower_owner = owners.get_by_id(&tower_id).map(|o| o.owner_id);
+
+        let target = targets
+            .iter()
+            .filter(|(_, pos, owner)| {
+                *owner != tower_owner
+                    && pos.room == tower_pos.room
+                    && pos.pos.hex_distance(tower_pos.pos) <= tower.range
+            })
+            .min_by_key(|(id, pos, _)| (pos.pos.hex_distance(tower_pos.pos), *id));
+        let (target_id, _, _) = match target {
+            Some(t) => t,
+            None => continue,
+        };
+        let hp = match hps.get_by_id_mut(target_id) {
+            Some(hp) => hp,
+            None => continue,
+        };
+        let damage = mitigate_damage(
+            tower.damage,
+            DamageType::Kinetic,
+            armour.get_by_id(target_id),
+        );
+        trace!(
+            logger,
+            "Tower {:?} shoots {:?} for {} damage",
+            tower_id,
+            target_id,
+            damage
+        );
+        tower_energy.energy -= tower.energy_per_shot;
+        hp.hp = hp.hp.saturating_sub(damage);
+        last_damage.insert_or_update(*target_id, LastDamageComponent(DeathCause::Tower));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indices::{UserId, WorldPosition};
+    use crate::prelude::Axial;
+    use crate::storage::views::{FromWorld, FromWorldMut};
+    use crate::utils::{setup_testing, test_logger};
+    use crate::world::init_inmemory_storage;
+
+    #[test]
+    fn shoots_closest_hostile_bot() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+
+        let owner = UserId::default();
+        let enemy = UserId(uuid::Uuid::new_v4());
+        let at = |q, r| {
+            PositionComponent(WorldPosition {
+                room: Axial::new(0, 0),
+                pos: Axial::new(q, r),
+            })
+        };
+
+        let tower_id = store.insert_entity();
+        let friend_id = store.insert_entity();
+        let near_id = store.insert_entity();
+        let far_id = store.insert_entity();
+
+        store
+            .unsafe_view::<EntityId, TowerComponent>()
+            .insert_or_update(
+                tower_id,
+                TowerComponent {
+                    range: 5,
+                    damage: 10,
+                    energy_per_shot: 10,
+                },
+            );
+        store
+            .unsafe_view::<EntityId, EnergyComponent>()
+            .insert_or_update(
+                tower_id,
+                EnergyComponent {
+                    energy: 15,
+                    energy_max: 100,
+                },
+            );
+        for (id, pos, owner_id) in [
+            (tower_id, at(5, 5), owner),
+            (friend_id, at(5, 6), owner),
+            (near_id, at(5, 7), enemy),
+            (far_id, at(5, 8), enemy),
+        ]
+        .iter()
+        {
+            store
+                .unsafe_view::<EntityId, PositionComponent>()
+                .insert_or_update(*id, *pos);
+            store
+                .unsafe_view::<EntityId, OwnedEntity>()
+                .insert_or_update(
+                    *id,
+                    OwnedEntity {
+                        owner_id: *owner_id,
+                    },
+                );
+            if *id != tower_id {
+                store.unsafe_view::<EntityId, Bot>().insert(*id);
+                store
+                    .unsafe_view::<EntityId, HpComponent>()
+                    .insert_or_update(
+                        *id,
+                        HpComponent {
+                            hp: 100,
+                            hp_max: 100,
+                        },
+                    );
+            }
+        }
+
+        // the tower only has energy for a single shot
+        update(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
+        update(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
+
+        let hp = |id| {
+            store
+                .view::<EntityId, HpComponent>()
+                .get_by_id(&id)
+                .map(|hp| hp.hp)
+                .unwrap()
+        };
+        assert_eq!(hp(friend_id), 100);
+        assert_eq!(hp(near_id), 90);
+        assert_eq!(hp(far_id), 100);
+        assert_eq!(
+            store
+                .view::<EntityId, EnergyComponent>()
+                .get_by_id(&tower_id)
+                .unwrap()
+                .energy,
+            5
+        );
+    }
+}
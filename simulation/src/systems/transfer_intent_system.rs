@@ -0,0 +1,209 @@
+//! Moves resources between bots, and between bots and resource piles.
+//!
+use super::pile_system::{drop_resources, DropResourcesMut};
+use super::record_outcome;
+use crate::components::{IntentOutcomes, PositionComponent};
+use crate::indices::*;
+use crate::intents::*;
+use crate::profile;
+use crate::scripting_api::OperationResult;
+use crate::storage::views::{UnsafeView, UnwrapView, WorldLogger};
+use slog::{o, trace, warn};
+
+type Mut = (UnsafeView<EntityId, IntentOutcomes>, DropResourcesMut);
+type Const<'a> = (
+    UnwrapView<'a, EmptyKey, Intents<TransferIntent>>,
+    UnwrapView<'a, EmptyKey, Intents<DropIntent>>,
+    UnwrapView<'a, EmptyKey, Intents<PickupIntent>>,
+    WorldLogger,
+);
+
+pub fn update(
+    (mut outcomes, drop_views): Mut,
+    (transfer_intents, drop_intents, pickup_intents, WorldLogger(logger)): Const,
+) {
+    profile!("TransferSystem update");
+
+    let DropResourcesMut {
+        mut carry_table,
+        positions,
+        ..
+    } = drop_views;
+
+    for intent in transfer_intents.iter() {
+        let logger = logger.new(o!("entity"=>intent.bot.0));
+        trace!(logger, "Executing transfer intent {:?}", intent);
+
+        let carried = carry_table
+            .get_by_id(&intent.bot)
+            .map(|c| c.get(intent.ty))
+            .unwrap_or(0);
+        let res = match carry_table.get_by_id_mut(&intent.target) {
+            Some(target) => {
+                let amount = target.add(intent.ty, intent.amount.min(carried));
+                if let Some(carry) = carry_table.get_by_id_mut(&intent.bot) {
+                    carry.take(intent.ty, amount);
+                }
+                if amount > 0 || intent.amount == 0 {
+                    OperationResult::Ok
+                } else if carried == 0 {
+                    OperationResult::Empty
+                } else {
+                    OperationResult::Full
+                }
+            }
+            None => {
+                warn!(logger, "Target has no carry");
+                OperationResult::InvalidTarget
+            }
+        };
+        record_outcome(&mut outcomes, intent.bot, |o| o.transfer_intent = Some(res));
+    }
+
+    for intent in drop_intents.iter() {
+        let logger = logger.new(o!("entity"=>intent.bot.0));
+        trace!(logger, "Executing drop intent {:?}", intent);
+
+        let pos = positions
+            .get_by_id(&intent.bot)
+            .map(|PositionComponent(p)| *p);
+        let res = match (carry_table.get_by_id_mut(&intent.bot), pos) {
+            (Some(carry), Some(pos)) => {
+                let amount = carry.take(intent.ty, intent.amount);
+                if amount > 0 {
+                    drop_resources(pos, Some((intent.ty, amount)), drop_views);
+                    OperationResult::Ok
+                } else {
+                    OperationResult::Empty
+                }
+            }
+            _ => {
+                warn!(logger, "Bot has no carry or position");
+                OperationResult::InvalidInput
+            }
+        };
+        record_outcome(&mut outcomes, intent.bot, |o| o.drop_intent = Some(res));
+    }
+
+    for intent in pickup_intents.iter() {
+        let logger = logger.new(o!("entity"=>intent.bot.0));
+        trace!(logger, "Executing pickup intent {:?}", intent);
+
+        let pile = match carry_table.get_by_id(&intent.pile) {
+            Some(pile) => pile.contents.clone(),
+            None => {
+                // the pile may have been emptied by another bot in this tick
+                record_outcome(&mut outcomes, intent.bot, |o| {
+                    o.pickup_intent = Some(OperationResult::InvalidTarget)
+                });
+                continue;
+            }
+        };
+        let picked_up = match carry_table.get_by_id_mut(&intent.bot) {
+            Some(carry) => pile
+                .into_iter()
+                .map(|(ty, amount)| (ty, carry.add(ty, amount)))
+                .collect::<Vec<_>>(),
+            None => {
+                warn!(logger, "Bot has no carry");
+                record_outcome(&mut outcomes, intent.bot, |o| {
+                    o.pickup_intent = Some(OperationResult::InvalidInput)
+                });
+                continue;
+            }
+        };
+        let mut total = 0;
+        if let Some(pile) = carry_table.get_by_id_mut(&intent.pile) {
+            for (ty, amount) in picked_up {
+                total += pile.take(ty, amount);
+            }
+        }
+        let res = if total > 0 {
+            OperationResult::Ok
+        } else {
+            OperationResult::Full
+        };
+        record_outcome(&mut outcomes, intent.bot, |o| o.pickup_intent = Some(res));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{CarryComponent, Resource, ResourcePileComponent};
+    use crate::storage::views::{FromWorld, FromWorldMut};
+    use crate::utils::{setup_testing, test_logger};
+    use crate::world::init_inmemory_storage;
+
+    #[test]
+    fn dropped_resources_can_be_picked_up() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+
+        let dropper = store.insert_entity();
+        let picker = store.insert_entity();
+
+        let mut carry = CarryComponent::new(50);
+        carry.add(Resource::Crystal, 30);
+        store
+            .unsafe_view::<EntityId, CarryComponent>()
+            .insert_or_update(dropper, carry);
+        store
+            .unsafe_view::<EntityId, CarryComponent>()
+            .insert_or_update(picker, CarryComponent::new(20));
+        for id in [dropper, picker].iter() {
+            store
+                .unsafe_view::<EntityId, PositionComponent>()
+                .insert_or_update(*id, PositionComponent::default());
+        }
+
+        move_into_storage(
+            &mut *store,
+            vec![BotIntents {
+                drop_intent: Some(DropIntent {
+                    bot: dropper,
+                    amount: 30,
+                    ty: Resource::Crystal,
+                }),
+                ..Default::default()
+            }],
+        );
+        update(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
+
+        let pile = store
+            .view::<EntityId, ResourcePileComponent>()
+            .iter()
+            .map(|(id, _)| id)
+            .next()
+            .expect("pile to be created");
+
+        move_into_storage(
+            &mut *store,
+            vec![BotIntents {
+                pickup_intent: Some(PickupIntent { bot: picker, pile }),
+                ..Default::default()
+            }],
+        );
+        update(FromWorldMut::new(&mut *store), FromWorld::new(&*store));
+
+        let carry_table = store.view::<EntityId, CarryComponent>();
+        assert_eq!(
+            carry_table
+                .get_by_id(&dropper)
+                .unwrap()
+                .get(Resource::Crystal),
+            0
+        );
+        assert_eq!(
+            carry_table
+                .get_by_id(&picker)
+                .unwrap()
+                .get(Resource::Crystal),
+            20
+        );
+        assert_eq!(
+            carry_table.get_by_id(&pile).unwrap().get(Resource::Crystal),
+            10
+        );
+    }
+}
@@ -0,0 +1,47 @@
+//! Records the rooms seen by each user, see `crate::visibility`.
+//!
+use crate::components::{Bot, ExploredRooms, OwnedEntity, PositionComponent, StructureKind};
+use crate::indices::{EntityId, Room, UserId};
+use crate::profile;
+use crate::storage::views::{UnsafeView, View, WorldLogger};
+use crate::visibility::vision_range;
+use slog::trace;
+use std::collections::BTreeSet;
+
+type Const<'a> = (
+    View<'a, Room, OwnedEntity>,
+    View<'a, EntityId, OwnedEntity>,
+    View<'a, EntityId, PositionComponent>,
+    View<'a, EntityId, Bot>,
+    View<'a, EntityId, StructureKind>,
+    WorldLogger,
+);
+
+pub fn update(
+    mut explored: UnsafeView<UserId, ExploredRooms>,
+    (room_owners, owners, positions, bots, kinds, WorldLogger(logger)): Const,
+) {
+    profile!("VisibilitySystem update");
+
+    let owned_rooms = room_owners
+        .iter()
+        .map(|(room, OwnedEntity { owner_id })| (*owner_id, room));
+    let seen_rooms = owners.iter().filter_map(|(id, OwnedEntity { owner_id })| {
+        vision_range(bots.contains_id(&id), kinds.get_by_id(&id).copied())?;
+        let PositionComponent(pos) = positions.get_by_id(&id)?;
+        Some((*owner_id, Room(pos.room)))
+    });
+    // collect first, most users see the same rooms tick after tick
+    let seen: BTreeSet<(UserId, Room)> = owned_rooms.chain(seen_rooms).collect();
+    for (user_id, room) in seen {
+        if !explored.contains(&user_id) {
+            explored.insert_or_update(user_id, ExploredRooms::default());
+        }
+        let ExploredRooms(rooms) = explored
+            .get_by_id_mut(&user_id)
+            .expect("explored rooms to be inserted");
+        if rooms.insert(room) {
+            trace!(logger, "User {:?} explored room {:?}", user_id, room);
+        }
+    }
+}
@@ -1,4 +1,6 @@
+use cao_lang::prelude::Scalar;
 use serde_derive::{Deserialize, Serialize};
+use std::convert::TryFrom;
 
 #[derive(Debug, Clone, Eq, PartialEq, Copy, Serialize, Deserialize)]
 #[repr(u8)]
@@ -8,6 +10,8 @@ pub enum TileTerrainType {
     /// allows teleporting to new rooms
     Bridge,
     Wall,
+    /// Walkable, but slows bots down
+    Swamp,
 }
 
 impl Default for TileTerrainType {
@@ -16,12 +20,51 @@ impl Default for TileTerrainType {
     }
 }
 
+impl TryFrom<Scalar> for TileTerrainType {
+    type Error = Scalar;
+    fn try_from(s: Scalar) -> Result<TileTerrainType, Scalar> {
+        let tile = match s {
+            Scalar::Integer(0) => TileTerrainType::Plain,
+            Scalar::Integer(1) => TileTerrainType::Bridge,
+            Scalar::Integer(2) => TileTerrainType::Wall,
+            Scalar::Integer(3) => TileTerrainType::Swamp,
+            _ => return Err(s),
+        };
+        Ok(tile)
+    }
+}
+
+/// Fatigue a bot gains by moving onto a road, regardless of the terrain beneath it
+pub const ROAD_MOVE_COST: u16 = 1;
+
 impl TileTerrainType {
     pub fn is_walkable(self) -> bool {
         is_walkable(self)
     }
+
+    /// Fatigue a bot gains by moving onto this tile
+    pub fn move_cost(self) -> u16 {
+        match self {
+            TileTerrainType::Plain | TileTerrainType::Bridge => 2,
+            TileTerrainType::Swamp => 6,
+            // can not be entered
+            TileTerrainType::Wall => 0,
+        }
+    }
 }
 
 pub fn is_walkable(tile: TileTerrainType) -> bool {
-    matches!(tile, TileTerrainType::Plain | TileTerrainType::Bridge)
+    matches!(
+        tile,
+        TileTerrainType::Plain | TileTerrainType::Bridge | TileTerrainType::Swamp
+    )
+}
+
+/// Fatigue a bot gains by moving onto a tile, roads are cheaper than any terrain
+pub fn move_cost(tile: TileTerrainType, has_road: bool) -> u16 {
+    if has_road {
+        ROAD_MOVE_COST
+    } else {
+        tile.move_cost()
+    }
 }
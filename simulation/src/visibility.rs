@@ -0,0 +1,262 @@
+//! Fog of war.
+//!
+//! Users see the rooms they own as a whole, and the positions in vision range of their bots and
+//! structures. Vision does not cross room borders.
+//!
+//! Rooms seen at least once are explored, see `ExploredRooms`.
+//!
+use crate::components::{Bot, EntityComponent, OwnedEntity, PositionComponent, StructureKind};
+use crate::geometry::Axial;
+use crate::indices::{EntityId, Room, UserId, WorldPosition};
+use crate::storage::views::View;
+use crate::tables::morton::MortonTable;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+pub const BOT_VISION: u32 = 5;
+pub const STRUCTURE_VISION: u32 = 3;
+pub const TOWER_VISION: u32 = 7;
+/// The largest vision range of any entity
+pub const MAX_VISION: u32 = TOWER_VISION;
+
+/// Vision range of an entity, `None` if it does not see
+pub fn vision_range(is_bot: bool, kind: Option<StructureKind>) -> Option<u32> {
+    match kind {
+        Some(StructureKind::Tower) => Some(TOWER_VISION),
+        Some(StructureKind::Road) => None,
+        Some(_) => Some(STRUCTURE_VISION),
+        None if is_bot => Some(BOT_VISION),
+        None => None,
+    }
+}
+
+pub type VisibilityInput<'a> = (
+    View<'a, Room, OwnedEntity>,
+    View<'a, EntityId, OwnedEntity>,
+    View<'a, EntityId, PositionComponent>,
+    View<'a, EntityId, Bot>,
+    View<'a, EntityId, StructureKind>,
+);
+
+/// What a single user sees
+#[derive(Debug, Clone, Default)]
+pub struct Visibility {
+    /// Rooms seen as a whole
+    pub rooms: BTreeSet<Room>,
+    /// Vision ranges of the entities of the user by their position, grouped by room
+    pub viewers: BTreeMap<Room, MortonTable<Axial, u32>>,
+}
+
+impl Visibility {
+    fn new(rooms: BTreeSet<Room>, viewers: Vec<(WorldPosition, u32)>) -> Self {
+        let mut by_room = BTreeMap::<Room, Vec<_>>::new();
+        for (pos, range) in viewers {
+            by_room
+                .entry(Room(pos.room))
+                .or_default()
+                .push((pos.pos, range));
+        }
+        let viewers = by_room
+            .into_iter()
+            .filter_map(|(room, viewers)| Some((room, MortonTable::from_vec(viewers).ok()?)))
+            .collect();
+        Self { rooms, viewers }
+    }
+
+    pub fn is_visible(&self, pos: WorldPosition) -> bool {
+        if self.rooms.contains(&Room(pos.room)) {
+            return true;
+        }
+        let viewers = match self.viewers.get(&Room(pos.room)) {
+            Some(viewers) => viewers,
+            None => return false,
+        };
+        let mut visible = false;
+        viewers.query_range(&pos.pos, MAX_VISION, &mut |viewer_pos, range| {
+            visible = visible || viewer_pos.hex_distance(pos.pos) <= *range;
+        });
+        visible
+    }
+
+    /// Rooms with at least a single visible position
+    pub fn seen_rooms(&self) -> impl Iterator<Item = Room> + '_ {
+        self.rooms
+            .iter()
+            .copied()
+            .chain(self.viewers.keys().copied())
+    }
+}
+
+pub fn compute_visibility(user_id: UserId, views: VisibilityInput) -> Visibility {
+    compute_visibilities(|id| id == user_id, views)
+        .remove(&user_id)
+        .unwrap_or_default()
+}
+
+/// Visibility of the users accepted by `filter`, in a single pass over the entities
+pub fn compute_visibilities(
+    filter: impl Fn(UserId) -> bool,
+    (room_owners, owners, positions, bots, kinds): VisibilityInput,
+) -> HashMap<UserId, Visibility> {
+    let mut rooms = HashMap::<UserId, BTreeSet<Room>>::new();
+    for (room, OwnedEntity { owner_id }) in room_owners.iter() {
+        if filter(*owner_id) {
+            rooms.entry(*owner_id).or_default().insert(room);
+        }
+    }
+    let mut viewers = HashMap::<UserId, Vec<_>>::new();
+    for (id, OwnedEntity { owner_id }) in owners.iter() {
+        if !filter(*owner_id) {
+            continue;
+        }
+        let range = match vision_range(bots.contains_id(&id), kinds.get_by_id(&id).copied()) {
+            Some(range) => range,
+            None => continue,
+        };
+        if let Some(PositionComponent(pos)) = positions.get_by_id(&id) {
+            viewers.entry(*owner_id).or_default().push((*pos, range));
+        }
+    }
+    let users: BTreeSet<UserId> = rooms.keys().chain(viewers.keys()).copied().collect();
+    users
+        .into_iter()
+        .map(|user_id| {
+            let visibility = Visibility::new(
+                rooms.remove(&user_id).unwrap_or_default(),
+                viewers.remove(&user_id).unwrap_or_default(),
+            );
+            (user_id, visibility)
+        })
+        .collect()
+}
+
+pub type IsVisibleInput<'a> = (
+    View<'a, Room, OwnedEntity>,
+    View<'a, EntityId, OwnedEntity>,
+    View<'a, EntityId, Bot>,
+    View<'a, EntityId, StructureKind>,
+    View<'a, WorldPosition, EntityComponent>,
+);
+
+/// Visibility of the user limited to a single room, only looks at the entities in the room.
+pub fn compute_room_visibility(
+    user_id: UserId,
+    room: Room,
+    (room_owners, owners, bots, kinds, entities_by_pos): IsVisibleInput,
+) -> Visibility {
+    let owns_room = room_owners
+        .get_by_id(&room)
+        .map(|OwnedEntity { owner_id }| *owner_id == user_id)
+        .unwrap_or(false);
+    if owns_room {
+        let mut visibility = Visibility::default();
+        visibility.rooms.insert(room);
+        return visibility;
+    }
+    let viewers = entities_by_pos
+        .table
+        .get_by_id(&room.0)
+        .into_iter()
+        .flat_map(|entities| entities.iter())
+        .filter(|(_, EntityComponent(id))| {
+            owners
+                .get_by_id(id)
+                .map(|OwnedEntity { owner_id }| *owner_id == user_id)
+                .unwrap_or(false)
+        })
+        .filter_map(|(pos, EntityComponent(id))| {
+            let range = vision_range(bots.contains_id(id), kinds.get_by_id(id).copied())?;
+            Some((WorldPosition { room: room.0, pos }, range))
+        })
+        .collect();
+    Visibility::new(BTreeSet::new(), viewers)
+}
+
+/// Can the user see the position.
+/// Only looks at the surroundings of `pos`, cheaper than `compute_visibility` for single positions.
+pub fn is_visible(
+    user_id: UserId,
+    pos: WorldPosition,
+    (room_owners, owners, bots, kinds, entities_by_pos): IsVisibleInput,
+) -> bool {
+    let owns_room = room_owners
+        .get_by_id(&Room(pos.room))
+        .map(|OwnedEntity { owner_id }| *owner_id == user_id)
+        .unwrap_or(false);
+    if owns_room {
+        return true;
+    }
+    let room = match entities_by_pos.table.get_by_id(&pos.room) {
+        Some(room) => room,
+        None => return false,
+    };
+    let mut visible = false;
+    room.query_range(
+        &pos.pos,
+        MAX_VISION,
+        &mut |viewer_pos, EntityComponent(id)| {
+            if visible
+                || owners
+                    .get_by_id(id)
+                    .map(|OwnedEntity { owner_id }| *owner_id != user_id)
+                    .unwrap_or(true)
+            {
+                return;
+            }
+            visible = vision_range(bots.contains_id(id), kinds.get_by_id(id).copied())
+                .map(|range| viewer_pos.hex_distance(pos.pos) <= range)
+                .unwrap_or(false);
+        },
+    );
+    visible
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::views::FromWorld;
+    use crate::utils::{setup_testing, test_logger};
+    use crate::world::init_inmemory_storage;
+
+    #[test]
+    fn bots_see_within_their_room() {
+        setup_testing();
+        let mut store = init_inmemory_storage(test_logger());
+        let user_id = UserId::default();
+        let at = |q, room_q| WorldPosition {
+            room: Axial::new(room_q, 0),
+            pos: Axial::new(q, 10),
+        };
+
+        let bot_id = store.insert_entity();
+        store.unsafe_view::<EntityId, Bot>().insert(bot_id);
+        store
+            .unsafe_view::<EntityId, OwnedEntity>()
+            .insert_or_update(bot_id, OwnedEntity { owner_id: user_id });
+        store
+            .unsafe_view::<EntityId, PositionComponent>()
+            .insert_or_update(bot_id, PositionComponent(at(10, 0)));
+        store
+            .unsafe_view::<WorldPosition, EntityComponent>()
+            .insert(at(10, 0), EntityComponent(bot_id))
+            .unwrap();
+
+        let visibility = compute_visibility(user_id, FromWorld::new(&*store));
+        let cases = [
+            (at(10 + BOT_VISION as i32, 0), true),
+            (at(11 + BOT_VISION as i32, 0), false),
+            (at(10, 1), false),
+        ];
+        for (pos, expected) in cases.iter() {
+            assert_eq!(visibility.is_visible(*pos), *expected, "{:?}", pos);
+            assert_eq!(
+                is_visible(user_id, *pos, FromWorld::new(&*store)),
+                *expected,
+                "{:?}",
+                pos
+            );
+        }
+
+        let enemy_id = UserId(uuid::Uuid::new_v4());
+        assert!(!is_visible(enemy_id, at(10, 0), FromWorld::new(&*store)));
+    }
+}
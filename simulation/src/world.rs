@@ -7,7 +7,7 @@ use crate::intents::*;
 use crate::storage;
 use crate::storage::views::{UnsafeView, View};
 use crate::tables::morton_hierarchy::ExtendFailure;
-use crate::tables::{Component, TableId};
+use crate::tables::{Component, Table, TableId};
 use crate::Time;
 use crate::{components::game_config::GameConfig, prelude::Axial};
 use serde::Serialize;
@@ -27,6 +27,7 @@ storage!(
     module entity_store key EntityId,
 
     table Bot = bot,
+    table NpcComponent = npc,
     table PositionComponent = pos,
     table SpawnBotComponent = spawnbot,
     table CarryComponent = carry,
@@ -35,12 +36,26 @@ storage!(
     table EnergyRegenComponent = energyregen,
     table EnergyComponent = energy,
     table ResourceComponent = resource,
+    table ResourceAmountComponent = resource_amount,
     table DecayComponent = decay,
+    table FatigueComponent = fatigue,
     table EntityScript = script,
     table SpawnComponent = spawn,
     table SpawnQueueComponent = spawnqueue,
     table OwnedEntity = owner,
     table MeleeAttackComponent = melee,
+    table RangedAttackComponent = ranged,
+    table ArmourComponent = armour,
+    table HealComponent = heal,
+    table IntentOutcomes = intent_outcomes,
+    table BotGroup = group,
+    table StructureKind = structure_kind,
+    table TowerComponent = tower,
+    table RoomControllerComponent = controller,
+    table ConstructionSite = construction_site,
+    table ResourcePileComponent = pile,
+    table RemainsComponent = remains,
+    table LastDamageComponent = last_damage,
 
     attr serde(skip) table PathCacheComponent = pathcache,
     attr serde(skip) table ScriptHistory = script_history
@@ -48,6 +63,8 @@ storage!(
     iterby bot
     iterby structure
     iterby resource
+    iterby pile
+    iterby remains
 );
 
 storage!(
@@ -56,7 +73,11 @@ storage!(
     table UserComponent = user,
     table EntityScript = user_default_script,
     table Rooms = user_rooms,
-    table UserProperties = user_props
+    table ExploredRooms = explored_rooms,
+    table UserProperties = user_props,
+    table CpuUsage = cpu_usage,
+    table BotGroups = bot_groups,
+    attr serde(skip) table DeadScriptHistories = dead_script_history
 
     iterby user
 );
@@ -73,8 +94,24 @@ storage!(
     table Intents<CachePathIntent> = update_path_cache_intents,
     table Intents<MutPathCacheIntent> = mut_path_cache_intents,
     table Intents<MeleeIntent> = melee_intents,
+    table Intents<RangedIntent> = ranged_intents,
+    table Intents<HealIntent> = heal_intents,
+    table Intents<RepairIntent> = repair_intents,
+    table Intents<TransferIntent> = transfer_intents,
+    table Intents<DropIntent> = drop_intents,
+    table Intents<PickupIntent> = pickup_intents,
     table Intents<ScriptHistoryEntry> = script_history_intents,
-    table Intents<DeleteEntityIntent> = delete_entity_intents
+    table Intents<DeleteEntityIntent> = delete_entity_intents,
+    table Intents<BuildIntent> = build_intents,
+    table Intents<ConstructionSiteIntent> = construction_site_intents
+);
+
+storage!(
+    module script_store key ScriptId,
+
+    table ScriptComponent = program,
+    table ScriptVersions = versions,
+    table ScriptErrorStats = error_stats
 );
 
 storage!(
@@ -88,7 +125,10 @@ storage!(
     module positions_store key WorldPosition,
     // don't forget to implement these in `reset_world_storage`
     table TerrainComponent = point_terrain,
-    attr serde(skip) table EntityComponent = point_entity
+    attr serde(skip) table EntityComponent = point_entity,
+    attr serde(skip) table RoadComponent = point_road,
+    attr serde(skip) table SiteComponent = point_site,
+    attr serde(skip) table PileComponent = point_pile
 );
 
 #[derive(Debug, Serialize)]
@@ -99,7 +139,7 @@ pub struct World {
     pub config: config_store::Storage,
     pub resources: resource_store::Storage,
     pub entity_logs: <LogEntry as Component<EntityTime>>::Table,
-    pub scripts: <ScriptComponent as Component<ScriptId>>::Table,
+    pub scripts: script_store::Storage,
     pub positions: positions_store::Storage,
 
     #[serde(skip)]
@@ -134,6 +174,7 @@ impl_hastable!(user_store, user);
 impl_hastable!(config_store, config);
 impl_hastable!(positions_store, positions);
 impl_hastable!(resource_store, resources);
+impl_hastable!(script_store, scripts);
 
 impl storage::HasTable<EntityTime, LogEntry> for World {
     fn view(&self) -> View<EntityTime, LogEntry> {
@@ -145,16 +186,6 @@ impl storage::HasTable<EntityTime, LogEntry> for World {
     }
 }
 
-impl storage::HasTable<ScriptId, ScriptComponent> for World {
-    fn view(&self) -> View<ScriptId, ScriptComponent> {
-        View::from_table(&self.scripts)
-    }
-
-    fn unsafe_view(&mut self) -> UnsafeView<ScriptId, ScriptComponent> {
-        UnsafeView::from_table(&mut self.scripts)
-    }
-}
-
 pub fn init_inmemory_storage(logger: impl Into<Option<slog::Logger>>) -> Pin<Box<World>> {
     fn _init(logger: Option<slog::Logger>) -> Pin<Box<World>> {
         match logger {
@@ -250,6 +281,7 @@ impl World {
 
     /// Perform post-tick cleanup on the storage
     pub fn post_process(&mut self) {
+        self.archive_script_histories();
         self.deferred_deletes.execute_all(&mut self.entities);
         self.deferred_deletes.clear();
 
@@ -261,6 +293,70 @@ impl World {
             .or(Some(Time(1)));
     }
 
+    /// Return the script execution history of the given entity in the `[from, to]` tick range
+    /// (inclusive).
+    /// Only the last `GameConfig::script_history_len` ticks are retained.
+    pub fn script_history(
+        &self,
+        entity_id: EntityId,
+        from: u64,
+        to: u64,
+    ) -> impl Iterator<Item = &ScriptHistoryEntry> {
+        self.entities
+            .script_history
+            .get_by_id(&entity_id)
+            .into_iter()
+            .flat_map(move |history| history.range(from, to))
+    }
+
+    /// Return the script execution history of a deleted entity of the given user in the
+    /// `[from, to]` tick range (inclusive).
+    /// Histories are retained for `GameConfig::dead_script_history_ttl` ticks after the deletion.
+    pub fn dead_script_history(
+        &self,
+        user_id: UserId,
+        entity_id: EntityId,
+        from: u64,
+        to: u64,
+    ) -> impl Iterator<Item = &ScriptHistoryEntry> {
+        self.user
+            .dead_script_history
+            .get_by_id(&user_id)
+            .into_iter()
+            .flat_map(|DeadScriptHistories(histories)| histories.iter())
+            .filter(move |dead| dead.entity_id == entity_id)
+            .flat_map(move |dead| dead.history.range(from, to))
+    }
+
+    /// Move the script histories of the entities about to be deleted to their owners, so they
+    /// can be inspected after death
+    fn archive_script_histories(&mut self) {
+        let died = self.time();
+        for entity_id in self.deferred_deletes.entityid.iter() {
+            let owner_id = match self.entities.owner.get_by_id(entity_id) {
+                Some(OwnedEntity { owner_id }) => *owner_id,
+                None => continue,
+            };
+            let history = match self.entities.script_history.delete(entity_id) {
+                Some(history) => history,
+                None => continue,
+            };
+            let dead = DeadScriptHistory {
+                entity_id: *entity_id,
+                died,
+                history,
+            };
+            match self.user.dead_script_history.get_by_id_mut(&owner_id) {
+                Some(DeadScriptHistories(histories)) => histories.push(dead),
+                None => {
+                    self.user
+                        .dead_script_history
+                        .insert_or_update(owner_id, DeadScriptHistories(vec![dead]));
+                }
+            }
+        }
+    }
+
     pub fn insert_entity(&mut self) -> EntityId {
         use crate::tables::SerialId;
 
@@ -288,12 +384,77 @@ impl World {
 
         clear_table!(TerrainComponent);
         clear_table!(EntityComponent);
+        clear_table!(RoadComponent);
+        clear_table!(SiteComponent);
+        clear_table!(PileComponent);
 
         Ok(self)
     }
 
     #[cfg(feature = "serde_json")]
     pub fn as_json(&self) -> serde_json::Value {
+        self.json_output(|_| true, |_| true, |_| true)
+    }
+
+    /// The world as seen by the user, see `crate::visibility`.
+    /// Includes the visible entities, and the terrain of the rooms the user explored.
+    /// Intent outcomes and scripts are only included for the bots of the user.
+    #[cfg(feature = "serde_json")]
+    pub fn as_json_for_user(&self, user_id: UserId) -> serde_json::Value {
+        let visibility = crate::visibility::compute_visibility(
+            user_id,
+            crate::storage::views::FromWorld::new(self),
+        );
+        json_impl::UserOutputs::new(self).for_user(
+            user_id,
+            &visibility,
+            &self.explored_rooms(user_id),
+        )
+    }
+
+    /// Same as `as_json_for_user`, for every user.
+    /// The world is serialized once, the output of each user only looks at the rooms they see.
+    #[cfg(feature = "serde_json")]
+    pub fn as_json_by_user(&self) -> Vec<(UserId, serde_json::Value)> {
+        use rayon::prelude::*;
+
+        let visibilities = crate::visibility::compute_visibilities(
+            |_| true,
+            crate::storage::views::FromWorld::new(self),
+        );
+        let outputs = json_impl::UserOutputs::new(self);
+        let users = self
+            .view::<UserId, UserComponent>()
+            .iter()
+            .map(|(user_id, _)| user_id)
+            .collect::<Vec<_>>();
+        let no_visibility = crate::visibility::Visibility::default();
+        users
+            .into_par_iter()
+            .map(|user_id| {
+                let visibility = visibilities.get(&user_id).unwrap_or(&no_visibility);
+                let output = outputs.for_user(user_id, visibility, &self.explored_rooms(user_id));
+                (user_id, output)
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "serde_json")]
+    fn explored_rooms(&self, user_id: UserId) -> std::collections::BTreeSet<Room> {
+        self.view::<UserId, ExploredRooms>()
+            .reborrow()
+            .get_by_id(&user_id)
+            .map(|ExploredRooms(rooms)| rooms.clone())
+            .unwrap_or_default()
+    }
+
+    #[cfg(feature = "serde_json")]
+    fn json_output(
+        &self,
+        visible: impl Fn(WorldPosition) -> bool + Sync,
+        explored: impl Fn(Room) -> bool + Sync,
+        visible_user: impl Fn(UserId) -> bool + Sync,
+    ) -> serde_json::Value {
         use rayon::prelude::*;
 
         // this can be quite a bit of data, so let's parallelize the serialization
@@ -301,6 +462,8 @@ impl World {
             "bots",
             "structures",
             "resources",
+            "piles",
+            "remains",
             "terrain",
             "roomProperties",
             "gameConfig",
@@ -313,11 +476,13 @@ impl World {
             Default::default,
             |mut output: serde_json::Map<String, _>, key: &str| {
                 let value: serde_json::Value = match key {
-                    "bots" => json_impl::json_serialize_bots(&self),
-                    "users" => json_impl::json_serialize_users(&self),
-                    "structures" => json_impl::json_serialize_structures(&self),
-                    "resources" => json_impl::json_serialize_resources(&self),
-                    "terrain" => json_impl::json_serialize_terrain(&self),
+                    "bots" => json_impl::json_serialize_bots(&self, &visible, &visible_user),
+                    "users" => json_impl::json_serialize_users(&self, &visible_user),
+                    "structures" => json_impl::json_serialize_structures(&self, &visible),
+                    "resources" => json_impl::json_serialize_resources(&self, &visible),
+                    "piles" => json_impl::json_serialize_piles(&self, &visible),
+                    "remains" => json_impl::json_serialize_remains(&self, &visible),
+                    "terrain" => json_impl::json_serialize_terrain(&self, &explored),
                     "roomProperties" => {
                         serde_json::to_value(&self.config.room_properties.value).unwrap()
                     }
@@ -378,10 +543,13 @@ mod tests {
             let entity = world.insert_entity();
 
             world.entities.bot.insert(entity);
-            world
-                .entities
-                .melee
-                .insert_or_update(entity, MeleeAttackComponent { strength: 128 });
+            world.entities.melee.insert_or_update(
+                entity,
+                MeleeAttackComponent {
+                    strength: 128,
+                    ..Default::default()
+                },
+            );
             world.entities.pos.insert_or_update(
                 entity,
                 PositionComponent(WorldPosition {
@@ -410,4 +578,107 @@ mod tests {
         let structures: Vec<_> = world.entities.iterby_structure().collect();
         serde_json::to_string_pretty(&structures).unwrap();
     }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn user_output_hides_what_the_user_can_not_see() {
+        setup_testing();
+        let mut world = init_inmemory_storage(None);
+        let user_id = UserId::default();
+        let enemy_id = UserId(uuid::Uuid::new_v4());
+        let room = Axial::new(0, 0);
+
+        for (owner_id, q) in [(user_id, 10), (enemy_id, 30)].iter() {
+            let entity = world.insert_entity();
+            world.entities.bot.insert(entity);
+            world.entities.owner.insert_or_update(
+                entity,
+                OwnedEntity {
+                    owner_id: *owner_id,
+                },
+            );
+            world.entities.pos.insert_or_update(
+                entity,
+                PositionComponent(WorldPosition {
+                    room,
+                    pos: Axial::new(*q, 10),
+                }),
+            );
+        }
+        world
+            .positions
+            .point_terrain
+            .insert(
+                WorldPosition {
+                    room: Axial::new(5, 5),
+                    pos: Axial::new(1, 1),
+                },
+                TerrainComponent::default(),
+            )
+            .unwrap();
+
+        let output = world.as_json_for_user(user_id);
+        let bots = output["bots"]["0;0"].as_array().expect("bots of room 0;0");
+        assert_eq!(bots.len(), 1);
+        assert!(output["terrain"].get("5;5").is_none());
+        assert!(world.as_json()["terrain"].get("5;5").is_some());
+
+        // every user gets the same output when published together
+        for user_id in [user_id, enemy_id].iter() {
+            world
+                .user
+                .user
+                .insert_or_update(*user_id, UserComponent::default());
+        }
+        let outputs = world.as_json_by_user();
+        assert_eq!(outputs.len(), 2);
+        for (user_id, output) in outputs {
+            assert_eq!(output, world.as_json_for_user(user_id));
+        }
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn user_output_hides_the_details_of_enemy_bots() {
+        setup_testing();
+        let mut world = init_inmemory_storage(None);
+        let user_id = UserId::default();
+        let enemy_id = UserId(uuid::Uuid::new_v4());
+        let room = Axial::new(0, 0);
+
+        for (owner_id, q) in [(user_id, 10), (enemy_id, 11)].iter() {
+            let entity = world.insert_entity();
+            world.entities.bot.insert(entity);
+            world.entities.owner.insert_or_update(
+                entity,
+                OwnedEntity {
+                    owner_id: *owner_id,
+                },
+            );
+            world.entities.pos.insert_or_update(
+                entity,
+                PositionComponent(WorldPosition {
+                    room,
+                    pos: Axial::new(*q, 10),
+                }),
+            );
+            world
+                .entities
+                .intent_outcomes
+                .insert_or_update(entity, IntentOutcomes::default());
+            world
+                .entities
+                .script
+                .insert_or_update(entity, EntityScript(ScriptId(uuid::Uuid::new_v4())));
+        }
+
+        let output = world.as_json_for_user(user_id);
+        let bots = output["bots"]["0;0"].as_array().expect("bots of room 0;0");
+        assert_eq!(bots.len(), 2);
+        for bot in bots {
+            let is_own = bot["owner"]["ownerId"] == serde_json::to_value(user_id).unwrap();
+            assert_eq!(bot.get("intent_outcomes").is_some(), is_own);
+            assert_eq!(bot.get("script").is_some(), is_own);
+        }
+    }
 }
@@ -1,18 +1,23 @@
 use serde_json::json;
 
 use super::World;
+use crate::indices::{Room, UserId, WorldPosition};
 use crate::prelude::Axial;
-use std::collections::HashMap;
+use crate::visibility::Visibility;
+use std::collections::{BTreeSet, HashMap};
 
 fn pos_to_string(pos: Axial) -> String {
     format!("{};{}", pos.q, pos.r)
 }
 
-pub fn json_serialize_resources(world: &World) -> serde_json::Value {
+pub fn json_serialize_resources(
+    world: &World,
+    visible: impl Fn(WorldPosition) -> bool,
+) -> serde_json::Value {
     let resources = world
         .entities
         .iterby_resource()
-        .filter_map(|payload| payload.pos.map(|_| payload))
+        .filter_map(|payload| payload.pos.filter(|pos| visible(pos.0)).map(|_| payload))
         .fold(HashMap::new(), |mut map, payload| {
             let room = payload.pos.unwrap().0.room;
             let room = pos_to_string(room);
@@ -22,11 +27,46 @@ pub fn json_serialize_resources(world: &World) -> serde_json::Value {
     serde_json::to_value(&resources).unwrap()
 }
 
-pub fn json_serialize_terrain(world: &World) -> serde_json::Value {
+pub fn json_serialize_piles(
+    world: &World,
+    visible: impl Fn(WorldPosition) -> bool,
+) -> serde_json::Value {
+    let piles = world
+        .entities
+        .iterby_pile()
+        .filter_map(|payload| payload.pos.filter(|pos| visible(pos.0)).map(|_| payload))
+        .fold(HashMap::new(), |mut map, payload| {
+            let room = payload.pos.unwrap().0.room;
+            let room = pos_to_string(room);
+            map.entry(room).or_insert_with(Vec::new).push(payload);
+            map
+        });
+    serde_json::to_value(&piles).unwrap()
+}
+
+pub fn json_serialize_remains(
+    world: &World,
+    visible: impl Fn(WorldPosition) -> bool,
+) -> serde_json::Value {
+    let remains = world
+        .entities
+        .iterby_remains()
+        .filter_map(|payload| payload.pos.filter(|pos| visible(pos.0)).map(|_| payload))
+        .fold(HashMap::new(), |mut map, payload| {
+            let room = payload.pos.unwrap().0.room;
+            let room = pos_to_string(room);
+            map.entry(room).or_insert_with(Vec::new).push(payload);
+            map
+        });
+    serde_json::to_value(&remains).unwrap()
+}
+
+pub fn json_serialize_terrain(world: &World, explored: impl Fn(Room) -> bool) -> serde_json::Value {
     let terrain = world
         .positions
         .point_terrain
         .iter()
+        .filter(|(pos, _)| explored(Room(pos.room)))
         .map(|(pos, terrain)| (pos_to_string(pos.room), (pos.pos, terrain)))
         .fold(HashMap::new(), |mut map, (room, payload)| {
             map.entry(room).or_insert_with(Vec::new).push(payload);
@@ -35,11 +75,14 @@ pub fn json_serialize_terrain(world: &World) -> serde_json::Value {
     serde_json::to_value(&terrain).unwrap()
 }
 
-pub fn json_serialize_structures(world: &World) -> serde_json::Value {
+pub fn json_serialize_structures(
+    world: &World,
+    visible: impl Fn(WorldPosition) -> bool,
+) -> serde_json::Value {
     let structures = world
         .entities
         .iterby_structure()
-        .filter_map(|payload| payload.pos.map(|_| payload))
+        .filter_map(|payload| payload.pos.filter(|pos| visible(pos.0)).map(|_| payload))
         .fold(HashMap::new(), |mut map, payload| {
             let room = payload.pos.unwrap().0.room;
             let room = pos_to_string(room);
@@ -49,13 +92,27 @@ pub fn json_serialize_structures(world: &World) -> serde_json::Value {
     serde_json::to_value(&structures).unwrap()
 }
 
-pub fn json_serialize_bots(world: &World) -> serde_json::Value {
+/// Intent outcomes and scripts are only output for the bots of the `own` users
+pub fn json_serialize_bots(
+    world: &World,
+    visible: impl Fn(WorldPosition) -> bool,
+    own: impl Fn(UserId) -> bool,
+) -> serde_json::Value {
     let bots = world
         .entities
         .iterby_bot()
         .filter_map(|mut payload| {
             payload.pathcache = None;
-            payload.pos.map(|_| payload)
+            payload.script_history = None;
+            let is_own = payload
+                .owner
+                .map(|owner| own(owner.owner_id))
+                .unwrap_or(false);
+            if !is_own {
+                payload.intent_outcomes = None;
+                payload.script = None;
+            }
+            payload.pos.filter(|pos| visible(pos.0)).map(|_| payload)
         })
         .fold(HashMap::new(), |mut map, payload| {
             let room = payload.pos.unwrap().0.room;
@@ -66,10 +123,11 @@ pub fn json_serialize_bots(world: &World) -> serde_json::Value {
     serde_json::to_value(&bots).unwrap()
 }
 
-pub fn json_serialize_users(world: &World) -> serde_json::Value {
+pub fn json_serialize_users(world: &World, visible: impl Fn(UserId) -> bool) -> serde_json::Value {
     let users = world
         .user
         .iterby_user()
+        .filter(|pl| visible(pl.__id))
         .map(|pl| (pl.__id, pl))
         .collect::<HashMap<_, _>>();
 
@@ -92,3 +150,191 @@ pub fn json_serialize_rooms(world: &World) -> serde_json::Value {
 
     serde_json::to_value(&rooms).unwrap()
 }
+
+/// Output keys of the entities, by room
+const ENTITY_KEYS: [&str; 5] = ["bots", "structures", "resources", "piles", "remains"];
+
+/// Serialized entities and terrain of a single room
+#[derive(Default)]
+struct RoomOutput {
+    /// Output key, position, owner and payload of the entities in the room
+    entities: Vec<(
+        &'static str,
+        WorldPosition,
+        Option<UserId>,
+        serde_json::Value,
+    )>,
+    terrain: Vec<serde_json::Value>,
+}
+
+/// The world serialized once, grouped by room.
+/// The output of a user only looks at the rooms they see, see `World::as_json_by_user`.
+pub struct UserOutputs {
+    rooms: HashMap<Room, RoomOutput>,
+    users: HashMap<UserId, serde_json::Value>,
+    shared: serde_json::Map<String, serde_json::Value>,
+}
+
+impl UserOutputs {
+    pub fn new(world: &World) -> Self {
+        let mut rooms = HashMap::<Room, RoomOutput>::new();
+        let mut push = |key, pos: WorldPosition, owner, payload| {
+            rooms
+                .entry(Room(pos.room))
+                .or_default()
+                .entities
+                .push((key, pos, owner, payload));
+        };
+        for mut payload in world.entities.iterby_bot() {
+            payload.pathcache = None;
+            payload.script_history = None;
+            if let Some(pos) = payload.pos {
+                let owner = payload.owner.map(|owner| owner.owner_id);
+                push(
+                    "bots",
+                    pos.0,
+                    owner,
+                    serde_json::to_value(&payload).unwrap(),
+                );
+            }
+        }
+        for payload in world.entities.iterby_structure() {
+            if let Some(pos) = payload.pos {
+                push(
+                    "structures",
+                    pos.0,
+                    None,
+                    serde_json::to_value(&payload).unwrap(),
+                );
+            }
+        }
+        for payload in world.entities.iterby_resource() {
+            if let Some(pos) = payload.pos {
+                push(
+                    "resources",
+                    pos.0,
+                    None,
+                    serde_json::to_value(&payload).unwrap(),
+                );
+            }
+        }
+        for payload in world.entities.iterby_pile() {
+            if let Some(pos) = payload.pos {
+                push(
+                    "piles",
+                    pos.0,
+                    None,
+                    serde_json::to_value(&payload).unwrap(),
+                );
+            }
+        }
+        for payload in world.entities.iterby_remains() {
+            if let Some(pos) = payload.pos {
+                push(
+                    "remains",
+                    pos.0,
+                    None,
+                    serde_json::to_value(&payload).unwrap(),
+                );
+            }
+        }
+        for (pos, terrain) in world.positions.point_terrain.iter() {
+            rooms
+                .entry(Room(pos.room))
+                .or_default()
+                .terrain
+                .push(serde_json::to_value(&(pos.pos, terrain)).unwrap());
+        }
+
+        let users = world
+            .user
+            .iterby_user()
+            .map(|pl| (pl.__id, serde_json::to_value(&pl).unwrap()))
+            .collect();
+
+        let mut shared = serde_json::Map::new();
+        shared.insert(
+            "roomProperties".to_string(),
+            serde_json::to_value(&world.config.room_properties.value).unwrap(),
+        );
+        shared.insert(
+            "gameConfig".to_string(),
+            serde_json::to_value(&world.config.game_config.value).unwrap(),
+        );
+        shared.insert("rooms".to_string(), json_serialize_rooms(world));
+
+        Self {
+            rooms,
+            users,
+            shared,
+        }
+    }
+
+    /// Intent outcomes and scripts are only output for the bots of the user
+    pub fn for_user(
+        &self,
+        user_id: UserId,
+        visibility: &Visibility,
+        explored: &BTreeSet<Room>,
+    ) -> serde_json::Value {
+        let mut entities: HashMap<&str, HashMap<String, Vec<serde_json::Value>>> = ENTITY_KEYS
+            .iter()
+            .map(|key| (*key, HashMap::new()))
+            .collect();
+        let seen: BTreeSet<Room> = visibility.seen_rooms().collect();
+        for room in seen.iter() {
+            let output = match self.rooms.get(room) {
+                Some(output) => output,
+                None => continue,
+            };
+            let whole_room = visibility.rooms.contains(room);
+            for (key, pos, owner, payload) in output.entities.iter() {
+                if !whole_room && !visibility.is_visible(*pos) {
+                    continue;
+                }
+                let mut payload = payload.clone();
+                if *key == "bots" && *owner != Some(user_id) {
+                    if let Some(bot) = payload.as_object_mut() {
+                        bot.remove("intent_outcomes");
+                        bot.remove("script");
+                    }
+                }
+                entities
+                    .entry(*key)
+                    .or_default()
+                    .entry(pos_to_string(room.0))
+                    .or_default()
+                    .push(payload);
+            }
+        }
+
+        let terrain: HashMap<String, &Vec<serde_json::Value>> = explored
+            .iter()
+            .chain(seen.iter())
+            .filter_map(|room| {
+                let output = self.rooms.get(room)?;
+                if output.terrain.is_empty() {
+                    return None;
+                }
+                Some((pos_to_string(room.0), &output.terrain))
+            })
+            .collect();
+        let users: HashMap<UserId, &serde_json::Value> = self
+            .users
+            .get(&user_id)
+            .map(|user| (user_id, user))
+            .into_iter()
+            .collect();
+
+        let mut output = self.shared.clone();
+        for (key, value) in entities {
+            output.insert(key.to_string(), serde_json::to_value(&value).unwrap());
+        }
+        output.insert(
+            "terrain".to_string(),
+            serde_json::to_value(&terrain).unwrap(),
+        );
+        output.insert("users".to_string(), serde_json::to_value(&users).unwrap());
+        output.into()
+    }
+}
@@ -8,6 +8,7 @@ pub struct GameConfig {
     pub room_radius: u32,
     pub n_actors: u32,
     pub target_tick_ms: u64,
+    pub world_seed: u64,
 }
 
 impl Default for GameConfig {
@@ -17,6 +18,7 @@ impl Default for GameConfig {
             room_radius: 8,
             world_radius: 8,
             target_tick_ms: 200,
+            world_seed: 0,
         }
     }
 }
@@ -44,6 +46,9 @@ impl GameConfig {
             target_tick_ms: std::env::var("TARGET_TICK_LATENCY_MS")
                 .map(|i| i.parse::<u64>().unwrap())
                 .unwrap_or(200),
+            world_seed: std::env::var("CAO_WORLD_SEED")
+                .map(|s| s.parse().expect("expected world seed to be an integer"))
+                .unwrap_or_else(|_| rand::random()),
         }
     }
 }
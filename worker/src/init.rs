@@ -15,7 +15,7 @@ pub fn init_storage(logger: Logger, storage: &mut World, config: &GameConfig) {
         serde_json::from_str(include_str!("./programs/mining_program.json"))
             .expect("deserialize example program");
     debug!(logger, "compiling default program");
-    let compiled = compile(None, script, CompileOptions::new().with_breadcrumbs(false))
+    let compiled = compile(None, script, CompileOptions::new().with_breadcrumbs(true))
         .expect("failed to compile example program");
     debug!(logger, "compilation done");
 
@@ -33,7 +33,7 @@ pub fn init_storage(logger: Logger, storage: &mut World, config: &GameConfig) {
         serde_json::from_str(include_str!("./programs/center_walking_program.json"))
             .expect("deserialize example program");
     debug!(logger, "compiling default program");
-    let compiled = compile(None, script, CompileOptions::new().with_breadcrumbs(false))
+    let compiled = compile(None, script, CompileOptions::new().with_breadcrumbs(true))
         .expect("failed to compile example program");
     debug!(logger, "compilation done");
 
@@ -46,6 +46,24 @@ pub fn init_storage(logger: Logger, storage: &mut World, config: &GameConfig) {
         }
     );
 
+    let invader_script_id = ScriptId(Uuid::new_v4());
+    let script: CompilationUnit =
+        serde_json::from_str(include_str!("./programs/invader_program.json"))
+            .expect("deserialize invader program");
+    debug!(logger, "compiling invader program");
+    let compiled = compile(None, script, CompileOptions::new().with_breadcrumbs(true))
+        .expect("failed to compile invader program");
+    debug!(logger, "compilation done");
+
+    caolo_sim::query!(
+        mutate
+        storage
+        {
+            ScriptId, ScriptComponent,
+                .insert_or_update(invader_script_id, ScriptComponent(compiled));
+        }
+    );
+
     let radius = config.room_radius;
     debug!(logger, "Reset position storage");
     let mut entities_by_pos = storage.unsafe_view::<WorldPosition, EntityComponent>();
@@ -133,7 +151,12 @@ pub fn init_storage(logger: Logger, storage: &mut World, config: &GameConfig) {
         trace!(logger, "initializing room #{} done", i);
     }
 
-    init_config(&logger, &config, FromWorldMut::new(storage));
+    init_config(
+        &logger,
+        &config,
+        invader_script_id,
+        FromWorldMut::new(storage),
+    );
 
     debug!(logger, "init done");
 }
@@ -141,10 +164,13 @@ pub fn init_storage(logger: Logger, storage: &mut World, config: &GameConfig) {
 fn init_config(
     logger: &Logger,
     conf: &GameConfig,
+    invader_script_id: ScriptId,
     mut game_conf: UnwrapViewMut<ConfigKey, caolo_sim::components::game_config::GameConfig>,
 ) {
     trace!(logger, "initializing config");
     game_conf.target_tick_ms = conf.target_tick_ms;
+    game_conf.world_seed = conf.world_seed;
+    game_conf.invaders.script = Some(invader_script_id);
     trace!(logger, "initializing config done");
 }
 
@@ -154,6 +180,7 @@ type InitBotMuts = (
     UnsafeView<EntityId, EntityScript>,
     UnsafeView<EntityId, Bot>,
     UnsafeView<EntityId, CarryComponent>,
+    UnsafeView<EntityId, FatigueComponent>,
     UnsafeView<EntityId, OwnedEntity>,
     UnsafeView<EntityId, PositionComponent>,
     UnsafeView<WorldPosition, EntityComponent>,
@@ -170,6 +197,7 @@ fn init_bot(
         mut entity_scripts,
         mut bots,
         mut carry_component,
+        mut fatigue,
         mut owners,
         mut positions,
         mut entities_by_pos,
@@ -177,11 +205,12 @@ fn init_bot(
 ) {
     entity_scripts.insert_or_update(id, EntityScript(script_id));
     bots.insert(id);
-    carry_component.insert_or_update(
+    carry_component.insert_or_update(id, CarryComponent::new(50));
+    fatigue.insert_or_update(
         id,
-        CarryComponent {
-            carry: 0,
-            carry_max: 50,
+        FatigueComponent {
+            fatigue: 0,
+            recovery: 2,
         },
     );
     owners.insert_or_update(
@@ -199,7 +228,13 @@ fn init_bot(
         .insert(pos.pos, EntityComponent(id))
         .expect("entities_by_pos insert");
 
-    melee.insert_or_update(id, MeleeAttackComponent { strength: 5 });
+    melee.insert_or_update(
+        id,
+        MeleeAttackComponent {
+            strength: 5,
+            ..Default::default()
+        },
+    );
     hp.insert_or_update(id, HpComponent { hp: 50, hp_max: 50 });
 }
 
@@ -208,6 +243,7 @@ type InitSpawnMuts = (
     UnsafeView<EntityId, SpawnComponent>,
     UnsafeView<EntityId, SpawnQueueComponent>,
     UnsafeView<EntityId, Structure>,
+    UnsafeView<EntityId, StructureKind>,
     UnsafeView<EntityId, PositionComponent>,
     UnsafeView<EntityId, EnergyComponent>,
     UnsafeView<EntityId, EnergyRegenComponent>,
@@ -228,6 +264,7 @@ fn init_spawn(
         mut spawns,
         mut spawn_queues,
         mut structures,
+        mut structure_kinds,
         mut positions,
         mut energies,
         mut regens,
@@ -237,6 +274,7 @@ fn init_spawn(
 ) {
     trace!(logger, "init_spawn");
     structures.insert(id);
+    structure_kinds.insert_or_update(id, StructureKind::Spawn);
     spawns.insert_or_update(id, SpawnComponent::default());
     spawn_queues.insert_or_update(id, SpawnQueueComponent::default());
     owners.insert_or_update(
@@ -269,7 +307,7 @@ fn init_spawn(
 type InitResourceMuts = (
     UnsafeView<EntityId, PositionComponent>,
     UnsafeView<EntityId, ResourceComponent>,
-    UnsafeView<EntityId, EnergyComponent>,
+    UnsafeView<EntityId, ResourceAmountComponent>,
     UnsafeView<WorldPosition, EntityComponent>,
 );
 
@@ -281,15 +319,21 @@ fn init_resource(
     id: EntityId,
     room: Room,
     rng: &mut impl Rng,
-    (mut positions_table, mut resources_table, mut energy_table, mut entities_by_pos, ): InitResourceMuts,
+    (mut positions_table, mut resources_table, mut amount_table, mut entities_by_pos, ): InitResourceMuts,
     (terrain,): InitResourceConst,
 ) {
-    resources_table.insert_or_update(id, ResourceComponent(Resource::Energy));
-    energy_table.insert_or_update(
+    // most resource nodes hold energy
+    let ty = match rng.gen_range(0..6) {
+        0 => Resource::Iron,
+        1 => Resource::Crystal,
+        _ => Resource::Energy,
+    };
+    resources_table.insert_or_update(id, ResourceComponent(ty));
+    amount_table.insert_or_update(
         id,
-        EnergyComponent {
-            energy: 250,
-            energy_max: 250,
+        ResourceAmountComponent {
+            amount: 250,
+            amount_max: 250,
         },
     );
 
@@ -1,4 +1,5 @@
 //! Handle inputs received via the message bus
+mod commands;
 mod rooms;
 mod script_update;
 mod structures;
@@ -149,6 +150,24 @@ pub async fn handle_messages<'a>(
             }
         }
     }
+    while let Ok(Some(message)) = queue
+        .rpop::<_, Option<Vec<u8>>>("CAO_JSON_COMMANDS")
+        .await
+        .map_err(|e| {
+            error!(logger, "Failed to GET JSON command {:?}", e);
+        })
+    {
+        let mut response = Vec::with_capacity(1024);
+        match commands::handle_json_message(&logger, message.as_slice(), storage, &mut response) {
+            Ok(msg_id) => {
+                queue.set_ex(format!("{}", msg_id), response, 10).await?;
+                info!(logger, "Command {:?} response sent!", msg_id);
+            }
+            Err(err) => {
+                error!(logger, "Command handling failed, {:?}", err);
+            }
+        }
+    }
     trace!(logger, "handling incoming messages done");
     Ok(())
 }
@@ -0,0 +1,266 @@
+//! Commands the capnp schema has no message for.
+//!
+//! Received as JSON on the `CAO_JSON_COMMANDS` queue, answered with a JSON `CommandResponse`
+//! under the id of the message.
+//!
+//! ```json
+//! {
+//!     "messageId": "8c62ae46-1e1b-4f0a-9b2f-5f0e2a6fa8e5",
+//!     "command": {
+//!         "ty": "rollbackScript",
+//!         "userId": "7d5b8d0e-7cbb-4c82-8c4b-3b1d5a1f1c3e",
+//!         "scriptId": "0d1b3c6c-6a8e-4a2c-9f0e-8c5a2b6d1e3f",
+//!         "version": 2
+//!     }
+//! }
+//! ```
+use super::{script_update, structures};
+use anyhow::Context;
+use caolo_sim::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use slog::{o, trace, warn, Logger};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandMessage {
+    pub message_id: Uuid,
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "ty")]
+pub enum Command {
+    #[serde(rename_all = "camelCase")]
+    ListScriptVersions { user_id: Uuid, script_id: Uuid },
+    #[serde(rename_all = "camelCase")]
+    RollbackScript {
+        user_id: Uuid,
+        script_id: Uuid,
+        version: u32,
+    },
+    #[serde(rename_all = "camelCase")]
+    SetGroupScript {
+        user_id: Uuid,
+        group: String,
+        script_id: Uuid,
+    },
+    /// Leaves the current group if `group` is not set
+    #[serde(rename_all = "camelCase")]
+    AssignBotGroup {
+        user_id: Uuid,
+        entity_id: u32,
+        #[serde(default)]
+        group: Option<String>,
+    },
+    /// Responds with the script history entries of the entity in the `[from, to]` tick range.
+    /// The histories of dead bots remain available for a while.
+    #[serde(rename_all = "camelCase")]
+    GetScriptHistory {
+        user_id: Uuid,
+        entity_id: u32,
+        from: u64,
+        to: u64,
+    },
+    /// Responds with the id of the new site
+    #[serde(rename_all = "camelCase")]
+    PlaceConstructionSite {
+        user_id: Uuid,
+        kind: StructureKind,
+        position: WorldPosition,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CommandResponse {
+    Ok(Value),
+    Error(String),
+}
+
+/// Execute a single command and return its response
+pub fn execute_command(logger: &Logger, storage: &mut World, command: Command) -> CommandResponse {
+    let res = match command {
+        Command::ListScriptVersions { user_id, script_id } => {
+            script_update::list_script_versions(storage, UserId(user_id), ScriptId(script_id))
+                .map(|versions| serde_json::to_value(versions).unwrap_or(Value::Null))
+                .map_err(|e| {
+                    warn!(logger, "Listing script versions failed {:?}", e);
+                    e.to_string()
+                })
+        }
+        Command::RollbackScript {
+            user_id,
+            script_id,
+            version,
+        } => script_update::rollback_script(
+            logger.clone(),
+            storage,
+            UserId(user_id),
+            ScriptId(script_id),
+            version,
+        )
+        .map(|_| Value::Null)
+        .map_err(|e| {
+            warn!(logger, "Script rollback failed {:?}", e);
+            e.to_string()
+        }),
+        Command::SetGroupScript {
+            user_id,
+            group,
+            script_id,
+        } => script_update::set_group_script(
+            logger.clone(),
+            storage,
+            UserId(user_id),
+            group,
+            ScriptId(script_id),
+        )
+        .map(|_| Value::Null)
+        .map_err(|e| {
+            warn!(logger, "Setting group script failed {:?}", e);
+            e.to_string()
+        }),
+        Command::AssignBotGroup {
+            user_id,
+            entity_id,
+            group,
+        } => script_update::assign_bot_group(storage, UserId(user_id), EntityId(entity_id), group)
+            .map(|_| Value::Null)
+            .map_err(|e| {
+                warn!(logger, "Assigning bot group failed {:?}", e);
+                e.to_string()
+            }),
+        Command::GetScriptHistory {
+            user_id,
+            entity_id,
+            from,
+            to,
+        } => script_update::script_history(storage, UserId(user_id), EntityId(entity_id), from, to)
+            .map(|history| serde_json::to_value(history).unwrap_or(Value::Null))
+            .map_err(|e| {
+                warn!(logger, "Getting script history failed {:?}", e);
+                e.to_string()
+            }),
+        Command::PlaceConstructionSite {
+            user_id,
+            kind,
+            position,
+        } => structures::place_construction_site(storage, UserId(user_id), kind, position)
+            .map(|EntityId(id)| Value::from(id))
+            .map_err(|e| {
+                warn!(logger, "Construction site placement failed {:?}", e);
+                e.to_string()
+            }),
+    };
+    match res {
+        Ok(value) => CommandResponse::Ok(value),
+        Err(err) => CommandResponse::Error(err),
+    }
+}
+
+/// Write the response and return the msg id
+pub fn handle_json_message(
+    logger: &Logger,
+    message: &[u8],
+    storage: &mut World,
+    response: &mut Vec<u8>,
+) -> anyhow::Result<Uuid> {
+    let message: CommandMessage =
+        serde_json::from_slice(message).with_context(|| "Failed to parse JSON command")?;
+    let msg_id = message.message_id;
+    let logger = logger.new(o!("msg_id" => format!("{:?}",msg_id)));
+    trace!(logger, "Handling JSON command");
+
+    let res = execute_command(&logger, storage, message.command);
+    serde_json::to_writer(response, &res).with_context(|| "Failed to write the response")?;
+    Ok(msg_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::script_update::tests::{program, test_world};
+
+    #[test]
+    fn commands_are_dispatched() {
+        let (logger, mut world) = test_world();
+        let user_id = Uuid::new_v4();
+        let script_id = Uuid::new_v4();
+        for _ in 0..2 {
+            let cu = program(r#"[ { "StringLiteral": "hello" }, { "Call": "Console Log" } ]"#);
+            script_update::insert_program(
+                logger.clone(),
+                &mut *world,
+                UserId(user_id),
+                ScriptId(script_id),
+                cu,
+            )
+            .unwrap();
+        }
+
+        let message = serde_json::json!({
+            "messageId": Uuid::new_v4(),
+            "command": {
+                "ty": "rollbackScript",
+                "userId": user_id,
+                "scriptId": script_id,
+                "version": 1
+            }
+        });
+        let mut response = Vec::new();
+        handle_json_message(
+            &logger,
+            message.to_string().as_bytes(),
+            &mut *world,
+            &mut response,
+        )
+        .unwrap();
+        let response: Value = serde_json::from_slice(&response).unwrap();
+        assert_eq!(response, serde_json::json!({ "ok": null }));
+
+        let command = Command::ListScriptVersions { user_id, script_id };
+        let versions = match execute_command(&logger, &mut *world, command) {
+            CommandResponse::Ok(versions) => versions,
+            CommandResponse::Error(err) => panic!("Failed to list versions {}", err),
+        };
+        assert_eq!(versions[0]["current"], Value::Bool(true));
+        assert_eq!(versions[1]["current"], Value::Bool(false));
+
+        let command = Command::ListScriptVersions {
+            user_id: Uuid::new_v4(),
+            script_id,
+        };
+        assert!(matches!(
+            execute_command(&logger, &mut *world, command),
+            CommandResponse::Error(_)
+        ));
+    }
+
+    #[test]
+    fn construction_sites_can_be_placed() {
+        let (logger, mut world) = test_world();
+        let command: Command = serde_json::from_value(serde_json::json!({
+            "ty": "placeConstructionSite",
+            "userId": Uuid::new_v4(),
+            "kind": "road",
+            "position": { "room": { "q": 0, "r": 0 }, "roomPos": { "q": 8, "r": 8 } }
+        }))
+        .unwrap();
+
+        let site_id = match execute_command(&logger, &mut *world, command.clone()) {
+            CommandResponse::Ok(id) => EntityId(id.as_u64().unwrap() as u32),
+            CommandResponse::Error(err) => panic!("Failed to place the site {}", err),
+        };
+        assert!(world
+            .view::<EntityId, ConstructionSite>()
+            .get_by_id(&site_id)
+            .is_some());
+        // the position is taken by the first site
+        assert!(matches!(
+            execute_command(&logger, &mut *world, command),
+            CommandResponse::Error(_)
+        ));
+    }
+}
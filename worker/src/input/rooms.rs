@@ -1,7 +1,10 @@
 use super::parse_uuid;
 use anyhow::Context;
 use cao_messages::command_capnp::take_room;
+use caolo_sim::components::game_config::GameConfig;
 use caolo_sim::prelude::*;
+use caolo_sim::room_rules::{check_claim_rules, RoomRuleError};
+use caolo_sim::structures;
 use slog::{debug, Logger};
 use thiserror::Error;
 use uuid::Uuid;
@@ -18,6 +21,10 @@ pub enum TakeRoomError {
     InternalError(anyhow::Error),
     #[error("User by id {0} was not registered")]
     NotRegistered(Uuid),
+    #[error("Failed to place the room controller: {0}")]
+    Controller(structures::PlaceStructureError),
+    #[error("{0}")]
+    RoomRules(RoomRuleError),
 }
 
 pub fn take_room(
@@ -72,6 +79,20 @@ pub fn take_room(
         return Err(TakeRoomError::MaxRoomsExceeded(available_rooms as usize));
     }
     let mut rooms = rooms.cloned().unwrap_or_else(|| Rooms::default());
+
+    let rules = world
+        .view::<ConfigKey, GameConfig>()
+        .unwrap_value()
+        .room_rules
+        .clone();
+    check_claim_rules(
+        &rules,
+        Room(room_id),
+        &rooms.0,
+        world.view::<Room, RoomConnections>(),
+    )
+    .map_err(TakeRoomError::RoomRules)?;
+
     rooms.0.push(Room(room_id));
 
     world
@@ -85,6 +106,14 @@ pub fn take_room(
         .with_context(|| "Failed to insert the new owner")
         .map_err(TakeRoomError::InternalError)?;
 
+    // the room is owned as long as its controller stands
+    if let Err(err) = structures::place_controller(world, UserId(user_id), Room(room_id)) {
+        world
+            .unsafe_view::<Room, OwnedEntity>()
+            .delete(&Room(room_id));
+        return Err(TakeRoomError::Controller(err));
+    }
+
     world
         .unsafe_view::<UserId, Rooms>()
         .insert_or_update(UserId(user_id), rooms);
@@ -1,9 +1,12 @@
 use super::parse_uuid;
 use anyhow::Context;
+use cao_lang::prelude::CompilationUnit;
 use cao_messages::command_capnp::{
     set_default_script_command, update_entity_script_command, update_script_command,
 };
+use caolo_sim::components::game_config::GameConfig;
 use caolo_sim::prelude::*;
+use caolo_sim::scripting_api::validation::{validate_program, ValidationErrors};
 use caolo_sim::{self, tables::JoinIterator};
 use slog::{debug, error, Logger};
 use thiserror::Error;
@@ -16,6 +19,13 @@ pub enum UpdateProgramError {
     Internal(anyhow::Error),
     #[error("Invalid message {0:?}")]
     BadMessage(anyhow::Error),
+    /// Serialized as JSON, so clients can parse the errors
+    #[error("{}", serde_json::to_string(.0).unwrap_or_else(|_| .0.to_string()))]
+    InvalidProgram(ValidationErrors),
+    #[error("Script {0:?} has no version {1}")]
+    VersionNotFound(ScriptId, u32),
+    #[error("Script {0:?} was not found")]
+    ScriptNotFound(ScriptId),
 }
 type UpdateResult = Result<(), UpdateProgramError>;
 
@@ -63,9 +73,45 @@ pub fn update_program(
         .with_context(|| "Failed to deserialize CU")
         .map_err(UpdateProgramError::BadMessage)?;
 
-    let program = cao_lang::prelude::compile(logger.clone(), compilation_unit, None)
-        .with_context(|| "Failed to compile script")
-        .map_err(UpdateProgramError::BadMessage)?;
+    insert_program(logger, storage, user_id, script_id, compilation_unit)
+}
+
+/// Validate and compile the program, then store it as the current version of the script
+pub(super) fn insert_program(
+    logger: Logger,
+    storage: &mut World,
+    user_id: UserId,
+    script_id: ScriptId,
+    compilation_unit: CompilationUnit,
+) -> UpdateResult {
+    validate_program(&compilation_unit).map_err(UpdateProgramError::InvalidProgram)?;
+
+    // breadcrumbs are required to meter the script's CPU usage
+    let program = cao_lang::prelude::compile(
+        logger.clone(),
+        compilation_unit,
+        cao_lang::compiler::CompileOptions::new().with_breadcrumbs(true),
+    )
+    .with_context(|| "Failed to compile script")
+    .map_err(UpdateProgramError::BadMessage)?;
+
+    let time = storage.time();
+    let capacity = UnwrapView::<ConfigKey, GameConfig>::new(storage).script_versions_len as usize;
+    let mut versions = storage.unsafe_view::<ScriptId, ScriptVersions>();
+    match versions.get_by_id_mut(&script_id) {
+        Some(versions) => {
+            versions.push(user_id, time, program.clone(), capacity);
+        }
+        None => {
+            let mut v = ScriptVersions::default();
+            v.push(user_id, time, program.clone(), capacity);
+            versions.insert_or_update(script_id, v);
+        }
+    }
+    // the new version starts with a clean slate
+    storage
+        .unsafe_view::<ScriptId, ScriptErrorStats>()
+        .delete(&script_id);
 
     let program = ScriptComponent(program);
     storage
@@ -83,11 +129,96 @@ pub fn update_program(
     Ok(())
 }
 
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptVersionInfo {
+    pub version: u32,
+    pub author: UserId,
+    pub time: u64,
+    pub failed: bool,
+    pub current: bool,
+}
+
+/// Only authors of the script may access its versions
+fn authorized_versions<'a>(
+    versions: &'a View<ScriptId, ScriptVersions>,
+    user_id: UserId,
+    script_id: ScriptId,
+) -> Result<&'a ScriptVersions, UpdateProgramError> {
+    versions
+        .get_by_id(&script_id)
+        .filter(|versions| versions.versions.iter().any(|v| v.author == user_id))
+        .ok_or(UpdateProgramError::Unauthorized)
+}
+
+/// List the stored versions of a script, oldest first
+pub fn list_script_versions(
+    storage: &World,
+    user_id: UserId,
+    script_id: ScriptId,
+) -> Result<Vec<ScriptVersionInfo>, UpdateProgramError> {
+    let versions = storage.view::<ScriptId, ScriptVersions>();
+    let versions = authorized_versions(&versions, user_id, script_id)?;
+    let result = versions
+        .versions
+        .iter()
+        .map(|v| ScriptVersionInfo {
+            version: v.version,
+            author: v.author,
+            time: v.time,
+            failed: v.failed,
+            current: v.version == versions.current,
+        })
+        .collect();
+    Ok(result)
+}
+
+/// Make a stored version of the script the current one
+pub fn rollback_script(
+    logger: Logger,
+    storage: &mut World,
+    user_id: UserId,
+    script_id: ScriptId,
+    version: u32,
+) -> UpdateResult {
+    debug!(
+        logger,
+        "Rolling back script {:?} to version {}", script_id, version
+    );
+    let program = {
+        let versions = storage.view::<ScriptId, ScriptVersions>();
+        let versions = authorized_versions(&versions, user_id, script_id)?;
+        versions
+            .get(version)
+            .map(|v| v.program.clone())
+            .ok_or(UpdateProgramError::VersionNotFound(script_id, version))?
+    };
+
+    if let Some(versions) = storage
+        .unsafe_view::<ScriptId, ScriptVersions>()
+        .get_by_id_mut(&script_id)
+    {
+        versions.current = version;
+        if let Some(v) = versions.get_mut(version) {
+            // explicitly chosen by the player, give it another chance
+            v.failed = false;
+        }
+    }
+    storage
+        .unsafe_view::<ScriptId, ScriptErrorStats>()
+        .delete(&script_id);
+    storage
+        .unsafe_view::<ScriptId, ScriptComponent>()
+        .insert_or_update(script_id, ScriptComponent(program));
+    Ok(())
+}
+
+/// Members of bot groups keep the script of their group
 fn update_user_bot_scripts(
     script_id: ScriptId,
     user_id: UserId,
     mut entity_scripts: UnsafeView<EntityId, EntityScript>,
-    owned_entities: View<EntityId, OwnedEntity>,
+    (owned_entities, groups): (View<EntityId, OwnedEntity>, View<EntityId, BotGroup>),
 ) {
     let entity_scripts = entity_scripts.iter_mut();
     let join = JoinIterator::new(
@@ -96,11 +227,162 @@ fn update_user_bot_scripts(
             .filter(|(_id, owner)| owner.owner_id == user_id),
         entity_scripts,
     );
-    for (_id, (_owner, entity_script)) in join {
-        entity_script.0 = script_id;
+    for (id, (_owner, entity_script)) in join {
+        if groups.get_by_id(&id).is_none() {
+            entity_script.0 = script_id;
+        }
+    }
+}
+
+fn update_group_bot_scripts(
+    script_id: ScriptId,
+    user_id: UserId,
+    group: &str,
+    mut entity_scripts: UnsafeView<EntityId, EntityScript>,
+    (owned_entities, groups): (View<EntityId, OwnedEntity>, View<EntityId, BotGroup>),
+) {
+    let members = groups
+        .iter()
+        .filter(|(_id, BotGroup(g))| g == group)
+        .filter(|(id, _)| {
+            owned_entities
+                .get_by_id(id)
+                .map(|owner| owner.owner_id == user_id)
+                .unwrap_or(false)
+        });
+    for (id, _) in members {
+        entity_scripts.insert_or_update(id, EntityScript(script_id));
     }
 }
 
+/// Attach a script to a bot group of the user, creating the group if it does not exist.
+/// All members of the group are updated to run the script.
+/// The script must have been uploaded by the user.
+pub fn set_group_script(
+    logger: Logger,
+    storage: &mut World,
+    user_id: UserId,
+    group: String,
+    script_id: ScriptId,
+) -> UpdateResult {
+    debug!(
+        logger,
+        "Setting the script of group {} of user {:?} to {:?}", group, user_id, script_id
+    );
+    if !storage
+        .view::<ScriptId, ScriptComponent>()
+        .contains(&script_id)
+    {
+        return Err(UpdateProgramError::ScriptNotFound(script_id));
+    }
+    let versions = storage.view::<ScriptId, ScriptVersions>();
+    authorized_versions(&versions, user_id, script_id)?;
+
+    let mut user_groups = storage.unsafe_view::<UserId, BotGroups>();
+    match user_groups.get_by_id_mut(&user_id) {
+        Some(BotGroups(groups)) => {
+            groups.insert(group.clone(), script_id);
+        }
+        None => {
+            let mut groups = BotGroups::default();
+            groups.0.insert(group.clone(), script_id);
+            user_groups.insert_or_update(user_id, groups);
+        }
+    }
+
+    update_group_bot_scripts(
+        script_id,
+        user_id,
+        group.as_str(),
+        FromWorldMut::new(storage as &mut _),
+        FromWorld::new(storage as &_),
+    );
+    Ok(())
+}
+
+/// Move a bot into a group, or out of its group if `group` is `None`.
+/// Members of groups that have a script are updated to run it, bots leaving a group return to
+/// the default script of the user.
+pub fn assign_bot_group(
+    storage: &mut World,
+    user_id: UserId,
+    entity_id: EntityId,
+    group: Option<String>,
+) -> UpdateResult {
+    storage
+        .view::<EntityId, OwnedEntity>()
+        .get_by_id(&entity_id)
+        .filter(|owner| owner.owner_id == user_id)
+        .ok_or(UpdateProgramError::Unauthorized)?;
+
+    let group = match group {
+        Some(g) => g,
+        None => {
+            let left = storage
+                .unsafe_view::<EntityId, BotGroup>()
+                .delete(&entity_id);
+            if left.is_some() {
+                let default_script = storage
+                    .view::<UserId, EntityScript>()
+                    .get_by_id(&user_id)
+                    .copied();
+                let mut entity_scripts = storage.unsafe_view::<EntityId, EntityScript>();
+                match default_script {
+                    Some(script) => {
+                        entity_scripts.insert_or_update(entity_id, script);
+                    }
+                    None => {
+                        entity_scripts.delete(&entity_id);
+                    }
+                }
+            }
+            return Ok(());
+        }
+    };
+
+    let script_id = storage
+        .view::<UserId, BotGroups>()
+        .get_by_id(&user_id)
+        .and_then(|BotGroups(groups)| groups.get(&group))
+        .copied();
+    if let Some(script_id) = script_id {
+        storage
+            .unsafe_view::<EntityId, EntityScript>()
+            .insert_or_update(entity_id, EntityScript(script_id));
+    }
+    storage
+        .unsafe_view::<EntityId, BotGroup>()
+        .insert_or_update(entity_id, BotGroup(group));
+    Ok(())
+}
+
+/// Script execution history of an entity of the user in the `[from, to]` tick range
+/// (inclusive). The histories of deleted entities are available for
+/// `GameConfig::dead_script_history_ttl` ticks.
+pub fn script_history(
+    storage: &World,
+    user_id: UserId,
+    entity_id: EntityId,
+    from: u64,
+    to: u64,
+) -> Result<Vec<ScriptHistoryEntry>, UpdateProgramError> {
+    let history = match storage
+        .view::<EntityId, OwnedEntity>()
+        .get_by_id(&entity_id)
+    {
+        Some(owner) if owner.owner_id != user_id => return Err(UpdateProgramError::Unauthorized),
+        Some(_) => storage
+            .script_history(entity_id, from, to)
+            .cloned()
+            .collect(),
+        None => storage
+            .dead_script_history(user_id, entity_id, from, to)
+            .cloned()
+            .collect(),
+    };
+    Ok(history)
+}
+
 pub fn update_entity_script(
     storage: &mut World,
     msg: &update_entity_script_command::Reader,
@@ -172,3 +454,218 @@ pub fn set_default_script(
 
     Ok(())
 }
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use caolo_sim::harness::{build_world, Fixture};
+    use slog::o;
+    use std::pin::Pin;
+
+    pub fn test_world() -> (Logger, Pin<Box<World>>) {
+        let logger = Logger::root(slog::Discard, o!());
+        let fixture: Fixture = serde_json::from_str(
+            r#"{ "rooms": [ { "room": { "q": 0, "r": 0 }, "defaultTerrain": "plain" } ] }"#,
+        )
+        .expect("Failed to parse fixture");
+        let world = build_world(logger.clone(), &fixture).expect("Failed to build the world");
+        (logger, world)
+    }
+
+    pub fn program(cards: &str) -> CompilationUnit {
+        let cu = format!(
+            r#"{{ "lanes": [ {{ "name": "main", "cards": {} }} ] }}"#,
+            cards
+        );
+        serde_json::from_str(&cu).expect("Failed to parse program")
+    }
+
+    #[test]
+    fn invalid_programs_are_rejected() {
+        let (logger, mut world) = test_world();
+        let user_id = UserId(uuid::Uuid::new_v4());
+        let script_id = ScriptId(uuid::Uuid::new_v4());
+
+        let cu = program(r#"[ { "ScalarInt": 1 }, { "Call": "Launch Rockets" } ]"#);
+        let err = insert_program(logger, &mut *world, user_id, script_id, cu)
+            .expect_err("Expected the program to be rejected");
+
+        match err {
+            UpdateProgramError::InvalidProgram(ref errors) => {
+                assert_eq!(errors.validation_errors.len(), 1)
+            }
+            _ => panic!("Expected validation errors, got {:?}", err),
+        }
+        // the response carries the errors as JSON
+        let response: serde_json::Value =
+            serde_json::from_str(&err.to_string()).expect("Expected a JSON response");
+        assert_eq!(
+            response["validationErrors"][0]["name"].as_str(),
+            Some("Launch Rockets")
+        );
+        assert!(world
+            .view::<ScriptId, ScriptComponent>()
+            .get_by_id(&script_id)
+            .is_none());
+    }
+
+    #[test]
+    fn versions_can_be_listed_and_rolled_back() {
+        let (logger, mut world) = test_world();
+        let user_id = UserId(uuid::Uuid::new_v4());
+        let script_id = ScriptId(uuid::Uuid::new_v4());
+
+        for time in 1..=2 {
+            world.resources.time.value = Some(caolo_sim::Time(time));
+            let cu = program(r#"[ { "StringLiteral": "hello" }, { "Call": "Console Log" } ]"#);
+            insert_program(logger.clone(), &mut *world, user_id, script_id, cu)
+                .expect("Failed to insert the program");
+        }
+
+        let versions = list_script_versions(&*world, user_id, script_id).unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].version, 1);
+        assert_eq!(versions[0].time, 1);
+        assert!(!versions[0].current);
+        assert_eq!(versions[1].time, 2);
+        assert!(versions[1].current);
+
+        let intruder = UserId(uuid::Uuid::new_v4());
+        assert!(matches!(
+            list_script_versions(&*world, intruder, script_id),
+            Err(UpdateProgramError::Unauthorized)
+        ));
+        assert!(matches!(
+            rollback_script(logger.clone(), &mut *world, intruder, script_id, 1),
+            Err(UpdateProgramError::Unauthorized)
+        ));
+        assert!(matches!(
+            rollback_script(logger.clone(), &mut *world, user_id, script_id, 3),
+            Err(UpdateProgramError::VersionNotFound(_, 3))
+        ));
+
+        rollback_script(logger, &mut *world, user_id, script_id, 1).expect("Failed to roll back");
+        let versions = list_script_versions(&*world, user_id, script_id).unwrap();
+        assert!(versions[0].current);
+        assert!(!versions[1].current);
+        assert!(world
+            .view::<ScriptId, ScriptComponent>()
+            .get_by_id(&script_id)
+            .is_some());
+    }
+
+    #[test]
+    fn group_scripts_are_assigned_and_removed() {
+        let (logger, mut world) = test_world();
+        let user_id = UserId(uuid::Uuid::new_v4());
+        let default_script = ScriptId(uuid::Uuid::new_v4());
+        let group_script = ScriptId(uuid::Uuid::new_v4());
+        for script_id in [default_script, group_script].iter() {
+            let cu = program(r#"[ { "StringLiteral": "hello" }, { "Call": "Console Log" } ]"#);
+            insert_program(logger.clone(), &mut *world, user_id, *script_id, cu).unwrap();
+        }
+        world
+            .unsafe_view::<UserId, EntityScript>()
+            .insert_or_update(user_id, EntityScript(default_script));
+        let bot = world.insert_entity();
+        world
+            .unsafe_view::<EntityId, OwnedEntity>()
+            .insert_or_update(bot, OwnedEntity { owner_id: user_id });
+        world
+            .unsafe_view::<EntityId, EntityScript>()
+            .insert_or_update(bot, EntityScript(default_script));
+
+        let intruder = UserId(uuid::Uuid::new_v4());
+        assert!(matches!(
+            set_group_script(
+                logger.clone(),
+                &mut *world,
+                intruder,
+                "miners".to_owned(),
+                group_script
+            ),
+            Err(UpdateProgramError::Unauthorized)
+        ));
+        let missing = ScriptId(uuid::Uuid::new_v4());
+        assert!(matches!(
+            set_group_script(
+                logger.clone(),
+                &mut *world,
+                user_id,
+                "miners".to_owned(),
+                missing
+            ),
+            Err(UpdateProgramError::ScriptNotFound(_))
+        ));
+        assert!(matches!(
+            assign_bot_group(&mut *world, intruder, bot, Some("miners".to_owned())),
+            Err(UpdateProgramError::Unauthorized)
+        ));
+
+        let script_of = |world: &World| {
+            world
+                .view::<EntityId, EntityScript>()
+                .get_by_id(&bot)
+                .map(|EntityScript(id)| *id)
+        };
+
+        assign_bot_group(&mut *world, user_id, bot, Some("miners".to_owned())).unwrap();
+        assert_eq!(script_of(&*world), Some(default_script));
+        set_group_script(
+            logger,
+            &mut *world,
+            user_id,
+            "miners".to_owned(),
+            group_script,
+        )
+        .unwrap();
+        assert_eq!(script_of(&*world), Some(group_script));
+
+        assign_bot_group(&mut *world, user_id, bot, None).unwrap();
+        assert_eq!(script_of(&*world), Some(default_script));
+        assert!(world.view::<EntityId, BotGroup>().get_by_id(&bot).is_none());
+    }
+
+    #[test]
+    fn script_history_is_available_after_death() {
+        let (_logger, mut world) = test_world();
+        let user_id = UserId(uuid::Uuid::new_v4());
+        let bot = world.insert_entity();
+        world
+            .unsafe_view::<EntityId, OwnedEntity>()
+            .insert_or_update(bot, OwnedEntity { owner_id: user_id });
+        let mut history = ScriptHistory::default();
+        history.push(
+            ScriptHistoryEntry {
+                entity_id: bot,
+                payload: vec![],
+                time: 0,
+            },
+            4,
+        );
+        world
+            .unsafe_view::<EntityId, ScriptHistory>()
+            .insert_or_update(bot, history);
+
+        let intruder = UserId(uuid::Uuid::new_v4());
+        assert!(matches!(
+            script_history(&*world, intruder, bot, 0, 10),
+            Err(UpdateProgramError::Unauthorized)
+        ));
+        assert_eq!(
+            script_history(&*world, user_id, bot, 0, 10).unwrap().len(),
+            1
+        );
+
+        world.deferred_delete(bot);
+        world.post_process();
+
+        assert_eq!(
+            script_history(&*world, user_id, bot, 0, 10).unwrap().len(),
+            1
+        );
+        assert!(script_history(&*world, intruder, bot, 0, 10)
+            .unwrap()
+            .is_empty());
+    }
+}
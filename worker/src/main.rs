@@ -42,53 +42,29 @@ async fn send_schema<'a>(
     queen_tag: Uuid,
 ) -> anyhow::Result<()> {
     debug!(logger, "Sending schema");
-    let schema = caolo_sim::scripting_api::make_import();
-    let imports = schema.imports();
-
-    let basic_descs = cao_lang::compiler::description::get_instruction_descriptions();
-
-    #[derive(serde::Serialize)]
-    struct Card<'a> {
-        name: &'a str,
-        description: &'a str,
-        ty: &'a str,
-        input: &'a [&'a str],
-        output: &'a [&'a str],
-        constants: &'a [&'a str],
-    }
-
-    let msg = imports
-        .iter()
-        .map(|import| Card {
-            name: import.desc.name,
-            description: import.desc.description,
-            constants: &*import.desc.constants,
-            input: &*import.desc.input,
-            output: &*import.desc.output,
-            ty: import.desc.ty.as_str(),
-        })
-        .chain(basic_descs.iter().map(|card| Card {
-            name: card.name,
-            description: card.description,
-            input: &*card.input,
-            output: &*card.output,
-            constants: &*card.constants,
-            ty: card.ty.as_str(),
-        }))
-        .collect::<Vec<_>>();
+    let schema = caolo_sim::scripting_api::schema::export_schema();
+    debug!(
+        logger,
+        "Schema version: {} hash: {}", schema.version, schema.hash
+    );
 
-    let payload = serde_json::to_value(&msg)?;
+    // `payload` keeps the plain list of cards for existing clients, the versioned document goes
+    // into its own column
+    let payload = serde_json::to_value(&schema.cards)?;
+    let document = serde_json::to_value(&schema)?;
 
     sqlx::query!(
         r#"
-    INSERT INTO scripting_schema (queen_tag, payload)
-    VALUES ($1, $2)
+    INSERT INTO scripting_schema (queen_tag, payload, document)
+    VALUES ($1, $2, $3)
     ON CONFLICT (queen_tag)
     DO UPDATE SET
-    payload=$2
+    payload=$2,
+    document=$3
         "#,
         queen_tag,
-        payload
+        payload,
+        document
     )
     .execute(connection)
     .await
@@ -98,24 +74,39 @@ async fn send_schema<'a>(
     Ok(())
 }
 
-async fn output<'a>(
-    world: &'a World,
-    connection: impl sqlx::Executor<'a, Database = sqlx::Postgres>,
-    queen_tag: Uuid,
-) -> anyhow::Result<()> {
-    let payload = world.as_json();
+/// Publish the world as seen by each user, see `World::as_json_by_user`.
+/// The outputs of a tick are inserted in a single statement, so the cleanup trigger of the table
+/// runs once per tick.
+async fn output(world: &World, db: &sqlx::PgPool, queen_tag: Uuid) -> anyhow::Result<()> {
+    let world_time = world.time() as i64;
+    let outputs = world
+        .as_json_by_user()
+        .into_iter()
+        .map(|(user_id, payload)| {
+            serde_json::json!({
+                "userId": user_id.0,
+                "payload": payload
+            })
+        })
+        .collect::<Vec<_>>();
+    if outputs.is_empty() {
+        return Ok(());
+    }
+    let outputs = serde_json::Value::Array(outputs);
+
     sqlx::query!(
         r#"
-        INSERT INTO world_output (queen_tag, world_time, payload)
-        VALUES ($1, $2, $3);
+    INSERT INTO world_output (queen_tag, world_time, user_id, payload)
+    SELECT $1::UUID, $2::BIGINT, (o->>'userId')::UUID, o->'payload'
+    FROM jsonb_array_elements($3::JSONB) AS o;
         "#,
         queen_tag,
-        world.time() as i64,
-        payload
+        world_time,
+        outputs
     )
-    .execute(connection)
+    .execute(db)
     .await
-    .with_context(|| "Failed to insert current world state")?;
+    .with_context(|| "Failed to insert the world output")?;
     Ok(())
 }
 